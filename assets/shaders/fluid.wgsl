@@ -0,0 +1,22 @@
+#import bevy_sprite::mesh2d_vertex_output::VertexOutput
+
+struct FluidMaterial {
+    color: vec4<f32>,
+    threshold: f32,
+};
+
+@group(1) @binding(0) var<uniform> material: FluidMaterial;
+@group(1) @binding(1) var field_texture: texture_2d<f32>;
+@group(1) @binding(2) var field_sampler: sampler;
+
+@fragment
+fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
+    let field = textureSample(field_texture, field_sampler, mesh.uv).r;
+    // fwidth keeps the edge about a pixel wide at any zoom
+    let edge = max(fwidth(field), 0.001);
+    let coverage = smoothstep(material.threshold - edge, material.threshold + edge, field);
+    // a little lighter near the surface, deeper in is the plain color
+    let depth = smoothstep(material.threshold, 1.0, field);
+    let color = mix(min(material.color.rgb * 1.3, vec3<f32>(1.0)), material.color.rgb, depth);
+    return vec4<f32>(color, material.color.a * coverage);
+}
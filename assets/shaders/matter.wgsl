@@ -1,31 +1,96 @@
+#import bevy_sprite::mesh2d_vertex_output::VertexOutput
+
 struct MatterMaterial {
     color: vec4<f32>,
-    strokeColor: vec4<f32>,
-    strokeWidth : f32
+    stroke_color: vec4<f32>,
+    stroke_width: f32,
+    size: vec2<f32>,
+    fill_mode: u32,
+    tile_size: f32,
+    mask: u32,
 };
 
+const FILL_STRETCH: u32 = 0u;
+const FILL_FIT: u32 = 1u;
+const FILL_TILE: u32 = 2u;
+
+const MASK_TEXTURE: u32 = 0u;
+const MASK_ELLIPSE: u32 = 1u;
+
 @group(1) @binding(0) var<uniform> material: MatterMaterial;
 @group(1) @binding(1) var color_texture: texture_2d<f32>;
 @group(1) @binding(2) var color_sampler: sampler;
+@group(1) @binding(3) var fill_texture: texture_2d<f32>;
+@group(1) @binding(4) var fill_sampler: sampler;
 
+// anything off the edge of the quad counts as outside the shape
 fn get_sample(probe: vec2<f32>) -> f32 {
-    return textureSample(color_texture, color_sampler, probe).a;
+    let inside = all(probe >= vec2<f32>(0.0)) && all(probe <= vec2<f32>(1.0));
+    return select(0.0, textureSampleLevel(color_texture, color_sampler, probe, 0.0).a, inside);
+}
+
+// color of the fill image at this uv, depending on the fill mode
+fn get_fill(uv: vec2<f32>) -> vec4<f32> {
+    let dims = vec2<f32>(textureDimensions(fill_texture));
+    var fill_uv = uv;
+    if (material.fill_mode == FILL_FIT) {
+        // scale the image so all of it fits, keeping its aspect ratio
+        let scale = min(material.size.x / dims.x, material.size.y / dims.y);
+        fill_uv = (uv - vec2<f32>(0.5)) * material.size / (dims * scale) + vec2<f32>(0.5);
+        if (any(fill_uv < vec2<f32>(0.0)) || any(fill_uv > vec2<f32>(1.0))) {
+            return vec4<f32>(1.0);
+        }
+    } else if (material.fill_mode == FILL_TILE) {
+        let tile = vec2<f32>(material.tile_size, material.tile_size * dims.y / dims.x);
+        fill_uv = fract(uv * material.size / max(tile, vec2<f32>(0.0001)));
+    }
+    return textureSampleLevel(fill_texture, fill_sampler, fill_uv, 0.0);
+}
+
+// roughly how far outside the ellipse filling the quad this is, in world units (exact for circles)
+fn ellipse_distance(uv: vec2<f32>) -> f32 {
+    let radii = max(material.size * 0.5, vec2<f32>(0.0001));
+    let p = (uv - vec2<f32>(0.5)) * material.size;
+    return (length(p / radii) - 1.0) * min(radii.x, radii.y);
 }
 
-[[stage(fragment)]]
-fn fragment(in_uv : [[location(0)]] vec2<f32>) -> [[location(0)]] vec4<f32> {
-    var uv = in_uv;
-    var stroke : f32 = get_sample(uv + vec2<f32>(material.strokeWidth,0.0));
-    stroke += get_sample(uv + vec2<f32>(-material.strokeWidth,0.0));
-    stroke += get_sample(uv + vec2<f32>(0.0,material.strokeWidth));
-    stroke += get_sample(uv + vec2<f32>(0.0,-material.strokeWidth));
-    stroke += get_sample(uv + vec2<f32>(material.strokeWidth,-material.strokeWidth));
-    stroke += get_sample(uv + vec2<f32>(-material.strokeWidth,material.strokeWidth));
-    stroke += get_sample(uv + vec2<f32>(material.strokeWidth,material.strokeWidth));
-    stroke += get_sample(uv + vec2<f32>(-material.strokeWidth,-material.strokeWidth));
-    stroke = min(stroke, 1.0);
-    if (textureSample(color_texture, color_sampler, uv).a > stroke) {
-        return material.color;  // return the fill color inside the object
+@fragment
+fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
+    let uv = mesh.uv;
+
+    // derivatives have to be taken before anything gets discarded
+    let edge_distance = ellipse_distance(uv);
+    let pixel = max(fwidth(edge_distance), 0.0001);
+    if (material.mask == MASK_ELLIPSE) {
+        // coverage of the pixel, so the edge is antialiased whatever the zoom
+        let coverage = clamp(0.5 - edge_distance / pixel, 0.0, 1.0);
+        if (coverage <= 0.0) {
+            discard;
+        }
+        let stroke = clamp(0.5 + (edge_distance + material.stroke_width) / pixel, 0.0, 1.0);
+        let fill = get_fill(uv);
+        let fill_color = vec4<f32>(material.color.rgb * fill.rgb, material.color.a);
+        let color = mix(fill_color, material.stroke_color, stroke);
+        return vec4<f32>(color.rgb, color.a * coverage);
+    }
+
+    if (get_sample(uv) < 0.5) {
+        discard;
     }
-    return material.strokeColor;  // return the stroke color at the boundary
-}
\ No newline at end of file
+
+    // stroke width is in world units, turn it into uv units for each axis
+    let d = material.stroke_width / max(material.size, vec2<f32>(0.0001));
+    var edge: f32 = get_sample(uv + vec2<f32>(d.x, 0.0));
+    edge = min(edge, get_sample(uv + vec2<f32>(-d.x, 0.0)));
+    edge = min(edge, get_sample(uv + vec2<f32>(0.0, d.y)));
+    edge = min(edge, get_sample(uv + vec2<f32>(0.0, -d.y)));
+    edge = min(edge, get_sample(uv + vec2<f32>(d.x, -d.y)));
+    edge = min(edge, get_sample(uv + vec2<f32>(-d.x, d.y)));
+    edge = min(edge, get_sample(uv + vec2<f32>(d.x, d.y)));
+    edge = min(edge, get_sample(uv + vec2<f32>(-d.x, -d.y)));
+    if (edge < 0.5) {
+        return material.stroke_color; // return the stroke color at the boundary
+    }
+    let fill = get_fill(uv);
+    return vec4<f32>(material.color.rgb * fill.rgb, material.color.a); // return the fill color inside the object
+}
@@ -0,0 +1,497 @@
+//! Built-in stress scenes for keeping an eye on physics performance. They run from the
+//! "Benchmarks" window, or without a window with `simulo --benchmark [name...]`.
+
+use std::collections::VecDeque;
+
+use bevy::diagnostic::DiagnosticsStore;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use bevy_rapier2d::prelude::*;
+
+use crate::fluid::Fluid;
+use crate::matter::Matter;
+use crate::performance::PHYSICS_STEP_TIME;
+use crate::ragdoll::spawn_person;
+use crate::rope::Rope;
+use crate::soft_body::SoftBody;
+use crate::water::WaterRegion;
+use crate::LaserPointer;
+
+/// How many physics steps each benchmark is measured over
+const FRAMES: u32 = 600;
+// same ground as the default scene
+const GROUND_CENTER: Vec2 = Vec2::new(0., -1000.);
+const GROUND_HALF_SIZE: Vec2 = Vec2::new(5000., 500.);
+const GROUND_TOP: f32 = GROUND_CENTER.y + GROUND_HALF_SIZE.y;
+
+const PYRAMID_ROWS: u32 = 40;
+const RAGDOLL_COLUMNS: u32 = 10;
+const RAGDOLL_ROWS: u32 = 20;
+const CHAINS: u32 = 4;
+const CHAIN_LINKS: u32 = 150;
+const RAIN_BODIES: u32 = 10_000;
+const RAIN_PER_FRAME: u32 = 50;
+const FLUID_PARTICLES: usize = 3000;
+const FLUID_TANK_HALF_WIDTH: f32 = 90.;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Benchmark {
+    BoxPyramid,
+    RagdollPile,
+    JointChain,
+    Rain,
+    Fluid,
+}
+
+impl Benchmark {
+    pub const ALL: [Benchmark; 5] = [
+        Benchmark::BoxPyramid,
+        Benchmark::RagdollPile,
+        Benchmark::JointChain,
+        Benchmark::Rain,
+        Benchmark::Fluid,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Benchmark::BoxPyramid => "Box pyramid",
+            Benchmark::RagdollPile => "Ragdoll pile",
+            Benchmark::JointChain => "Joint chains",
+            Benchmark::Rain => "10k body rain",
+            Benchmark::Fluid => "Fluid dam break",
+        }
+    }
+
+    /// What it's called on the command line
+    pub fn id(self) -> &'static str {
+        match self {
+            Benchmark::BoxPyramid => "pyramid",
+            Benchmark::RagdollPile => "ragdolls",
+            Benchmark::JointChain => "chain",
+            Benchmark::Rain => "rain",
+            Benchmark::Fluid => "fluid",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|benchmark| benchmark.id() == id)
+    }
+
+    fn setup(self, commands: &mut Commands, asset_server: &Res<AssetServer>, fluid: &mut Fluid) {
+        commands.spawn((
+            SpatialBundle::from_transform(Transform::from_translation(GROUND_CENTER.extend(0.))),
+            Matter::new(Color::rgb(
+                0.7254901960784313,
+                0.6313725490196078,
+                0.7686274509803922,
+            )),
+            Collider::cuboid(GROUND_HALF_SIZE.x, GROUND_HALF_SIZE.y),
+        ));
+
+        match self {
+            Benchmark::BoxPyramid => {
+                for row in 0..PYRAMID_ROWS {
+                    let count = PYRAMID_ROWS - row;
+                    let left = -(count as f32 - 1.) * 4.2 / 2.;
+                    for i in 0..count {
+                        spawn_box(
+                            commands,
+                            Vec2::new(left + i as f32 * 4.2, GROUND_TOP + 2. + row as f32 * 4.),
+                            Color::rgb(0.75, 0.25, 0.25),
+                        );
+                    }
+                }
+            }
+            Benchmark::RagdollPile => {
+                for row in 0..RAGDOLL_ROWS {
+                    for column in 0..RAGDOLL_COLUMNS {
+                        // every other row is shifted so they land on each other instead of stacking neatly
+                        let offset = if row % 2 == 0 { 0. } else { 4. };
+                        let position = Vec2::new(
+                            (column as f32 - RAGDOLL_COLUMNS as f32 / 2.) * 8. + offset,
+                            GROUND_TOP + 20. + row as f32 * 20.,
+                        );
+                        spawn_person(
+                            commands,
+                            asset_server,
+                            Color::rgb(0.82, 0.64, 0.52),
+                            position,
+                            false,
+                        );
+                    }
+                }
+            }
+            Benchmark::JointChain => {
+                for chain in 0..CHAINS {
+                    let anchor_position =
+                        Vec2::new((chain as f32 - CHAINS as f32 / 2.) * 80., GROUND_TOP + 700.);
+                    let mut previous = commands
+                        .spawn((
+                            SpatialBundle::from_transform(Transform::from_translation(
+                                anchor_position.extend(0.),
+                            )),
+                            Matter::new(Color::rgb(0.25, 0.25, 0.75)),
+                            Collider::cuboid(2., 2.),
+                        ))
+                        .id();
+                    // starts out sideways so the whole thing swings down
+                    for link in 0..CHAIN_LINKS {
+                        let position = anchor_position + Vec2::new(2. + link as f32 * 4., 0.);
+                        let joint = RevoluteJointBuilder::new()
+                            .local_anchor1(Vec2::new(if link == 0 { 0. } else { 2. }, 0.))
+                            .local_anchor2(Vec2::new(-2., 0.));
+                        previous = commands
+                            .spawn((
+                                SpatialBundle::from_transform(Transform::from_translation(
+                                    position.extend(0.),
+                                )),
+                                Matter::new(Color::rgb(0.6, 0.6, 0.65)),
+                                Collider::cuboid(2., 0.5),
+                                RigidBody::Dynamic,
+                                ImpulseJoint::new(previous, joint),
+                            ))
+                            .id();
+                    }
+                }
+            }
+            // spawned over time in `update`
+            Benchmark::Rain => {}
+            Benchmark::Fluid => {
+                // a tank with a column of fluid against one wall and some boxes to knock around
+                for side in [-1., 1.] {
+                    commands.spawn((
+                        SpatialBundle::from_transform(Transform::from_translation(Vec3::new(
+                            side * (FLUID_TANK_HALF_WIDTH + 2.),
+                            GROUND_TOP + 100.,
+                            0.,
+                        ))),
+                        Matter::new(Color::rgb(0.4, 0.4, 0.45)),
+                        Collider::cuboid(2., 100.),
+                    ));
+                }
+                fluid.fill_rect(
+                    Rect::new(-FLUID_TANK_HALF_WIDTH, GROUND_TOP, 0., GROUND_TOP + 300.),
+                    FLUID_PARTICLES,
+                );
+                for i in 0..6 {
+                    spawn_box(
+                        commands,
+                        Vec2::new(10. + i as f32 * 12., GROUND_TOP + 20.),
+                        Color::rgb(0.75, 0.55, 0.25),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Called once per measured frame
+    fn update(self, commands: &mut Commands, frame: u32) {
+        if self != Benchmark::Rain {
+            return;
+        }
+        for i in 0..RAIN_PER_FRAME {
+            let index = frame * RAIN_PER_FRAME + i;
+            if index >= RAIN_BODIES {
+                return;
+            }
+            // a low discrepancy sequence spreads them out without needing an rng, so every run is the same
+            let spread = Vec2::new(
+                (0.5 + index as f32 * 0.754_877_7).fract(),
+                (0.5 + index as f32 * 0.569_840_3).fract(),
+            );
+            let position = Vec2::new(-600., GROUND_TOP + 800.) + spread * Vec2::new(1200., 400.);
+            let collider = if index % 2 == 0 {
+                Collider::cuboid(2., 2.)
+            } else {
+                Collider::ball(2.)
+            };
+            // already falling, so they're out of the way of the next batch
+            commands.spawn((
+                SpatialBundle::from_transform(Transform::from_translation(position.extend(0.))),
+                Matter::new(Color::rgb(0.25, 0.55, 0.75)),
+                collider,
+                RigidBody::Dynamic,
+                Velocity::linear(Vec2::new(0., -150.)),
+            ));
+        }
+    }
+}
+
+fn spawn_box(commands: &mut Commands, position: Vec2, color: Color) {
+    commands.spawn((
+        SpatialBundle::from_transform(Transform::from_translation(position.extend(0.))),
+        Matter::new(color),
+        Collider::cuboid(2., 2.),
+        RigidBody::Dynamic,
+    ));
+}
+
+#[derive(Debug, Clone)]
+pub struct BenchmarkResult {
+    pub benchmark: Benchmark,
+    pub frames: u32,
+    /// rapier bodies at the end, including fixed ones
+    pub bodies: usize,
+    pub average_step_ms: f64,
+    pub max_step_ms: f64,
+}
+
+impl std::fmt::Display for BenchmarkResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:<16} {:>6} bodies  avg {:>8.3} ms  max {:>8.3} ms  ({} steps)",
+            self.benchmark.name(),
+            self.bodies,
+            self.average_step_ms,
+            self.max_step_ms,
+            self.frames
+        )
+    }
+}
+
+struct RunningBenchmark {
+    benchmark: Benchmark,
+    frame: u32,
+    step_times: Vec<f64>,
+}
+
+#[derive(Resource, Default)]
+pub struct BenchmarkRunner {
+    queue: VecDeque<Benchmark>,
+    running: Option<RunningBenchmark>,
+    pub results: Vec<BenchmarkResult>,
+}
+
+impl BenchmarkRunner {
+    pub fn queue(&mut self, benchmarks: impl IntoIterator<Item = Benchmark>) {
+        self.queue.extend(benchmarks);
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.running.is_none() && self.queue.is_empty()
+    }
+}
+
+pub struct BenchmarkPlugin;
+
+impl Plugin for BenchmarkPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BenchmarkRunner>()
+            .add_systems(Update, benchmarks_ui)
+            .add_systems(Last, run_benchmarks);
+    }
+}
+
+// runs in Last so the step time for this frame is already in the diagnostics
+fn run_benchmarks(
+    mut commands: Commands,
+    mut runner: ResMut<BenchmarkRunner>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+    asset_server: Res<AssetServer>,
+    diagnostics: Res<DiagnosticsStore>,
+    rapier_context: Res<RapierContext>,
+    mut fluid: ResMut<Fluid>,
+    existing: Query<
+        Entity,
+        (
+            Or<(With<Matter>, With<WaterRegion>, With<SoftBody>, With<Rope>)>,
+            Without<LaserPointer>,
+        ),
+    >,
+) {
+    if runner.is_idle() {
+        return;
+    }
+    let runner = &mut *runner;
+
+    let Some(running) = runner.running.as_mut() else {
+        let Some(benchmark) = runner.queue.pop_front() else {
+            return;
+        };
+        // every benchmark gets an empty world to itself
+        for entity in existing.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        fluid.clear();
+        benchmark.setup(&mut commands, &asset_server, &mut fluid);
+        rapier_config.physics_pipeline_active = true;
+        runner.running = Some(RunningBenchmark {
+            benchmark,
+            frame: 0,
+            step_times: Vec::with_capacity(FRAMES as usize),
+        });
+        return;
+    };
+
+    // the first step also has to build everything in rapier, leave it out
+    if running.frame > 0 {
+        if let Some(step) = diagnostics
+            .get(PHYSICS_STEP_TIME)
+            .and_then(|step| step.value())
+        {
+            running.step_times.push(step);
+        }
+    }
+    running.benchmark.update(&mut commands, running.frame);
+    running.frame += 1;
+    if running.frame <= FRAMES {
+        return;
+    }
+
+    let steps = running.step_times.len().max(1) as f64;
+    let result = BenchmarkResult {
+        benchmark: running.benchmark,
+        frames: running.step_times.len() as u32,
+        bodies: rapier_context.bodies.len(),
+        average_step_ms: running.step_times.iter().sum::<f64>() / steps,
+        max_step_ms: running.step_times.iter().copied().fold(0., f64::max),
+    };
+    info!("{}", result);
+    runner.results.push(result);
+    runner.running = None;
+}
+
+fn benchmarks_ui(mut contexts: EguiContexts, mut runner: ResMut<BenchmarkRunner>) {
+    egui::Window::new("Benchmarks")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label("Replaces the current scene!");
+            ui.add_enabled_ui(runner.is_idle(), |ui| {
+                ui.horizontal_wrapped(|ui| {
+                    for benchmark in Benchmark::ALL {
+                        if ui.button(benchmark.name()).clicked() {
+                            runner.queue([benchmark]);
+                        }
+                    }
+                });
+                if ui.button("Run all").clicked() {
+                    runner.queue(Benchmark::ALL);
+                }
+            });
+
+            if let Some(running) = runner.running.as_ref() {
+                ui.label(format!(
+                    "Running {}... {}/{}",
+                    running.benchmark.name(),
+                    running.frame,
+                    FRAMES
+                ));
+            }
+
+            if runner.results.is_empty() {
+                return;
+            }
+            ui.separator();
+            egui::Grid::new("benchmark_results")
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.strong("Scene");
+                    ui.strong("Bodies");
+                    ui.strong("Avg step");
+                    ui.strong("Max step");
+                    ui.end_row();
+                    for result in runner.results.iter() {
+                        ui.label(result.benchmark.name());
+                        ui.label(result.bodies.to_string());
+                        ui.label(format!("{:.3} ms", result.average_step_ms));
+                        ui.label(format!("{:.3} ms", result.max_step_ms));
+                        ui.end_row();
+                    }
+                });
+            if ui.button("Clear results").clicked() {
+                runner.results.clear();
+            }
+        });
+}
+
+/// Benchmarks asked for with `--benchmark`, all of them if none are named
+#[cfg(not(target_arch = "wasm32"))]
+pub fn from_args() -> Option<Result<Vec<Benchmark>, String>> {
+    let mut args = std::env::args().skip_while(|arg| arg != "--benchmark");
+    args.next()?;
+    let names: Vec<String> = args.take_while(|arg| !arg.starts_with("--")).collect();
+    if names.is_empty() || names.iter().any(|name| name == "all") {
+        return Some(Ok(Benchmark::ALL.to_vec()));
+    }
+    Some(
+        names
+            .iter()
+            .map(|name| {
+                Benchmark::from_id(name).ok_or_else(|| {
+                    let ids: Vec<&str> = Benchmark::ALL.iter().map(|b| b.id()).collect();
+                    format!(
+                        "Unknown benchmark {}, try one of: all {}",
+                        name,
+                        ids.join(" ")
+                    )
+                })
+            })
+            .collect(),
+    )
+}
+
+/// Runs the benchmarks with no window or renderer, prints the results and exits
+#[cfg(not(target_arch = "wasm32"))]
+pub fn run_headless(benchmarks: Vec<Benchmark>) {
+    use bevy::app::{AppExit, ScheduleRunnerPlugin};
+    use bevy::audio::AudioPlugin;
+    use bevy::render::settings::WgpuSettings;
+    use bevy::render::RenderPlugin;
+    use bevy::utils::Duration;
+    use bevy::window::ExitCondition;
+    use bevy::winit::WinitPlugin;
+    use bevy_embedded_assets::{EmbeddedAssetPlugin, PluginMode};
+
+    fn exit_when_done(runner: Res<BenchmarkRunner>, mut exit: EventWriter<AppExit>) {
+        if !runner.is_idle() {
+            return;
+        }
+        for result in runner.results.iter() {
+            println!("{}", result);
+        }
+        exit.send(AppExit);
+    }
+
+    let mut runner = BenchmarkRunner::default();
+    runner.queue(benchmarks);
+
+    App::new()
+        .add_plugins(EmbeddedAssetPlugin {
+            mode: PluginMode::ReplaceDefault,
+        })
+        .add_plugins(
+            DefaultPlugins
+                .set(WindowPlugin {
+                    primary_window: None,
+                    exit_condition: ExitCondition::DontExit,
+                    close_when_requested: false,
+                })
+                .set(RenderPlugin {
+                    render_creation: WgpuSettings {
+                        backends: None,
+                        ..default()
+                    }
+                    .into(),
+                })
+                .disable::<WinitPlugin>()
+                // servers usually have no sound card, and there's nothing to hear
+                .disable::<AudioPlugin>(),
+        )
+        .add_plugins(ScheduleRunnerPlugin::run_loop(Duration::ZERO))
+        .add_plugins(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(12.0))
+        .add_plugins(crate::performance::StepTimePlugin)
+        .add_plugins(crate::fluid::FluidSimulationPlugin)
+        .add_plugins(crate::sound::SoundPlugin)
+        // one step per update no matter how fast we go, so runs compare
+        .insert_resource(RapierConfiguration {
+            timestep_mode: TimestepMode::Fixed {
+                dt: 1. / 60.,
+                substeps: 1,
+            },
+            ..default()
+        })
+        .insert_resource(runner)
+        .add_systems(Last, (run_benchmarks, exit_when_done).chain())
+        .run();
+}
@@ -0,0 +1,119 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// What a body is made of. Sets friction, bounciness and density on all its colliders.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum BodyMaterial {
+    #[default]
+    Plastic,
+    Wood,
+    Metal,
+    Rubber,
+    Ice,
+    Stone,
+    Glass,
+}
+
+impl BodyMaterial {
+    pub const ALL: [BodyMaterial; 7] = [
+        BodyMaterial::Plastic,
+        BodyMaterial::Wood,
+        BodyMaterial::Metal,
+        BodyMaterial::Rubber,
+        BodyMaterial::Ice,
+        BodyMaterial::Stone,
+        BodyMaterial::Glass,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BodyMaterial::Plastic => "Plastic",
+            BodyMaterial::Wood => "Wood",
+            BodyMaterial::Metal => "Metal",
+            BodyMaterial::Rubber => "Rubber",
+            BodyMaterial::Ice => "Ice",
+            BodyMaterial::Stone => "Stone",
+            BodyMaterial::Glass => "Glass",
+        }
+    }
+
+    pub fn friction(self) -> f32 {
+        match self {
+            BodyMaterial::Plastic => 0.5,
+            BodyMaterial::Wood => 0.6,
+            BodyMaterial::Metal => 0.4,
+            BodyMaterial::Rubber => 1.0,
+            BodyMaterial::Ice => 0.02,
+            BodyMaterial::Stone => 0.7,
+            BodyMaterial::Glass => 0.3,
+        }
+    }
+
+    pub fn restitution(self) -> f32 {
+        match self {
+            BodyMaterial::Rubber => 0.8,
+            BodyMaterial::Plastic => 0.2,
+            BodyMaterial::Glass => 0.15,
+            BodyMaterial::Wood | BodyMaterial::Metal => 0.1,
+            BodyMaterial::Ice | BodyMaterial::Stone => 0.05,
+        }
+    }
+
+    pub fn density(self) -> f32 {
+        match self {
+            BodyMaterial::Plastic => 1.,
+            BodyMaterial::Wood => 0.6,
+            BodyMaterial::Metal => 7.8,
+            BodyMaterial::Rubber => 1.2,
+            BodyMaterial::Ice => 0.9,
+            BodyMaterial::Stone => 2.5,
+            BodyMaterial::Glass => 2.5,
+        }
+    }
+
+    /// colour a body gets when you pick this material
+    pub fn color(self) -> Color {
+        match self {
+            BodyMaterial::Plastic => Color::rgb(0.9, 0.9, 0.9),
+            BodyMaterial::Wood => Color::rgb(0.65, 0.45, 0.25),
+            BodyMaterial::Metal => Color::rgb(0.6, 0.63, 0.68),
+            BodyMaterial::Rubber => Color::rgb(0.2, 0.2, 0.22),
+            BodyMaterial::Ice => Color::rgb(0.75, 0.9, 1.),
+            BodyMaterial::Stone => Color::rgb(0.5, 0.5, 0.47),
+            BodyMaterial::Glass => Color::rgba(0.7, 0.85, 0.9, 0.6),
+        }
+    }
+}
+
+pub struct BodyMaterialPlugin;
+
+impl Plugin for BodyMaterialPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, apply_body_materials);
+    }
+}
+
+// ragdolls keep their colliders on child entities, so do those too
+fn apply_body_materials(
+    mut commands: Commands,
+    changed: Query<(Entity, &BodyMaterial, Option<&Children>), Changed<BodyMaterial>>,
+    colliders: Query<(), With<Collider>>,
+) {
+    for (entity, material, children) in changed.iter() {
+        let mut targets = vec![entity];
+        if let Some(children) = children {
+            targets.extend(children.iter().copied());
+        }
+        for target in targets {
+            if !colliders.contains(target) {
+                continue;
+            }
+            commands.entity(target).insert((
+                Friction::coefficient(material.friction()),
+                Restitution::coefficient(material.restitution()),
+                ColliderMassProperties::Density(material.density()),
+            ));
+        }
+    }
+}
@@ -0,0 +1,365 @@
+use bevy::ecs::system::SystemParam;
+use bevy::input::mouse::MouseWheel;
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_egui::{egui, EguiContexts};
+use bevy_pancam::PanCam;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::inspector::Selected;
+use crate::settings::KeyBindings;
+use crate::{EguiUnfocusedSystemSet, MainCamera};
+
+// extra room around the bodies when zooming to fit
+const FIT_MARGIN: f32 = 1.2;
+// how close the camera has to get before an animated move counts as done
+const ARRIVE_DISTANCE: f32 = 0.05;
+
+#[derive(Resource, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraSettings {
+    /// smallest projection scale, i.e. most zoomed in
+    pub min_zoom: f32,
+    /// biggest projection scale, i.e. most zoomed out
+    pub max_zoom: f32,
+    /// how quickly focus/follow moves catch up, higher is snappier
+    pub speed: f32,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            min_zoom: 0.01,
+            max_zoom: 50.,
+            speed: 6.,
+        }
+    }
+}
+
+/// A saved camera position, stored in the scene file.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CameraBookmark {
+    pub name: String,
+    pub position: [f32; 2],
+    pub scale: f32,
+}
+
+#[derive(Resource, Default)]
+pub struct CameraBookmarks {
+    pub bookmarks: Vec<CameraBookmark>,
+}
+
+/// What the camera is doing on its own. Panning or zooming by hand cancels it.
+#[derive(Resource, Default)]
+pub struct CameraControl {
+    /// body the camera keeps centered
+    pub follow: Option<Entity>,
+    /// where the camera is easing towards, and the scale it wants there
+    pub goal: Option<(Vec2, f32)>,
+}
+
+impl CameraControl {
+    pub fn move_to(&mut self, position: Vec2, scale: f32) {
+        self.follow = None;
+        self.goal = Some((position, scale));
+    }
+}
+
+#[derive(Clone, Copy)]
+enum CameraAction {
+    FocusSelected,
+    FollowSelected,
+    ZoomToFit,
+    Reset,
+}
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraSettings>()
+            .init_resource::<CameraBookmarks>()
+            .init_resource::<CameraControl>()
+            .add_systems(Update, camera_keys.in_set(EguiUnfocusedSystemSet))
+            .add_systems(
+                Update,
+                (camera_ui, apply_zoom_limits, cancel_on_input, move_camera).chain(),
+            );
+    }
+}
+
+/// Everything the camera actions look at to decide where to go
+#[derive(SystemParam)]
+struct CameraTargets<'w, 's> {
+    q_window: Query<'w, 's, &'static Window, With<PrimaryWindow>>,
+    rapier_context: Res<'w, RapierContext>,
+    selected_query: Query<'w, 's, (Entity, &'static GlobalTransform), With<Selected>>,
+    colliders: Query<'w, 's, (Entity, &'static GlobalTransform, &'static Collider)>,
+    rigid_bodies: Query<'w, 's, &'static RigidBody>,
+}
+
+impl CameraTargets<'_, '_> {
+    fn run(
+        &self,
+        action: CameraAction,
+        control: &mut CameraControl,
+        projection: &OrthographicProjection,
+    ) {
+        match action {
+            CameraAction::FocusSelected => {
+                if let Some((_, transform)) = self.selected_query.iter().next() {
+                    control.move_to(transform.translation().truncate(), projection.scale);
+                }
+            }
+            CameraAction::FollowSelected => {
+                control.goal = None;
+                control.follow = match control.follow {
+                    Some(_) => None,
+                    None => self.selected_query.iter().next().map(|(entity, _)| entity),
+                };
+            }
+            CameraAction::ZoomToFit => {
+                if let Some((center, scale)) = self.fit_bodies() {
+                    control.move_to(center, scale);
+                }
+            }
+            CameraAction::Reset => control.move_to(Vec2::ZERO, 1.),
+        }
+    }
+
+    // box around every dynamic body, or around everything if nothing is dynamic.
+    // the ground is huge so including it would zoom way out
+    fn fit_bodies(&self) -> Option<(Vec2, f32)> {
+        let is_dynamic = |collider: Entity| {
+            let body = self
+                .rapier_context
+                .collider_parent(collider)
+                .unwrap_or(collider);
+            matches!(self.rigid_bodies.get(body), Ok(RigidBody::Dynamic))
+        };
+        let any_dynamic = self
+            .colliders
+            .iter()
+            .any(|(entity, _, _)| is_dynamic(entity));
+
+        let mut min = Vec2::splat(f32::INFINITY);
+        let mut max = Vec2::splat(f32::NEG_INFINITY);
+        for (entity, transform, collider) in self.colliders.iter() {
+            if any_dynamic && !is_dynamic(entity) {
+                continue;
+            }
+            let aabb = collider.raw.compute_local_aabb();
+            for corner in [
+                Vec2::new(aabb.mins.x, aabb.mins.y),
+                Vec2::new(aabb.maxs.x, aabb.mins.y),
+                Vec2::new(aabb.maxs.x, aabb.maxs.y),
+                Vec2::new(aabb.mins.x, aabb.maxs.y),
+            ] {
+                let world = transform.transform_point(corner.extend(0.)).truncate();
+                min = min.min(world);
+                max = max.max(world);
+            }
+        }
+        if !min.is_finite() || !max.is_finite() {
+            return None;
+        }
+        let window = self.q_window.single();
+        let size = (max - min) * FIT_MARGIN;
+        let scale = (size.x / window.width()).max(size.y / window.height());
+        Some(((min + max) / 2., scale))
+    }
+}
+
+// C to focus the selected body, G to follow it, Z to zoom to fit, Home to reset (by default)
+fn camera_keys(
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut control: ResMut<CameraControl>,
+    camera_query: Query<&OrthographicProjection, With<MainCamera>>,
+    targets: CameraTargets,
+) {
+    let action = if keys.just_pressed(bindings.focus_selected) {
+        CameraAction::FocusSelected
+    } else if keys.just_pressed(bindings.follow_selected) {
+        CameraAction::FollowSelected
+    } else if keys.just_pressed(bindings.zoom_to_fit) {
+        CameraAction::ZoomToFit
+    } else if keys.just_pressed(bindings.reset_camera) {
+        CameraAction::Reset
+    } else {
+        return;
+    };
+    targets.run(action, &mut control, camera_query.single());
+}
+
+fn camera_ui(
+    mut contexts: EguiContexts,
+    mut settings: ResMut<CameraSettings>,
+    mut bookmarks: ResMut<CameraBookmarks>,
+    mut control: ResMut<CameraControl>,
+    camera_query: Query<(&Transform, &OrthographicProjection), With<MainCamera>>,
+    targets: CameraTargets,
+) {
+    let (camera_transform, projection) = camera_query.single();
+    let mut action = None;
+    let mut go_to = None;
+    let mut edited = settings.clone();
+
+    egui::Window::new("Camera")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Focus selected (C)").clicked() {
+                    action = Some(CameraAction::FocusSelected);
+                }
+                let follow_label = if control.follow.is_some() {
+                    "Stop following (G)"
+                } else {
+                    "Follow selected (G)"
+                };
+                if ui.button(follow_label).clicked() {
+                    action = Some(CameraAction::FollowSelected);
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Zoom to fit (Z)").clicked() {
+                    action = Some(CameraAction::ZoomToFit);
+                }
+                if ui.button("Reset (Home)").clicked() {
+                    action = Some(CameraAction::Reset);
+                }
+            });
+
+            ui.separator();
+            ui.label("Zoom limits");
+            let max_zoom = edited.max_zoom;
+            ui.add(
+                egui::Slider::new(&mut edited.min_zoom, 0.001..=max_zoom)
+                    .logarithmic(true)
+                    .text("Closest"),
+            );
+            let min_zoom = edited.min_zoom;
+            ui.add(
+                egui::Slider::new(&mut edited.max_zoom, min_zoom..=500.)
+                    .logarithmic(true)
+                    .text("Farthest"),
+            );
+            ui.add(egui::Slider::new(&mut edited.speed, 1.0..=20.).text("Move speed"));
+
+            ui.separator();
+            ui.label("Bookmarks (saved with the scene)");
+            let mut remove = None;
+            for (i, bookmark) in bookmarks.bookmarks.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut bookmark.name);
+                    if ui.button("Go").clicked() {
+                        go_to = Some((Vec2::from_array(bookmark.position), bookmark.scale));
+                    }
+                    if ui.button("Remove").clicked() {
+                        remove = Some(i);
+                    }
+                });
+            }
+            if let Some(i) = remove {
+                bookmarks.bookmarks.remove(i);
+            }
+            if ui.button("Bookmark this view").clicked() {
+                let name = format!("View {}", bookmarks.bookmarks.len() + 1);
+                bookmarks.bookmarks.push(CameraBookmark {
+                    name,
+                    position: camera_transform.translation.truncate().to_array(),
+                    scale: projection.scale,
+                });
+            }
+        });
+
+    settings.set_if_neq(edited);
+    if let Some(action) = action {
+        targets.run(action, &mut control, projection);
+    }
+    if let Some((position, scale)) = go_to {
+        control.move_to(position, scale);
+    }
+}
+
+fn apply_zoom_limits(
+    settings: Res<CameraSettings>,
+    mut camera_query: Query<(&mut PanCam, &mut OrthographicProjection), With<MainCamera>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    let (mut pancam, mut projection) = camera_query.single_mut();
+    pancam.min_scale = settings.min_zoom;
+    pancam.max_scale = Some(settings.max_zoom);
+    let scale = projection.scale.clamp(settings.min_zoom, settings.max_zoom);
+    if scale != projection.scale {
+        projection.scale = scale;
+    }
+}
+
+// grabbing or scrolling takes the camera back from us
+fn cancel_on_input(
+    mut control: ResMut<CameraControl>,
+    buttons: Res<Input<MouseButton>>,
+    mut scroll: EventReader<MouseWheel>,
+    mut contexts: EguiContexts,
+    camera_query: Query<&PanCam, With<MainCamera>>,
+) {
+    let scrolled = scroll.read().count() > 0;
+    if contexts.ctx_mut().wants_pointer_input() {
+        return;
+    }
+    let pancam = camera_query.single();
+    let grabbed = pancam
+        .grab_buttons
+        .iter()
+        .any(|button| buttons.just_pressed(*button));
+    if pancam.enabled && (grabbed || (scrolled && control.follow.is_none())) {
+        control.goal = None;
+        control.follow = None;
+    }
+}
+
+fn move_camera(
+    time: Res<Time>,
+    settings: Res<CameraSettings>,
+    mut control: ResMut<CameraControl>,
+    bodies: Query<&GlobalTransform>,
+    mut camera_query: Query<(&mut Transform, &mut OrthographicProjection), With<MainCamera>>,
+) {
+    let (mut camera_transform, mut projection) = camera_query.single_mut();
+    let blend = (settings.speed * time.delta_seconds()).min(1.);
+
+    if let Some(entity) = control.follow {
+        let Ok(body_transform) = bodies.get(entity) else {
+            // it got deleted
+            control.follow = None;
+            return;
+        };
+        let target = body_transform
+            .translation()
+            .truncate()
+            .extend(camera_transform.translation.z);
+        camera_transform.translation = camera_transform.translation.lerp(target, blend);
+        return;
+    }
+
+    let Some((position, scale)) = control.goal else {
+        return;
+    };
+    let scale = scale.clamp(settings.min_zoom, settings.max_zoom);
+    let target = position.extend(camera_transform.translation.z);
+    camera_transform.translation = camera_transform.translation.lerp(target, blend);
+    // zoom in log space so big changes don't feel lopsided
+    projection.scale = (projection.scale.ln() + (scale.ln() - projection.scale.ln()) * blend).exp();
+
+    let arrived = camera_transform.translation.truncate().distance(position)
+        < ARRIVE_DISTANCE * projection.scale
+        && (projection.scale / scale - 1.).abs() < ARRIVE_DISTANCE;
+    if arrived {
+        camera_transform.translation = target;
+        projection.scale = scale;
+        control.goal = None;
+    }
+}
@@ -0,0 +1,580 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_egui::{egui, EguiContexts, EguiSettings};
+use bevy_rapier2d::prelude::*;
+
+use crate::body_material::BodyMaterial;
+use crate::forces::{attach_thruster, spawn_force_field};
+use crate::images::ImportedImages;
+use crate::inspector::{body_of_collider, Selected};
+use crate::layers::CollisionLayer;
+use crate::matter::Matter;
+use crate::ragdoll::{spawn_person, ActiveRagdoll, Ragdoll, RagdollPart, RagdollSettings};
+use crate::scene::{array_to_color, color_to_array, SavedBody, SavedPerson};
+use crate::{cursor_world_position, EguiUnfocusedSystemSet, MainCamera, MultiBodySpring};
+
+/// What the right-click menu was opened on
+#[derive(Clone, Copy)]
+enum MenuTarget {
+    Body(Entity),
+    Empty,
+}
+
+#[derive(Resource, Default)]
+struct ContextMenu {
+    open: Option<OpenMenu>,
+}
+
+#[derive(Clone, Copy)]
+struct OpenMenu {
+    target: MenuTarget,
+    /// where it was opened, in egui points
+    screen_position: egui::Pos2,
+    world_position: Vec2,
+    /// the click that opened it also counts as a click outside it, so skip that one
+    just_opened: bool,
+}
+
+/// Last thing copied from the context menu
+#[derive(Resource, Default)]
+pub enum Clipboard {
+    #[default]
+    Empty,
+    Body(SavedBody),
+    Person(SavedPerson),
+}
+
+/// "Attach joint" or "Attach spring" was picked, the next left click picks the other body
+#[derive(Resource, Default)]
+struct PendingJoint {
+    body: Option<Entity>,
+    pivot: Vec2,
+    /// a spring from the pivot to wherever the click lands, instead of a pin
+    spring: bool,
+}
+
+enum MenuAction {
+    Delete(Entity),
+    Duplicate(Entity),
+    Copy(Entity),
+    ToggleFreeze(Entity),
+    SetMaterial(Entity, BodyMaterial),
+    SetColor(Entity, Color),
+    AttachJoint(Entity),
+    AttachSpring(Entity),
+    AddThruster(Entity),
+    Inspect(Entity),
+    SpawnBox(Vec2),
+    SpawnPlank(Vec2),
+    SpawnBall(Vec2),
+    SpawnPerson(Vec2),
+    SpawnForceField(Vec2),
+    Paste(Vec2),
+}
+
+pub struct ContextMenuPlugin;
+
+impl Plugin for ContextMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ContextMenu>()
+            .init_resource::<Clipboard>()
+            .init_resource::<PendingJoint>()
+            .add_systems(
+                Update,
+                (open_context_menu, finish_joint).in_set(EguiUnfocusedSystemSet),
+            )
+            .add_systems(
+                Update,
+                (context_menu_ui, draw_pending_joint).after(open_context_menu),
+            );
+    }
+}
+
+/// The body with a collider under this point, if any
+pub fn body_at_point(
+    rapier_context: &RapierContext,
+    parts: &Query<&RagdollPart>,
+    point: Vec2,
+) -> Option<Entity> {
+    let mut found = None;
+    rapier_context.intersections_with_point(point, QueryFilter::default(), |collider| {
+        found = Some(collider);
+        false
+    });
+    found.map(|collider| body_of_collider(rapier_context, parts, collider))
+}
+
+fn open_context_menu(
+    buttons: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    egui_settings: Res<EguiSettings>,
+    rapier_context: Res<RapierContext>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    parts: Query<&RagdollPart>,
+    mut menu: ResMut<ContextMenu>,
+    mut pending: ResMut<PendingJoint>,
+) {
+    if keys.just_pressed(KeyCode::Escape) {
+        menu.open = None;
+        pending.body = None;
+    }
+    // open on release so the menu doesn't see its own click
+    if !buttons.just_released(MouseButton::Right) {
+        return;
+    }
+    pending.body = None;
+    let window = q_window.single();
+    let (camera, camera_transform) = camera_query.single();
+    let (Some(cursor), Some(world_position)) = (
+        window.cursor_position(),
+        cursor_world_position(window, camera, camera_transform),
+    ) else {
+        return;
+    };
+    let scale = egui_settings.scale_factor as f32;
+    menu.open = Some(OpenMenu {
+        target: match body_at_point(&rapier_context, &parts, world_position) {
+            Some(body) => MenuTarget::Body(body),
+            None => MenuTarget::Empty,
+        },
+        screen_position: egui::pos2(cursor.x / scale, cursor.y / scale),
+        world_position,
+        just_opened: true,
+    });
+}
+
+fn context_menu_ui(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    mut menu: ResMut<ContextMenu>,
+    mut clipboard: ResMut<Clipboard>,
+    mut pending: ResMut<PendingJoint>,
+    bodies: Query<(
+        &Transform,
+        Option<&Collider>,
+        Option<&RigidBody>,
+        Option<&Velocity>,
+        Option<&BodyMaterial>,
+        Option<&CollisionLayer>,
+    )>,
+    mut matters: Query<&mut Matter>,
+    ragdolls: Query<(&Ragdoll, Option<&ActiveRagdoll>)>,
+    selected_query: Query<Entity, With<Selected>>,
+    imported: Res<ImportedImages>,
+    asset_server: Res<AssetServer>,
+    ragdoll_settings: Res<RagdollSettings>,
+) {
+    let Some(open) = menu.open else {
+        return;
+    };
+    // the body might have been deleted or shattered since
+    if let MenuTarget::Body(body) = open.target {
+        if !bodies.contains(body) {
+            menu.open = None;
+            return;
+        }
+    }
+
+    let mut actions = vec![];
+    let paste_ready = !matches!(*clipboard, Clipboard::Empty);
+    let area = egui::Area::new("context_menu")
+        .fixed_pos(open.screen_position)
+        .order(egui::Order::Foreground)
+        .show(contexts.ctx_mut(), |ui| {
+            egui::Frame::menu(ui.style()).show(ui, |ui| {
+                ui.set_min_width(140.);
+                match open.target {
+                    MenuTarget::Body(body) => {
+                        let (_, collider, rigidbody, _, material, _) = bodies.get(body).unwrap();
+                        let is_ragdoll = ragdolls.contains(body);
+
+                        if ui.button("Inspect").clicked() {
+                            actions.push(MenuAction::Inspect(body));
+                        }
+                        if ui.button("Duplicate").clicked() {
+                            actions.push(MenuAction::Duplicate(body));
+                        }
+                        if ui.button("Copy").clicked() {
+                            actions.push(MenuAction::Copy(body));
+                        }
+                        if let Some(rigidbody) = rigidbody {
+                            let label = if *rigidbody == RigidBody::Dynamic {
+                                "Freeze"
+                            } else {
+                                "Unfreeze"
+                            };
+                            if ui.button(label).clicked() {
+                                actions.push(MenuAction::ToggleFreeze(body));
+                            }
+                        }
+                        if collider.is_some() || is_ragdoll {
+                            ui.menu_button("Set material", |ui| {
+                                for option in BodyMaterial::ALL {
+                                    let current = material == Some(&option);
+                                    if ui.selectable_label(current, option.name()).clicked() {
+                                        actions.push(MenuAction::SetMaterial(body, option));
+                                        ui.close_menu();
+                                    }
+                                }
+                            });
+                        }
+                        if let Ok(matter) = matters.get(body) {
+                            ui.horizontal(|ui| {
+                                let mut rgb = [matter.fill.r(), matter.fill.g(), matter.fill.b()];
+                                if ui.color_edit_button_rgb(&mut rgb).changed() {
+                                    actions.push(MenuAction::SetColor(
+                                        body,
+                                        Color::rgba(rgb[0], rgb[1], rgb[2], matter.fill.a()),
+                                    ));
+                                }
+                                ui.label("Colour");
+                            });
+                        }
+                        if rigidbody.is_some() && ui.button("Attach joint").clicked() {
+                            actions.push(MenuAction::AttachJoint(body));
+                        }
+                        if rigidbody.is_some() && ui.button("Attach spring").clicked() {
+                            actions.push(MenuAction::AttachSpring(body));
+                        }
+                        if rigidbody == Some(&RigidBody::Dynamic)
+                            && ui.button("Add thruster").clicked()
+                        {
+                            actions.push(MenuAction::AddThruster(body));
+                        }
+                        ui.separator();
+                        if ui.button("Delete").clicked() {
+                            actions.push(MenuAction::Delete(body));
+                        }
+                    }
+                    MenuTarget::Empty => {
+                        let at = open.world_position;
+                        if ui.button("Box").clicked() {
+                            actions.push(MenuAction::SpawnBox(at));
+                        }
+                        if ui.button("Plank").clicked() {
+                            actions.push(MenuAction::SpawnPlank(at));
+                        }
+                        if ui.button("Ball").clicked() {
+                            actions.push(MenuAction::SpawnBall(at));
+                        }
+                        if ui.button("Person").clicked() {
+                            actions.push(MenuAction::SpawnPerson(at));
+                        }
+                        if ui.button("Force field").clicked() {
+                            actions.push(MenuAction::SpawnForceField(at));
+                        }
+                        ui.separator();
+                        if ui
+                            .add_enabled(paste_ready, egui::Button::new("Paste"))
+                            .clicked()
+                        {
+                            actions.push(MenuAction::Paste(at));
+                        }
+                    }
+                }
+            });
+        });
+
+    // colour edits keep the menu open so you can drag the picker around
+    let keep_open = actions
+        .iter()
+        .all(|action| matches!(action, MenuAction::SetColor(..)));
+    if open.just_opened {
+        menu.open = Some(OpenMenu {
+            just_opened: false,
+            ..open
+        });
+    } else if !keep_open || area.response.clicked_elsewhere() {
+        menu.open = None;
+    }
+
+    for action in actions {
+        match action {
+            MenuAction::Delete(body) => {
+                if let Ok((ragdoll, _)) = ragdolls.get(body) {
+                    commands.entity(ragdoll.head).despawn_recursive();
+                }
+                commands.entity(body).despawn_recursive();
+            }
+            MenuAction::Copy(body) | MenuAction::Duplicate(body) => {
+                let copied = if let Ok((_, active)) = ragdolls.get(body) {
+                    let (transform, ..) = bodies.get(body).unwrap();
+                    matters.get(body).ok().map(|matter| {
+                        Clipboard::Person(SavedPerson {
+                            position: transform.translation.truncate().to_array(),
+                            color: color_to_array(matter.fill),
+                            alive: active.is_some(),
+                        })
+                    })
+                } else {
+                    let (transform, collider, rigidbody, velocity, material, layer) =
+                        bodies.get(body).unwrap();
+                    match (collider, matters.get(body)) {
+                        (Some(collider), Ok(matter)) => SavedBody::capture(
+                            transform, collider, matter, rigidbody, velocity, material, &imported,
+                        )
+                        .map(|mut saved| {
+                            saved.layer = layer.map_or(0, |layer| layer.0);
+                            Clipboard::Body(saved)
+                        }),
+                        _ => None,
+                    }
+                };
+                let Some(copied) = copied else {
+                    continue;
+                };
+                if let MenuAction::Duplicate(_) = action {
+                    // put the copy just to the right of the original
+                    let (transform, collider, ..) = bodies.get(body).unwrap();
+                    let width = collider.map_or(8., |collider| {
+                        let aabb = collider.raw.compute_local_aabb();
+                        aabb.maxs.x - aabb.mins.x
+                    });
+                    let at = transform.translation.truncate() + Vec2::new(width + 1., 0.);
+                    paste(&mut commands, &copied, at, &imported, &asset_server);
+                } else {
+                    *clipboard = copied;
+                }
+            }
+            MenuAction::ToggleFreeze(body) => {
+                let Ok((_, _, Some(rigidbody), ..)) = bodies.get(body) else {
+                    continue;
+                };
+                // a person's head is its own body, it goes along with the torso
+                let head = ragdolls.get(body).ok().map(|(ragdoll, _)| ragdoll.head);
+                for entity in std::iter::once(body).chain(head) {
+                    if *rigidbody == RigidBody::Dynamic {
+                        commands
+                            .entity(entity)
+                            .insert((RigidBody::Fixed, Velocity::zero()));
+                    } else {
+                        commands.entity(entity).insert(RigidBody::Dynamic);
+                    }
+                }
+            }
+            MenuAction::SetMaterial(body, material) => {
+                commands.entity(body).insert(material);
+                if let Ok((ragdoll, _)) = ragdolls.get(body) {
+                    commands.entity(ragdoll.head).insert(material);
+                }
+                // people keep their skin colour
+                if !ragdolls.contains(body) {
+                    if let Ok(mut matter) = matters.get_mut(body) {
+                        matter.set_fill(material.color());
+                    }
+                }
+            }
+            MenuAction::SetColor(body, color) => {
+                if let Ok(mut matter) = matters.get_mut(body) {
+                    matter.set_fill(color);
+                }
+                if let Ok((ragdoll, _)) = ragdolls.get(body) {
+                    if let Ok(mut matter) = matters.get_mut(ragdoll.head) {
+                        matter.set_fill(color);
+                    }
+                }
+            }
+            MenuAction::AttachJoint(body) => {
+                pending.body = Some(body);
+                pending.pivot = open.world_position;
+                pending.spring = false;
+            }
+            MenuAction::AttachSpring(body) => {
+                pending.body = Some(body);
+                pending.pivot = open.world_position;
+                pending.spring = true;
+            }
+            MenuAction::AddThruster(body) => {
+                let (transform, ..) = bodies.get(body).unwrap();
+                let local_point = transform
+                    .compute_affine()
+                    .inverse()
+                    .transform_point3(open.world_position.extend(0.))
+                    .truncate();
+                // pointing up in the world, whichever way the body is turned
+                let angle = -transform.rotation.to_euler(EulerRot::XYZ).2;
+                attach_thruster(&mut commands, body, local_point, angle);
+            }
+            MenuAction::Inspect(body) => {
+                for entity in selected_query.iter() {
+                    commands.entity(entity).remove::<Selected>();
+                }
+                commands.entity(body).insert(Selected);
+            }
+            MenuAction::SpawnBox(at) => {
+                commands.spawn((
+                    SpatialBundle::from_transform(Transform::from_translation(at.extend(0.))),
+                    Matter::new(Color::WHITE),
+                    Collider::cuboid(4., 4.),
+                    RigidBody::Dynamic,
+                ));
+            }
+            MenuAction::SpawnPlank(at) => {
+                commands.spawn((
+                    SpatialBundle::from_transform(Transform::from_translation(at.extend(0.))),
+                    Matter::new(BodyMaterial::Wood.color()),
+                    Collider::cuboid(16., 1.),
+                    RigidBody::Dynamic,
+                    BodyMaterial::Wood,
+                ));
+            }
+            MenuAction::SpawnBall(at) => {
+                commands.spawn((
+                    SpatialBundle::from_transform(Transform::from_translation(at.extend(0.))),
+                    Matter::new(BodyMaterial::Rubber.color()),
+                    Collider::ball(4.),
+                    RigidBody::Dynamic,
+                    BodyMaterial::Rubber,
+                ));
+            }
+            MenuAction::SpawnPerson(at) => {
+                spawn_person(
+                    &mut commands,
+                    &asset_server,
+                    Color::rgb(0.6627450980392157, 0.7372549019607844, 0.4),
+                    at,
+                    ragdoll_settings.spawn_alive,
+                );
+            }
+            MenuAction::SpawnForceField(at) => {
+                spawn_force_field(&mut commands, at);
+            }
+            MenuAction::Paste(at) => {
+                paste(&mut commands, &clipboard, at, &imported, &asset_server);
+            }
+        }
+    }
+}
+
+fn paste(
+    commands: &mut Commands,
+    item: &Clipboard,
+    at: Vec2,
+    imported: &ImportedImages,
+    asset_server: &Res<AssetServer>,
+) {
+    match item {
+        Clipboard::Empty => {}
+        Clipboard::Body(body) => {
+            let mut body = body.clone();
+            body.position = at.to_array();
+            body.spawn(commands, imported);
+        }
+        Clipboard::Person(person) => {
+            spawn_person(
+                commands,
+                asset_server,
+                array_to_color(person.color),
+                at,
+                person.alive,
+            );
+        }
+    }
+}
+
+// second click of "attach joint": pin the two bodies together where the menu was opened.
+// for "attach spring" the click is the other end of the spring
+fn finish_joint(
+    mut commands: Commands,
+    buttons: Res<Input<MouseButton>>,
+    mut pending: ResMut<PendingJoint>,
+    rapier_context: Res<RapierContext>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    parts: Query<&RagdollPart>,
+    transforms: Query<&GlobalTransform>,
+    joints: Query<(), With<ImpulseJoint>>,
+    springs: Query<(), With<MultiBodySpring>>,
+) {
+    let Some(first) = pending.body else {
+        return;
+    };
+    if !buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+    pending.body = None;
+    let (camera, camera_transform) = camera_query.single();
+    let Some(world_position) = cursor_world_position(q_window.single(), camera, camera_transform)
+    else {
+        return;
+    };
+    let Some(second) = body_at_point(&rapier_context, &parts, world_position) else {
+        return;
+    };
+    if second == first {
+        return;
+    }
+    let (Ok(first_transform), Ok(second_transform)) =
+        (transforms.get(first), transforms.get(second))
+    else {
+        return;
+    };
+    let local_anchor = |transform: &GlobalTransform, point: Vec2| {
+        transform
+            .affine()
+            .inverse()
+            .transform_point3(point.extend(0.))
+            .truncate()
+    };
+    if pending.spring {
+        // springs don't take up the joint slot, but a body can only hold one of them too
+        let ends = [
+            (first, local_anchor(first_transform, pending.pivot)),
+            (second, local_anchor(second_transform, world_position)),
+        ];
+        let [(holder, holder_anchor), (other, other_anchor)] = if !springs.contains(first) {
+            ends
+        } else if !springs.contains(second) {
+            [ends[1], ends[0]]
+        } else {
+            warn!("both bodies already have a spring");
+            return;
+        };
+        commands.entity(holder).insert(MultiBodySpring::new(
+            other,
+            holder_anchor,
+            other_anchor,
+            pending.pivot.distance(world_position),
+        ));
+        for body in [holder, other] {
+            commands
+                .entity(body)
+                .insert((ExternalImpulse::default(), ReadMassProperties::default()));
+        }
+        return;
+    }
+    let first_anchor = local_anchor(first_transform, pending.pivot);
+    let second_anchor = local_anchor(second_transform, pending.pivot);
+
+    // each body can only hold one joint, so put it on whichever is free
+    let (parent, child, parent_anchor, child_anchor) = if !joints.contains(second) {
+        (first, second, first_anchor, second_anchor)
+    } else if !joints.contains(first) {
+        (second, first, second_anchor, first_anchor)
+    } else {
+        warn!("both bodies already have a joint");
+        return;
+    };
+    let joint = RevoluteJointBuilder::new()
+        .local_anchor1(parent_anchor)
+        .local_anchor2(child_anchor);
+    commands
+        .entity(child)
+        .insert(ImpulseJoint::new(parent, joint));
+}
+
+fn draw_pending_joint(
+    pending: Res<PendingJoint>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut gizmos: Gizmos,
+) {
+    if pending.body.is_none() {
+        return;
+    }
+    let (camera, camera_transform) = camera_query.single();
+    gizmos.circle_2d(pending.pivot, 1., Color::rgb(1., 0.85, 0.3));
+    if let Some(cursor) = cursor_world_position(q_window.single(), camera, camera_transform) {
+        gizmos.line_2d(pending.pivot, cursor, Color::rgb(1., 0.85, 0.3));
+    }
+}
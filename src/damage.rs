@@ -0,0 +1,281 @@
+use bevy::{prelude::*, utils::HashSet};
+use bevy_rapier2d::prelude::*;
+
+use crate::body_material::BodyMaterial;
+use crate::inspector::body_of_collider;
+use crate::layers::CollisionLayer;
+use crate::matter::Matter;
+use crate::ragdoll::{ActiveRagdoll, Ragdoll, RagdollPart};
+
+// contacts softer than this dont make events at all
+const DAMAGE_EVENT_THRESHOLD: f32 = 5000.;
+
+#[derive(Component, Clone, PartialEq)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+    /// contact forces below this dont hurt
+    pub damage_threshold: f32,
+    /// how much health each unit of force over the threshold takes away
+    pub damage_per_force: f32,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self {
+            current: 100.,
+            max: 100.,
+            damage_threshold: 20000.,
+            damage_per_force: 0.0005,
+        }
+    }
+}
+
+impl Health {
+    pub fn fraction(&self) -> f32 {
+        (self.current / self.max).clamp(0., 1.)
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.
+    }
+}
+
+/// Body shatters into a grid of fragments when hit harder than `threshold`.
+#[derive(Component, Clone, PartialEq)]
+pub struct Breakable {
+    pub threshold: f32,
+    /// fragments along each side
+    pub pieces: u32,
+}
+
+impl Default for Breakable {
+    fn default() -> Self {
+        Self {
+            threshold: 60000.,
+            pieces: 3,
+        }
+    }
+}
+
+/// Colour the body had before it got hurt, we tint from this. If the fill isn't the tint we
+/// last put on, something else recoloured the body and that becomes the new base.
+#[derive(Component)]
+struct BaseColor {
+    base: Color,
+    tinted: Color,
+}
+
+/// Sent when a body with `Health` reaches 0.
+#[derive(Event)]
+pub struct Died {
+    pub entity: Entity,
+}
+
+pub struct DamagePlugin;
+
+impl Plugin for DamagePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Died>().add_systems(
+            Update,
+            (
+                enable_contact_events,
+                apply_impacts,
+                (show_injuries, kill_ragdolls),
+            )
+                .chain(),
+        );
+    }
+}
+
+// bodies only report contact forces if we ask for them
+fn enable_contact_events(
+    mut commands: Commands,
+    added: Query<
+        (Entity, Option<&Velocity>),
+        (
+            With<Collider>,
+            Without<RagdollPart>,
+            Or<(Added<Health>, Added<Breakable>)>,
+        ),
+    >,
+) {
+    for (entity, velocity) in added.iter() {
+        let mut ent = commands.entity(entity);
+        ent.insert((
+            ActiveEvents::CONTACT_FORCE_EVENTS,
+            ContactForceEventThreshold(DAMAGE_EVENT_THRESHOLD),
+        ));
+        // fragments inherit this when it breaks
+        if velocity.is_none() {
+            ent.insert(Velocity::default());
+        }
+    }
+}
+
+fn apply_impacts(
+    mut commands: Commands,
+    mut contact_force_events: EventReader<ContactForceEvent>,
+    rapier_context: Res<RapierContext>,
+    parts: Query<&RagdollPart>,
+    mut health_query: Query<&mut Health>,
+    breakable_query: Query<(
+        &Breakable,
+        &Collider,
+        &Transform,
+        Option<&Velocity>,
+        Option<&Matter>,
+        Option<&CollisionLayer>,
+        Option<&BodyMaterial>,
+    )>,
+    mut died: EventWriter<Died>,
+) {
+    let mut shattered = HashSet::new();
+    for event in contact_force_events.read() {
+        let force = event.total_force_magnitude;
+        for collider in [event.collider1, event.collider2] {
+            let body = body_of_collider(&rapier_context, &parts, collider);
+
+            if let Ok(mut health) = health_query.get_mut(body) {
+                if !health.is_dead() && force > health.damage_threshold {
+                    health.current -= (force - health.damage_threshold) * health.damage_per_force;
+                    if health.is_dead() {
+                        health.current = 0.;
+                        died.send(Died { entity: body });
+                    }
+                }
+            }
+
+            if let Ok((breakable, collider, transform, velocity, matter, layer, material)) =
+                breakable_query.get(body)
+            {
+                if force > breakable.threshold && shattered.insert(body) {
+                    let fragments = shatter(
+                        &mut commands,
+                        body,
+                        breakable.pieces,
+                        collider,
+                        transform,
+                        velocity.copied().unwrap_or_default(),
+                        matter.map_or(Color::WHITE, |m| m.fill),
+                    );
+                    // the pieces are still made of the same stuff, on the same layer
+                    for fragment in fragments {
+                        let mut fragment = commands.entity(fragment);
+                        if let Some(layer) = layer {
+                            fragment.insert(*layer);
+                        }
+                        if let Some(material) = material {
+                            fragment.insert(*material);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// chop the body's bounding box into a grid and keep the cells that are inside the shape,
+// returns the pieces
+fn shatter(
+    commands: &mut Commands,
+    entity: Entity,
+    pieces: u32,
+    collider: &Collider,
+    transform: &Transform,
+    velocity: Velocity,
+    color: Color,
+) -> Vec<Entity> {
+    let aabb = collider.raw.compute_local_aabb();
+    let mins = Vec2::new(aabb.mins.x, aabb.mins.y);
+    let size = Vec2::new(aabb.maxs.x - aabb.mins.x, aabb.maxs.y - aabb.mins.y);
+    let pieces = pieces.max(2);
+    let cell = size / pieces as f32;
+
+    let mut fragments = Vec::new();
+    for x in 0..pieces {
+        for y in 0..pieces {
+            let local_center = mins + cell * Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+            if !collider.contains_point(Vec2::ZERO, 0., local_center) {
+                continue;
+            }
+            let world_center = transform.transform_point(local_center.extend(0.));
+            let offset = world_center.truncate() - transform.translation.truncate();
+            // rigid body velocity at that point
+            let linvel = velocity.linvel + Vec2::new(-offset.y, offset.x) * velocity.angvel;
+            let fragment = commands.spawn((
+                SpatialBundle::from_transform(Transform {
+                    translation: world_center,
+                    rotation: transform.rotation,
+                    ..default()
+                }),
+                Matter::new(color),
+                Collider::cuboid(cell.x / 2., cell.y / 2.),
+                RigidBody::Dynamic,
+                Velocity {
+                    linvel,
+                    angvel: velocity.angvel,
+                },
+            ));
+            fragments.push(fragment.id());
+        }
+    }
+
+    commands.entity(entity).despawn_recursive();
+    fragments
+}
+
+// people go pale and bruised as they lose health
+fn show_injuries(
+    mut commands: Commands,
+    health_query: Query<(Entity, &Health, Option<&Ragdoll>, Option<&BaseColor>), Changed<Health>>,
+    mut matters: Query<&mut Matter>,
+) {
+    const BRUISED: Color = Color::rgb(0.45, 0.3, 0.45);
+    for (entity, health, ragdoll, base_color) in health_query.iter() {
+        let Ok(matter) = matters.get(entity) else {
+            continue;
+        };
+        let base = match base_color {
+            Some(base_color) if base_color.tinted == matter.fill => base_color.base,
+            _ => matter.fill,
+        };
+        let hurt = 1. - health.fraction();
+        let color = Color::rgb(
+            base.r() + (BRUISED.r() - base.r()) * hurt,
+            base.g() + (BRUISED.g() - base.g()) * hurt,
+            base.b() + (BRUISED.b() - base.b()) * hurt,
+        );
+        // already showing this, don't make the matter redraw
+        if base_color.is_some_and(|base_color| base_color.tinted == color) {
+            continue;
+        }
+        commands.entity(entity).insert(BaseColor {
+            base,
+            tinted: color,
+        });
+
+        let mut targets = vec![entity];
+        if let Some(ragdoll) = ragdoll {
+            targets.push(ragdoll.head);
+        }
+        for target in targets {
+            if let Ok(mut matter) = matters.get_mut(target) {
+                matter.set_fill(color);
+            }
+        }
+    }
+}
+
+// dead people stop trying to stand up
+fn kill_ragdolls(
+    mut commands: Commands,
+    mut died: EventReader<Died>,
+    active_query: Query<(), With<ActiveRagdoll>>,
+) {
+    for event in died.read() {
+        if active_query.contains(event.entity) {
+            commands.entity(event.entity).remove::<ActiveRagdoll>();
+        }
+    }
+}
@@ -0,0 +1,665 @@
+//! Particle fluid, using the double density relaxation from Clavet et al's
+//! "Particle-based Viscoelastic Fluid Simulation". Particles live in the `Fluid` resource
+//! rather than as entities, push rapier bodies around and get pushed back.
+
+use bevy::render::render_resource::{
+    AsBindGroup, Extent3d, ShaderRef, TextureDimension, TextureFormat,
+};
+use bevy::render::texture::ImageSampler;
+use bevy::sprite::{Material2d, Material2dPlugin, MaterialMesh2dBundle, Mesh2dHandle};
+use bevy::utils::HashMap;
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_egui::{egui, EguiContexts};
+use bevy_rapier2d::prelude::*;
+use bevy_rapier2d::rapier::math::{Point, Vector};
+use bevy_rapier2d::rapier::prelude::RigidBodyHandle;
+
+use crate::performance::StepTimed;
+use crate::{cursor_world_position, EguiUnfocusedSystemSet, MainCamera, Tool, Tools};
+
+/// Distance between particles at rest
+pub const SPACING: f32 = 3.;
+// particles closer than this push on each other
+const SMOOTHING: f32 = 2. * SPACING;
+// how close a particle gets to a collider
+const PARTICLE_RADIUS: f32 = SPACING / 2.;
+// what the density sums to for particles sitting `SPACING` apart
+const REST_DENSITY: f32 = 1.4;
+const STIFFNESS: f32 = 1000.;
+const NEAR_STIFFNESS: f32 = 2000.;
+const QUADRATIC_VISCOSITY: f32 = 0.002;
+// fraction of sliding speed lost when touching a body
+const FRICTION: f32 = 0.02;
+// kg per square meter, same as the water regions
+const DENSITY: f32 = 1.;
+// the relaxation blows up with long steps, so longer frames are split into these
+const MAX_SUBSTEP: f32 = 1. / 120.;
+// particles this far from the origin have fallen out of the world
+const KILL_DISTANCE: f32 = 50_000.;
+
+// metaball field: each particle adds a bump this wide, the surface is where they add up to `THRESHOLD`
+const BLOB_RADIUS: f32 = 4.;
+const THRESHOLD: f32 = 0.5;
+const TEXEL_SIZE: f32 = 1.;
+const MAX_TEXTURE_SIZE: f32 = 1024.;
+// in front of bodies, behind water regions
+const FLUID_Z: f32 = 0.4;
+
+#[derive(Resource)]
+pub struct FluidSettings {
+    /// how much neighbouring particles even out their speeds, thicker fluid at higher values
+    pub viscosity: f32,
+    pub max_particles: usize,
+    /// particles per second from the pour tool
+    pub pour_rate: f32,
+    pub color: Color,
+}
+
+impl Default for FluidSettings {
+    fn default() -> Self {
+        Self {
+            viscosity: 2.,
+            max_particles: 3000,
+            pour_rate: 300.,
+            color: Color::rgba(0.25, 0.5, 0.95, 0.8),
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Fluid {
+    positions: Vec<Vec2>,
+    velocities: Vec<Vec2>,
+    // reused between steps
+    previous: Vec<Vec2>,
+    grid: HashMap<IVec2, Vec<usize>>,
+    neighbors: Vec<Vec<usize>>,
+}
+
+impl Fluid {
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    pub fn positions(&self) -> &[Vec2] {
+        &self.positions
+    }
+
+    pub fn clear(&mut self) {
+        self.positions.clear();
+        self.velocities.clear();
+    }
+
+    pub fn spawn(&mut self, position: Vec2, velocity: Vec2) {
+        self.positions.push(position);
+        self.velocities.push(velocity);
+    }
+
+    /// Packs `rect` with particles at rest, up to `max` in total
+    pub fn fill_rect(&mut self, rect: Rect, max: usize) {
+        let columns = (rect.width() / SPACING) as u32;
+        let rows = (rect.height() / SPACING) as u32;
+        for row in 0..rows {
+            for column in 0..columns {
+                if self.len() >= max {
+                    return;
+                }
+                let offset = Vec2::new(column as f32 + 0.5, row as f32 + 0.5) * SPACING;
+                self.spawn(rect.min + offset, Vec2::ZERO);
+            }
+        }
+    }
+
+    fn remove(&mut self, index: usize) {
+        self.positions.swap_remove(index);
+        self.velocities.swap_remove(index);
+    }
+
+    // every pair closer than `SMOOTHING`, both ways round
+    fn find_neighbors(&mut self) {
+        let cell = |position: Vec2| (position / SMOOTHING).floor().as_ivec2();
+        for particles in self.grid.values_mut() {
+            particles.clear();
+        }
+        for (index, position) in self.positions.iter().enumerate() {
+            self.grid.entry(cell(*position)).or_default().push(index);
+        }
+        self.grid.retain(|_, particles| !particles.is_empty());
+
+        self.neighbors.resize_with(self.positions.len(), Vec::new);
+        for (index, position) in self.positions.iter().enumerate() {
+            let neighbors = &mut self.neighbors[index];
+            neighbors.clear();
+            let center = cell(*position);
+            for x in -1..=1 {
+                for y in -1..=1 {
+                    let Some(particles) = self.grid.get(&(center + IVec2::new(x, y))) else {
+                        continue;
+                    };
+                    neighbors.extend(particles.iter().copied().filter(|other| {
+                        *other != index
+                            && self.positions[*other].distance_squared(*position)
+                                < SMOOTHING * SMOOTHING
+                    }));
+                }
+            }
+        }
+    }
+
+    /// Moves everything forward by `dt`, without touching any bodies
+    fn step(&mut self, dt: f32, gravity: Vec2, viscosity: f32) {
+        for velocity in self.velocities.iter_mut() {
+            *velocity += gravity * dt;
+        }
+        self.find_neighbors();
+
+        // viscosity, pairs moving towards each other even out their speeds
+        for i in 0..self.len() {
+            for &j in self.neighbors[i].iter().filter(|j| **j > i) {
+                let delta = self.positions[j] - self.positions[i];
+                let distance = delta.length();
+                if distance < f32::EPSILON {
+                    continue;
+                }
+                let direction = delta / distance;
+                let q = distance / SMOOTHING;
+                let approach = (self.velocities[i] - self.velocities[j]).dot(direction);
+                if approach > 0. {
+                    let impulse = direction
+                        * (dt
+                            * (1. - q)
+                            * (viscosity * approach + QUADRATIC_VISCOSITY * approach * approach));
+                    self.velocities[i] -= impulse / 2.;
+                    self.velocities[j] += impulse / 2.;
+                }
+            }
+        }
+
+        self.previous.clone_from(&self.positions);
+        for (position, velocity) in self.positions.iter_mut().zip(self.velocities.iter()) {
+            *position += *velocity * dt;
+        }
+
+        // double density relaxation, pushes crowded particles apart
+        for i in 0..self.len() {
+            let mut density = 0.;
+            let mut near_density = 0.;
+            for &j in self.neighbors[i].iter() {
+                let q = self.positions[j].distance(self.positions[i]) / SMOOTHING;
+                if q < 1. {
+                    density += (1. - q).powi(2);
+                    near_density += (1. - q).powi(3);
+                }
+            }
+            let pressure = STIFFNESS * (density - REST_DENSITY);
+            let near_pressure = NEAR_STIFFNESS * near_density;
+            let mut moved = Vec2::ZERO;
+            for &j in self.neighbors[i].iter() {
+                let delta = self.positions[j] - self.positions[i];
+                let distance = delta.length();
+                let q = distance / SMOOTHING;
+                if q >= 1. || distance < f32::EPSILON {
+                    continue;
+                }
+                let displacement = delta / distance
+                    * (dt * dt * (pressure * (1. - q) + near_pressure * (1. - q).powi(2)))
+                    * SMOOTHING;
+                self.positions[j] += displacement / 2.;
+                moved -= displacement / 2.;
+            }
+            self.positions[i] += moved;
+        }
+
+        for ((velocity, position), previous) in self
+            .velocities
+            .iter_mut()
+            .zip(self.positions.iter())
+            .zip(self.previous.iter())
+        {
+            *velocity = (*position - *previous) / dt;
+        }
+    }
+
+    /// Pushes particles out of colliders, and the colliders' bodies the other way
+    fn collide(&mut self, rapier_context: &mut RapierContext) {
+        let scale = rapier_context.physics_scale();
+        // kg, the water each particle stands for
+        let mass = DENSITY * SPACING * SPACING / (scale * scale);
+        let mut impulses: Vec<(RigidBodyHandle, Vec2, Vec2)> = Vec::new();
+        // straight to rapier, we want its collider handles rather than entities
+        let filter = bevy_rapier2d::rapier::pipeline::QueryFilter::default().exclude_sensors();
+        for index in 0..self.len() {
+            let position = self.positions[index];
+            let Some((collider, projection)) = rapier_context.query_pipeline.project_point(
+                &rapier_context.bodies,
+                &rapier_context.colliders,
+                &Point::new(position.x / scale, position.y / scale),
+                false,
+                filter,
+            ) else {
+                continue;
+            };
+            let projected = Vec2::new(projection.point.x, projection.point.y) * scale;
+            let offset = position - projected;
+            let distance = offset.length();
+            if (!projection.is_inside && distance >= PARTICLE_RADIUS) || distance < f32::EPSILON {
+                continue;
+            }
+            let normal = if projection.is_inside {
+                -offset / distance
+            } else {
+                offset / distance
+            };
+            self.positions[index] = projected + normal * PARTICLE_RADIUS;
+
+            let body = rapier_context
+                .colliders
+                .get(collider)
+                .and_then(|collider| collider.parent())
+                .and_then(|handle| Some((handle, rapier_context.bodies.get(handle)?)));
+            let surface_velocity = body
+                .map(|(_, body)| {
+                    let velocity = body.velocity_at_point(&projection.point);
+                    Vec2::new(velocity.x, velocity.y) * scale
+                })
+                .unwrap_or(Vec2::ZERO);
+
+            // only stop it going into the surface, it can still slide along and leave
+            let velocity = self.velocities[index];
+            let relative = velocity - surface_velocity;
+            let normal_speed = relative.dot(normal);
+            if normal_speed >= 0. {
+                continue;
+            }
+            let sliding = (relative - normal * normal_speed) * (1. - FRICTION);
+            self.velocities[index] = surface_velocity + sliding;
+
+            if let Some((handle, body)) = body {
+                if body.is_dynamic() {
+                    impulses.push((
+                        handle,
+                        (velocity - self.velocities[index]) * mass,
+                        projected,
+                    ));
+                }
+            }
+        }
+
+        for (handle, impulse, point) in impulses {
+            if let Some(body) = rapier_context.bodies.get_mut(handle) {
+                body.apply_impulse_at_point(
+                    Vector::new(impulse.x / scale, impulse.y / scale),
+                    Point::new(point.x / scale, point.y / scale),
+                    true,
+                );
+            }
+        }
+    }
+}
+
+/// Just the simulation, no tool or drawing, so benchmarks can run it without a window
+pub struct FluidSimulationPlugin;
+
+impl Plugin for FluidSimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Fluid>()
+            .init_resource::<FluidSettings>()
+            .add_systems(PostUpdate, step_fluid.in_set(StepTimed));
+    }
+}
+
+pub struct FluidPlugin;
+
+impl Plugin for FluidPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            FluidSimulationPlugin,
+            Material2dPlugin::<FluidMaterial>::default(),
+        ))
+        .init_resource::<Pouring>()
+        .add_systems(Startup, spawn_fluid_surface)
+        .add_systems(Update, pour.in_set(EguiUnfocusedSystemSet))
+        .add_systems(Update, fluid_ui)
+        .add_systems(
+            PostUpdate,
+            update_fluid_surface
+                .after(PhysicsSet::Writeback)
+                .before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+// runs right before rapier steps, so bodies see the fluid's push in the same step
+fn step_fluid(
+    time: Res<Time>,
+    settings: Res<FluidSettings>,
+    rapier_config: Res<RapierConfiguration>,
+    mut rapier_context: ResMut<RapierContext>,
+    mut fluid: ResMut<Fluid>,
+) {
+    if fluid.is_empty() || !rapier_config.physics_pipeline_active {
+        return;
+    }
+    // the same amount of time rapier is about to step
+    let dt = match rapier_config.timestep_mode {
+        TimestepMode::Fixed { dt, .. } | TimestepMode::Interpolated { dt, .. } => dt,
+        TimestepMode::Variable {
+            max_dt, time_scale, ..
+        } => (time.delta_seconds() * time_scale).min(max_dt),
+    };
+    if dt <= 0. {
+        return;
+    }
+    let substeps = (dt / MAX_SUBSTEP).ceil().max(1.);
+    for _ in 0..substeps as u32 {
+        fluid.step(dt / substeps, rapier_config.gravity, settings.viscosity);
+        fluid.collide(&mut rapier_context);
+    }
+
+    let mut index = 0;
+    while index < fluid.len() {
+        if fluid.positions[index].length_squared() > KILL_DISTANCE * KILL_DISTANCE {
+            fluid.remove(index);
+        } else {
+            index += 1;
+        }
+    }
+}
+
+/// Leftover fraction of a particle between frames, so low pour rates still pour
+#[derive(Resource, Default)]
+struct Pouring {
+    carry: f32,
+    poured: u32,
+}
+
+// hold the mouse down with the pour tool to pour fluid out of the cursor
+fn pour(
+    time: Res<Time>,
+    buttons: Res<Input<MouseButton>>,
+    tool_res: Res<Tools>,
+    settings: Res<FluidSettings>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut pouring: ResMut<Pouring>,
+    mut fluid: ResMut<Fluid>,
+) {
+    if tool_res.current_tool != Tool::Pour || !buttons.pressed(MouseButton::Left) {
+        pouring.carry = 0.;
+        return;
+    }
+    let (camera, camera_transform) = camera_query.single();
+    let Some(world_position) = cursor_world_position(q_window.single(), camera, camera_transform)
+    else {
+        return;
+    };
+    pouring.carry += settings.pour_rate * time.delta_seconds();
+    while pouring.carry >= 1. {
+        pouring.carry -= 1.;
+        if fluid.len() >= settings.max_particles {
+            continue;
+        }
+        // golden angle spiral, so particles poured on the same frame never start on top of each other
+        pouring.poured = pouring.poured.wrapping_add(1);
+        let angle = pouring.poured as f32 * 2.399_963;
+        let distance = (pouring.poured % 16) as f32 / 16. * SPACING * 1.5;
+        fluid.spawn(
+            world_position + Vec2::from_angle(angle) * distance,
+            Vec2::new(0., -30.),
+        );
+    }
+}
+
+fn fluid_ui(
+    mut contexts: EguiContexts,
+    tool_res: Res<Tools>,
+    mut settings: ResMut<FluidSettings>,
+    mut fluid: ResMut<Fluid>,
+) {
+    if tool_res.current_tool != Tool::Pour {
+        return;
+    }
+    egui::Window::new("Fluid").show(contexts.ctx_mut(), |ui| {
+        ui.label(format!(
+            "{} / {} particles",
+            fluid.len(),
+            settings.max_particles
+        ));
+        ui.add(egui::Slider::new(&mut settings.viscosity, 0.0..=50.).text("Viscosity"));
+        ui.add(egui::Slider::new(&mut settings.max_particles, 100..=10_000).text("Max particles"));
+        ui.add(egui::Slider::new(&mut settings.pour_rate, 10.0..=1000.).text("Pour rate (/s)"));
+        ui.horizontal(|ui| {
+            ui.label("Color");
+            let mut color = settings.color.as_rgba_f32();
+            if ui.color_edit_button_rgba_unmultiplied(&mut color).changed() {
+                settings.color = Color::rgba(color[0], color[1], color[2], color[3]);
+            }
+        });
+        if ui.button("Clear").clicked() {
+            fluid.clear();
+        }
+    });
+    // lowering the limit drains the newest particles
+    while fluid.len() > settings.max_particles {
+        let last = fluid.len() - 1;
+        fluid.remove(last);
+    }
+}
+
+/// Draws the fluid as one surface: particles are splatted into a density texture on the cpu
+/// and the shader cuts it off at a threshold.
+#[derive(AsBindGroup, Debug, Clone, Asset, TypePath)]
+pub struct FluidMaterial {
+    #[uniform(0)]
+    pub color: Color,
+    #[uniform(0)]
+    pub threshold: f32,
+    #[texture(1)]
+    #[sampler(2)]
+    pub field: Handle<Image>,
+}
+
+impl Material2d for FluidMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/fluid.wgsl".into()
+    }
+}
+
+/// The quad the fluid is drawn on, stretched over all the particles every frame
+#[derive(Component)]
+struct FluidSurface;
+
+fn spawn_fluid_surface(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<FluidMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+    settings: Res<FluidSettings>,
+) {
+    let field = images.add(field_image(1, 1, vec![0]));
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: Mesh2dHandle(meshes.add(shape::Quad::new(Vec2::ONE).into())),
+            material: materials.add(FluidMaterial {
+                color: settings.color,
+                threshold: THRESHOLD,
+                field,
+            }),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        FluidSurface,
+    ));
+}
+
+fn field_image(width: u32, height: u32, data: Vec<u8>) -> Image {
+    let mut image = Image::new(
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::R8Unorm,
+    );
+    // smooth between texels so the threshold gives a smooth edge
+    image.sampler = ImageSampler::linear();
+    image
+}
+
+fn update_fluid_surface(
+    fluid: Res<Fluid>,
+    settings: Res<FluidSettings>,
+    mut surface: Query<
+        (&Handle<FluidMaterial>, &mut Transform, &mut Visibility),
+        With<FluidSurface>,
+    >,
+    mut materials: ResMut<Assets<FluidMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let Ok((material_handle, mut transform, mut visibility)) = surface.get_single_mut() else {
+        return;
+    };
+    if fluid.is_empty() {
+        *visibility = Visibility::Hidden;
+        return;
+    }
+    *visibility = Visibility::Visible;
+
+    let mut min = Vec2::splat(f32::MAX);
+    let mut max = Vec2::splat(f32::MIN);
+    for position in fluid.positions() {
+        min = min.min(*position);
+        max = max.max(*position);
+    }
+    min -= Vec2::splat(BLOB_RADIUS);
+    max += Vec2::splat(BLOB_RADIUS);
+    let size = max - min;
+    let texel = TEXEL_SIZE.max(size.max_element() / MAX_TEXTURE_SIZE);
+    let width = (size.x / texel).ceil() as usize + 1;
+    let height = (size.y / texel).ceil() as usize + 1;
+
+    let mut field = vec![0f32; width * height];
+    let reach = (BLOB_RADIUS / texel).ceil() as isize;
+    for position in fluid.positions() {
+        // row 0 is the top of the quad
+        let x = (position.x - min.x) / texel;
+        let y = (max.y - position.y) / texel;
+        let (column, row) = (x as isize, y as isize);
+        for texel_row in (row - reach).max(0)..=(row + reach).min(height as isize - 1) {
+            for texel_column in (column - reach).max(0)..=(column + reach).min(width as isize - 1) {
+                let offset =
+                    Vec2::new(texel_column as f32 + 0.5 - x, texel_row as f32 + 0.5 - y) * texel;
+                let falloff = 1. - offset.length_squared() / (BLOB_RADIUS * BLOB_RADIUS);
+                if falloff > 0. {
+                    field[texel_row as usize * width + texel_column as usize] += falloff * falloff;
+                }
+            }
+        }
+    }
+    let data = field
+        .into_iter()
+        .map(|value| (value.min(1.) * 255.) as u8)
+        .collect();
+
+    // the texels cover a little more than `size` since they were rounded up
+    let covered = Vec2::new(width as f32, height as f32) * texel;
+    transform.translation = Vec3::new(min.x + covered.x / 2., max.y - covered.y / 2., FLUID_Z);
+    transform.scale = covered.extend(1.);
+
+    // getting the material mutably is what makes bevy rebind the new texture
+    let Some(material) = materials.get_mut(material_handle) else {
+        return;
+    };
+    material.color = settings.color;
+    if let Some(image) = images.get_mut(&material.field) {
+        *image = field_image(width as u32, height as u32, data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_rapier2d::rapier::prelude::{ColliderBuilder, RigidBodyBuilder};
+
+    const GRAVITY: Vec2 = Vec2::new(0., -9.81 * 12.);
+
+    // one box body, set up the way rapier holds it, no app needed
+    fn context_with(
+        body: RigidBodyBuilder,
+        half_extents: Vec2,
+    ) -> (RapierContext, RigidBodyHandle) {
+        let mut context = RapierContext::default();
+        let handle = context.bodies.insert(body);
+        context.colliders.insert_with_parent(
+            ColliderBuilder::cuboid(half_extents.x, half_extents.y),
+            handle,
+            &mut context.bodies,
+        );
+        context
+            .query_pipeline
+            .update(&context.bodies, &context.colliders);
+        (context, handle)
+    }
+
+    #[test]
+    fn fill_rect_packs_a_grid() {
+        let mut fluid = Fluid::default();
+        fluid.fill_rect(Rect::new(0., 0., 10. * SPACING, 5. * SPACING), 1000);
+        assert_eq!(fluid.len(), 50);
+        let positions = fluid.positions();
+        assert_eq!(positions[0], Vec2::splat(SPACING / 2.));
+        assert_eq!(positions[1] - positions[0], Vec2::new(SPACING, 0.));
+        assert_eq!(positions[10] - positions[0], Vec2::new(0., SPACING));
+
+        let mut capped = Fluid::default();
+        capped.fill_rect(Rect::new(0., 0., 10. * SPACING, 5. * SPACING), 20);
+        assert_eq!(capped.len(), 20);
+    }
+
+    #[test]
+    fn particles_settle_on_a_fixed_box() {
+        // top of the box at y = 10, wide enough that the puddle stays on it
+        let (mut context, _) = context_with(RigidBodyBuilder::fixed(), Vec2::new(200., 10.));
+        let mut fluid = Fluid::default();
+        fluid.fill_rect(Rect::new(-15., 20., 15., 35.), 1000);
+
+        for _ in 0..(3. / MAX_SUBSTEP) as u32 {
+            fluid.step(MAX_SUBSTEP, GRAVITY, 2.);
+            fluid.collide(&mut context);
+        }
+
+        for position in fluid.positions() {
+            assert!(position.y > 10., "particle fell into the box at {position}");
+            assert!(
+                position.x.abs() < 200.,
+                "particle ran off the box at {position}"
+            );
+        }
+        let average_speed =
+            fluid.velocities.iter().map(|v| v.length()).sum::<f32>() / fluid.len() as f32;
+        assert!(average_speed < 20., "still moving at {average_speed}");
+    }
+
+    #[test]
+    fn falling_particles_push_a_dynamic_body() {
+        let (mut context, handle) = context_with(RigidBodyBuilder::dynamic(), Vec2::new(20., 5.));
+        let mut fluid = Fluid::default();
+        for i in -5..5 {
+            fluid.spawn(Vec2::new(i as f32 * SPACING, 6.), Vec2::new(0., -50.));
+        }
+
+        fluid.collide(&mut context);
+
+        let body = &context.bodies[handle];
+        assert!(body.linvel().y < 0., "body didn't get pushed down");
+        for velocity in fluid.velocities.iter() {
+            assert!(velocity.y >= 0., "particle still going into the body");
+        }
+    }
+}
@@ -0,0 +1,421 @@
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+use bevy_egui::egui;
+use bevy_prototype_lyon::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::matter::{stroke_for, Matter};
+use crate::settings::{next_bound_key, KeyCaptureSet};
+
+const NOZZLE_COLOR: Color = Color::rgb(0.35, 0.35, 0.4);
+const FLAME_COLOR: Color = Color::rgb(1., 0.6, 0.15);
+const FLAME_LENGTH: f32 = 6.;
+const ATTRACT_COLOR: Color = Color::rgb(0.4, 0.6, 1.);
+const REPEL_COLOR: Color = Color::rgb(1., 0.45, 0.4);
+
+/// Pushes its parent body along its own local up while firing. Lives on a child of the body,
+/// so its `Transform` is where it sits and which way it points.
+#[derive(Component, Clone)]
+pub struct Thruster {
+    pub strength: f32,
+    /// fires while this is held, always on without one
+    pub key: Option<KeyCode>,
+    firing: bool,
+}
+
+impl Default for Thruster {
+    fn default() -> Self {
+        Self {
+            strength: 300.,
+            key: Some(KeyCode::Up),
+            firing: false,
+        }
+    }
+}
+
+/// The force and torque thrusters added to this body's `ExternalForce` last frame, so we can
+/// take it back out without stomping on anything else using it (like active ragdolls)
+#[derive(Component, Default)]
+struct AppliedThrust {
+    force: Vec2,
+    torque: f32,
+}
+
+#[derive(Component)]
+struct ThrusterFlame;
+
+/// Pushes dynamic bodies within `radius` away from it, or pulls them in with a negative strength
+#[derive(Component, Clone)]
+pub struct ForceField {
+    pub radius: f32,
+    /// acceleration at the center, falls off to nothing at the edge
+    pub strength: f32,
+}
+
+impl Default for ForceField {
+    fn default() -> Self {
+        Self {
+            radius: 40.,
+            strength: 300.,
+        }
+    }
+}
+
+/// Which thruster is waiting for a key press
+#[derive(Resource, Default)]
+pub struct ThrusterRebinding(Option<Entity>);
+
+impl ThrusterRebinding {
+    pub fn waiting(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+pub struct ForcesPlugin;
+
+impl Plugin for ForcesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ThrusterRebinding>().add_systems(
+            Update,
+            (
+                (
+                    capture_thruster_key.in_set(KeyCaptureSet),
+                    prepare_thrust_bodies,
+                    fire_thrusters,
+                )
+                    .chain(),
+                animate_flames.after(fire_thrusters),
+                (apply_force_fields, draw_force_fields),
+            ),
+        );
+    }
+}
+
+/// Sticks a thruster on `body` at a point in its local space
+pub fn attach_thruster(commands: &mut Commands, body: Entity, local_point: Vec2, angle: f32) {
+    let thruster = commands
+        .spawn((
+            SpatialBundle::from_transform(
+                Transform::from_translation(local_point.extend(0.1))
+                    .with_rotation(Quat::from_rotation_z(angle)),
+            ),
+            Thruster::default(),
+        ))
+        .with_children(|children| {
+            children.spawn((
+                ShapeBundle {
+                    path: GeometryBuilder::build_as(&shapes::Polygon {
+                        points: vec![
+                            Vec2::new(-0.8, 0.),
+                            Vec2::new(0.8, 0.),
+                            Vec2::new(1.4, -2.),
+                            Vec2::new(-1.4, -2.),
+                        ],
+                        closed: true,
+                    }),
+                    ..default()
+                },
+                Fill::color(NOZZLE_COLOR),
+                Stroke::new(stroke_for(NOZZLE_COLOR), 0.3),
+            ));
+            children.spawn((
+                ShapeBundle {
+                    path: GeometryBuilder::build_as(&shapes::Polygon {
+                        points: vec![
+                            Vec2::new(-1.2, 0.),
+                            Vec2::new(1.2, 0.),
+                            Vec2::new(0., -FLAME_LENGTH),
+                        ],
+                        closed: true,
+                    }),
+                    spatial: SpatialBundle {
+                        transform: Transform::from_xyz(0., -2., -0.05),
+                        visibility: Visibility::Hidden,
+                        ..default()
+                    },
+                    ..default()
+                },
+                Fill::color(FLAME_COLOR),
+                ThrusterFlame,
+            ));
+        })
+        .id();
+    commands.entity(body).add_child(thruster);
+}
+
+/// A free standing force field. It has a small fixed sensor so it can be right clicked, moved and deleted like a body.
+pub fn spawn_force_field(commands: &mut Commands, at: Vec2) {
+    commands.spawn((
+        SpatialBundle::from_transform(Transform::from_translation(at.extend(0.))),
+        Matter::new(Color::rgb(0.55, 0.45, 0.85)),
+        Collider::ball(1.5),
+        Sensor,
+        RigidBody::Fixed,
+        ForceField::default(),
+    ));
+}
+
+// the body needs somewhere to put the force, and its center of mass for the torque
+fn prepare_thrust_bodies(
+    mut commands: Commands,
+    thrusters: Query<&Parent, Added<Thruster>>,
+    bodies: Query<(
+        Option<&ExternalForce>,
+        Option<&ReadMassProperties>,
+        Option<&AppliedThrust>,
+    )>,
+) {
+    for parent in thrusters.iter() {
+        let Ok((force, mass, applied)) = bodies.get(parent.get()) else {
+            continue;
+        };
+        let mut body = commands.entity(parent.get());
+        if force.is_none() {
+            body.insert(ExternalForce::default());
+        }
+        if mass.is_none() {
+            body.insert(ReadMassProperties::default());
+        }
+        if applied.is_none() {
+            body.insert(AppliedThrust::default());
+        }
+    }
+}
+
+fn fire_thrusters(
+    keys: Res<Input<KeyCode>>,
+    mut thrusters: Query<(&mut Thruster, &Parent, &GlobalTransform)>,
+    mut bodies: Query<(
+        Entity,
+        &GlobalTransform,
+        &ReadMassProperties,
+        &mut ExternalForce,
+        &mut AppliedThrust,
+    )>,
+) {
+    let mut totals: HashMap<Entity, (Vec2, f32)> = HashMap::new();
+    for (mut thruster, parent, global_transform) in thrusters.iter_mut() {
+        let firing = thruster.key.map_or(true, |key| keys.pressed(key));
+        if thruster.firing != firing {
+            thruster.firing = firing;
+        }
+        if !firing {
+            continue;
+        }
+        let Ok((_, body_transform, mass_props, ..)) = bodies.get(parent.get()) else {
+            continue;
+        };
+        let center_of_mass = body_transform
+            .transform_point(mass_props.local_center_of_mass.extend(0.))
+            .truncate();
+        let force = global_transform.up().truncate() * thruster.strength;
+        let arm = global_transform.translation().truncate() - center_of_mass;
+        let total = totals.entry(parent.get()).or_default();
+        total.0 += force;
+        total.1 += arm.perp_dot(force);
+    }
+
+    for (entity, _, _, mut external, mut applied) in bodies.iter_mut() {
+        let (force, torque) = totals.get(&entity).copied().unwrap_or_default();
+        if force == applied.force && torque == applied.torque {
+            continue;
+        }
+        external.force += force - applied.force;
+        external.torque += torque - applied.torque;
+        applied.force = force;
+        applied.torque = torque;
+    }
+}
+
+// flames flicker while their thruster fires
+fn animate_flames(
+    time: Res<Time>,
+    thrusters: Query<(Entity, &Thruster, &Children)>,
+    mut flames: Query<(&mut Visibility, &mut Transform), With<ThrusterFlame>>,
+) {
+    for (entity, thruster, children) in thrusters.iter() {
+        for child in children.iter() {
+            let Ok((mut visibility, mut transform)) = flames.get_mut(*child) else {
+                continue;
+            };
+            let wanted = if thruster.firing {
+                Visibility::Inherited
+            } else {
+                Visibility::Hidden
+            };
+            if *visibility != wanted {
+                *visibility = wanted;
+            }
+            if thruster.firing {
+                // a different phase for each thruster so they don't all pulse together
+                let t = time.elapsed_seconds() * 30. + entity.index() as f32;
+                transform.scale.y = 0.8 + 0.2 * t.sin() + 0.1 * (t * 2.3).sin();
+            }
+        }
+    }
+}
+
+fn apply_force_fields(
+    mut commands: Commands,
+    time: Res<Time>,
+    rapier_context: Res<RapierContext>,
+    fields: Query<(&ForceField, &GlobalTransform)>,
+    mut bodies: Query<(&GlobalTransform, Option<&mut ExternalImpulse>)>,
+) {
+    let dt = time.delta_seconds();
+    let mut impulses: HashMap<Entity, Vec2> = HashMap::new();
+    for (field, field_transform) in fields.iter() {
+        let center = field_transform.translation().truncate();
+        let mut in_range = HashSet::new();
+        rapier_context.intersections_with_shape(
+            center,
+            0.,
+            &Collider::ball(field.radius),
+            QueryFilter::only_dynamic(),
+            |collider| {
+                in_range.insert(rapier_context.collider_parent(collider).unwrap_or(collider));
+                true
+            },
+        );
+        for body in in_range {
+            let Ok((body_transform, _)) = bodies.get(body) else {
+                continue;
+            };
+            let Some(mass) = rapier_context
+                .entity2body()
+                .get(&body)
+                .and_then(|handle| rapier_context.bodies.get(*handle))
+                .map(|rigid_body| rigid_body.mass())
+            else {
+                continue;
+            };
+            let offset = body_transform.translation().truncate() - center;
+            let falloff = 1. - (offset.length() / field.radius).min(1.);
+            // same acceleration for everything, like gravity
+            *impulses.entry(body).or_default() +=
+                offset.normalize_or_zero() * field.strength * falloff * mass * dt;
+        }
+    }
+
+    for (body, impulse) in impulses {
+        match bodies.get_mut(body) {
+            Ok((_, Some(mut external))) => external.impulse += impulse,
+            Ok((_, None)) => {
+                commands.entity(body).insert(ExternalImpulse {
+                    impulse,
+                    ..default()
+                });
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+fn draw_force_fields(fields: Query<(&ForceField, &GlobalTransform)>, mut gizmos: Gizmos) {
+    for (field, transform) in fields.iter() {
+        let center = transform.translation().truncate();
+        let color = if field.strength >= 0. {
+            REPEL_COLOR
+        } else {
+            ATTRACT_COLOR
+        };
+        gizmos.circle_2d(center, field.radius, color);
+        gizmos.circle_2d(center, field.radius / 2., color.with_a(0.4));
+    }
+}
+
+// the next bindable key pressed after clicking a thruster's key takes its place
+fn capture_thruster_key(
+    keys: Res<Input<KeyCode>>,
+    mut rebinding: ResMut<ThrusterRebinding>,
+    mut thrusters: Query<&mut Thruster>,
+) {
+    let Some(entity) = rebinding.0 else {
+        return;
+    };
+    let Ok(mut thruster) = thrusters.get_mut(entity) else {
+        rebinding.0 = None;
+        return;
+    };
+    let Some(pressed) = next_bound_key(&keys) else {
+        return;
+    };
+    if let Ok(key) = pressed {
+        thruster.key = Some(key);
+    }
+    rebinding.0 = None;
+}
+
+/// One thruster's settings in the inspector, returns true if it should be removed
+pub fn thruster_ui(
+    ui: &mut egui::Ui,
+    entity: Entity,
+    thruster: &mut Thruster,
+    transform: &mut Transform,
+    rebinding: &mut ThrusterRebinding,
+) -> bool {
+    let mut remove = false;
+    ui.horizontal(|ui| {
+        ui.label("Strength");
+        ui.add(
+            egui::DragValue::new(&mut thruster.strength)
+                .speed(10.)
+                .clamp_range(0.0..=f32::MAX),
+        );
+    });
+    ui.horizontal(|ui| {
+        ui.label("Angle");
+        let mut degrees = transform.rotation.to_euler(EulerRot::XYZ).2.to_degrees();
+        if ui
+            .add(egui::DragValue::new(&mut degrees).suffix("°"))
+            .changed()
+        {
+            transform.rotation = Quat::from_rotation_z(degrees.to_radians());
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("Key");
+        let text = if rebinding.0 == Some(entity) {
+            "Press a key...".to_string()
+        } else {
+            match thruster.key {
+                Some(key) => format!("{:?}", key),
+                None => "Always on".to_string(),
+            }
+        };
+        if ui.button(text).clicked() {
+            rebinding.0 = Some(entity);
+        }
+        if thruster.key.is_some() && ui.small_button("x").clicked() {
+            thruster.key = None;
+        }
+        if ui.button("Remove").clicked() {
+            remove = true;
+        }
+    });
+    remove
+}
+
+/// Force field section of the inspector
+pub fn force_field_ui(ui: &mut egui::Ui, field: &mut ForceField) {
+    ui.horizontal(|ui| {
+        ui.label("Radius");
+        ui.add(
+            egui::DragValue::new(&mut field.radius)
+                .speed(0.5)
+                .clamp_range(1.0..=f32::MAX),
+        );
+    });
+    ui.horizontal(|ui| {
+        ui.label("Strength");
+        ui.add(egui::DragValue::new(&mut field.strength).speed(10.));
+    });
+    ui.horizontal(|ui| {
+        if ui.button("Repel").clicked() {
+            field.strength = field.strength.abs();
+        }
+        if ui.button("Attract").clicked() {
+            field.strength = -field.strength.abs();
+        }
+    });
+}
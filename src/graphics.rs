@@ -0,0 +1,145 @@
+#[cfg(not(target_arch = "wasm32"))]
+use bevy::utils::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use bevy::utils::Instant;
+use bevy::window::{PresentMode, PrimaryWindow, WindowMode};
+use bevy::{prelude::*, render::view::Msaa};
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+
+use crate::settings::KeyBindings;
+use crate::EguiUnfocusedSystemSet;
+
+// browsers only do vsync
+#[cfg(target_arch = "wasm32")]
+const PRESENT_MODES: &[(PresentMode, &str)] = &[(PresentMode::AutoVsync, "Vsync")];
+#[cfg(not(target_arch = "wasm32"))]
+const PRESENT_MODES: &[(PresentMode, &str)] = &[
+    (PresentMode::AutoVsync, "Vsync"),
+    (PresentMode::AutoNoVsync, "No vsync (may tear)"),
+    (PresentMode::Immediate, "Immediate (lowest latency, tears)"),
+    (PresentMode::Mailbox, "Mailbox (fast vsync, not everywhere)"),
+    (PresentMode::Fifo, "Fifo (strict vsync)"),
+];
+
+const MSAA_LEVELS: [(Msaa, &str); 4] = [
+    (Msaa::Off, "Off"),
+    (Msaa::Sample2, "2x"),
+    (Msaa::Sample4, "4x"),
+    (Msaa::Sample8, "8x"),
+];
+
+#[cfg(not(target_arch = "wasm32"))]
+const FRAME_CAPS: [u32; 5] = [30, 60, 120, 144, 240];
+
+#[derive(Resource, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphicsSettings {
+    /// frames per second we won't go over, `None` for as fast as possible
+    pub frame_cap: Option<u32>,
+    pub fullscreen: bool,
+}
+
+pub struct GraphicsPlugin;
+
+impl Plugin for GraphicsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GraphicsSettings>()
+            .add_systems(Update, toggle_fullscreen.in_set(EguiUnfocusedSystemSet))
+            .add_systems(Update, (graphics_ui, apply_window_mode).chain());
+        #[cfg(not(target_arch = "wasm32"))]
+        app.add_systems(Last, limit_frame_rate);
+    }
+}
+
+fn toggle_fullscreen(
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut graphics: ResMut<GraphicsSettings>,
+) {
+    if keys.just_pressed(bindings.toggle_fullscreen) {
+        graphics.fullscreen = !graphics.fullscreen;
+    }
+}
+
+fn graphics_ui(
+    mut contexts: EguiContexts,
+    mut graphics: ResMut<GraphicsSettings>,
+    mut msaa: ResMut<Msaa>,
+    mut q_window: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let Ok(mut window) = q_window.get_single_mut() else {
+        return;
+    };
+    let mut present_mode = window.present_mode;
+    let mut samples = *msaa;
+    let mut edited = graphics.clone();
+
+    egui::Window::new("Graphics")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label("Present mode");
+            for (mode, label) in PRESENT_MODES {
+                ui.radio_value(&mut present_mode, *mode, *label);
+            }
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                ui.label("Anti-aliasing");
+                for (level, label) in MSAA_LEVELS {
+                    ui.selectable_value(&mut samples, level, label);
+                }
+            });
+
+            // there's no sleeping in the browser, it's capped by vsync anyway
+            #[cfg(not(target_arch = "wasm32"))]
+            ui.horizontal(|ui| {
+                ui.label("Frame cap");
+                ui.selectable_value(&mut edited.frame_cap, None, "None");
+                for cap in FRAME_CAPS {
+                    ui.selectable_value(&mut edited.frame_cap, Some(cap), cap.to_string());
+                }
+            });
+
+            ui.checkbox(&mut edited.fullscreen, "Fullscreen");
+        });
+
+    if present_mode != window.present_mode {
+        window.present_mode = present_mode;
+    }
+    msaa.set_if_neq(samples);
+    graphics.set_if_neq(edited);
+}
+
+fn apply_window_mode(
+    graphics: Res<GraphicsSettings>,
+    mut q_window: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    if !graphics.is_changed() {
+        return;
+    }
+    let Ok(mut window) = q_window.get_single_mut() else {
+        return;
+    };
+    let mode = if graphics.fullscreen {
+        WindowMode::BorderlessFullscreen
+    } else {
+        WindowMode::Windowed
+    };
+    if window.mode != mode {
+        window.mode = mode;
+    }
+}
+
+// sleep off whatever is left of the frame's time budget
+#[cfg(not(target_arch = "wasm32"))]
+fn limit_frame_rate(graphics: Res<GraphicsSettings>, mut last_frame: Local<Option<Instant>>) {
+    if let (Some(cap), Some(last)) = (graphics.frame_cap, *last_frame) {
+        let budget = Duration::from_secs_f64(1. / cap.max(1) as f64);
+        let elapsed = last.elapsed();
+        if elapsed < budget {
+            std::thread::sleep(budget - elapsed);
+        }
+    }
+    *last_frame = Some(Instant::now());
+}
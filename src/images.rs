@@ -0,0 +1,490 @@
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use bevy::render::texture::{CompressedImageFormats, ImageSampler, ImageType};
+use bevy_egui::{egui, EguiContexts};
+use bevy_rapier2d::prelude::*;
+
+use crate::inspector::Selected;
+use crate::matter::{Matter, MatterShape, MatterTexture, TextureMode};
+use crate::MainCamera;
+
+/// An image the user brought in from disk. We keep the original bytes so the scene can save a copy next to itself.
+pub struct ImportedImage {
+    /// file name, unique among imported images
+    pub name: String,
+    pub bytes: Vec<u8>,
+    pub handle: Handle<Image>,
+}
+
+#[derive(Resource, Default)]
+pub struct ImportedImages {
+    pub images: Vec<ImportedImage>,
+}
+
+impl ImportedImages {
+    pub fn by_name(&self, name: &str) -> Option<&ImportedImage> {
+        self.images.iter().find(|image| image.name == name)
+    }
+
+    pub fn by_handle(&self, handle: &Handle<Image>) -> Option<&ImportedImage> {
+        self.images.iter().find(|image| &image.handle == handle)
+    }
+
+    /// Decode and add an image. A different image with the same name gets a new name
+    /// (`wood-2.png`), bodies might still be using the old one.
+    pub fn import(
+        &mut self,
+        images: &mut Assets<Image>,
+        name: String,
+        bytes: Vec<u8>,
+    ) -> Result<Handle<Image>, String> {
+        // the same picture again, just use the one we have
+        if let Some(existing) = self
+            .images
+            .iter()
+            .find(|image| image.name == name && image.bytes == bytes)
+        {
+            return Ok(existing.handle.clone());
+        }
+
+        let extension = Path::new(&name)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or("png")
+            .to_lowercase();
+        let image = Image::from_buffer(
+            &bytes,
+            ImageType::Extension(&extension),
+            CompressedImageFormats::NONE,
+            true,
+            ImageSampler::Default,
+        )
+        .map_err(|err| format!("Couldn't read {}: {}", name, err))?;
+        let handle = images.add(image);
+
+        let name = self.unique_name(&name);
+        self.images.push(ImportedImage {
+            name,
+            bytes,
+            handle: handle.clone(),
+        });
+        Ok(handle)
+    }
+
+    fn unique_name(&self, name: &str) -> String {
+        if self.by_name(name).is_none() {
+            return name.to_string();
+        }
+        let path = Path::new(name);
+        let stem = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or(name);
+        let extension = path.extension().and_then(|extension| extension.to_str());
+        (2..)
+            .map(|i| match extension {
+                Some(extension) => format!("{}-{}.{}", stem, i, extension),
+                None => format!("{}-{}", stem, i),
+            })
+            .find(|candidate| self.by_name(candidate).is_none())
+            .unwrap()
+    }
+
+    /// Read a file from disk and import it
+    pub fn import_file(
+        &mut self,
+        images: &mut Assets<Image>,
+        path: &Path,
+    ) -> Result<Handle<Image>, String> {
+        let bytes = std::fs::read(path)
+            .map_err(|err| format!("Couldn't open {}: {}", path.display(), err))?;
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| format!("Bad file name: {}", path.display()))?
+            .to_string();
+        self.import(images, name, bytes)
+    }
+}
+
+#[derive(Resource)]
+struct ImagesUiState {
+    path: String,
+    mode: TextureMode,
+    tile_size: f32,
+    /// last error or success message
+    status: Option<String>,
+}
+
+impl Default for ImagesUiState {
+    fn default() -> Self {
+        Self {
+            path: String::new(),
+            mode: TextureMode::Stretch,
+            tile_size: 10.,
+            status: None,
+        }
+    }
+}
+
+// how wide a body made from an image is, height follows the aspect ratio
+const IMAGE_BODY_WIDTH: f32 = 40.;
+// the alpha outline is traced on a grid this many cells across at most
+const OUTLINE_RESOLUTION: u32 = 48;
+
+pub struct ImagesPlugin;
+
+impl Plugin for ImagesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ImportedImages>()
+            .init_resource::<ImagesUiState>()
+            .add_systems(Update, (import_dropped_files, images_ui));
+    }
+}
+
+// dragging files onto the window imports them
+fn import_dropped_files(
+    mut drop_events: EventReader<FileDragAndDrop>,
+    mut imported: ResMut<ImportedImages>,
+    mut images: ResMut<Assets<Image>>,
+    mut ui_state: ResMut<ImagesUiState>,
+) {
+    for event in drop_events.read() {
+        if let FileDragAndDrop::DroppedFile { path_buf, .. } = event {
+            ui_state.status = Some(match imported.import_file(&mut images, path_buf) {
+                Ok(_) => format!("Imported {}", path_buf.display()),
+                Err(err) => err,
+            });
+        }
+    }
+}
+
+fn images_ui(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    mut imported: ResMut<ImportedImages>,
+    mut images: ResMut<Assets<Image>>,
+    mut ui_state: ResMut<ImagesUiState>,
+    mut selected_query: Query<(Entity, &mut Matter), With<Selected>>,
+    camera_query: Query<&GlobalTransform, With<MainCamera>>,
+) {
+    let mut import_path = None;
+    let mut apply = None;
+    let mut spawn = None;
+    let mut outline = false;
+    let mut clear = false;
+
+    egui::Window::new("Images")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            #[cfg(not(target_arch = "wasm32"))]
+            {
+                ui.label("Drop PNG/JPG files on the window, or type a path:");
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut ui_state.path);
+                    if ui.button("Import").clicked() {
+                        import_path = Some(PathBuf::from(ui_state.path.trim()));
+                    }
+                });
+            }
+            #[cfg(target_arch = "wasm32")]
+            ui.label("Importing images isn't supported in the browser yet.");
+
+            if let Some(status) = &ui_state.status {
+                ui.label(status);
+            }
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                ui.label("Mode");
+                ui.radio_value(&mut ui_state.mode, TextureMode::Stretch, "Stretch");
+                ui.radio_value(&mut ui_state.mode, TextureMode::Fit, "Fit");
+                ui.radio_value(&mut ui_state.mode, TextureMode::Tile, "Tile");
+            });
+            if ui_state.mode == TextureMode::Tile {
+                ui.horizontal(|ui| {
+                    ui.label("Tile size");
+                    ui.add(egui::DragValue::new(&mut ui_state.tile_size).clamp_range(0.5..=1000.0));
+                });
+            }
+
+            for image in imported.images.iter() {
+                ui.horizontal(|ui| {
+                    ui.label(&image.name);
+                    if ui
+                        .add_enabled(!selected_query.is_empty(), egui::Button::new("Apply"))
+                        .on_hover_text("Put this image on the selected body")
+                        .clicked()
+                    {
+                        apply = Some(image.handle.clone());
+                    }
+                    if ui
+                        .button("Spawn")
+                        .on_hover_text("New body shaped like the image")
+                        .clicked()
+                    {
+                        spawn = Some(image.handle.clone());
+                    }
+                });
+            }
+            if imported.images.is_empty() {
+                ui.label("No images yet.");
+            }
+
+            if !selected_query.is_empty() {
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("Remove texture").clicked() {
+                        clear = true;
+                    }
+                    if ui
+                        .button("Collider from alpha")
+                        .on_hover_text("Trace the outline of the selected body's image")
+                        .clicked()
+                    {
+                        outline = true;
+                    }
+                });
+            }
+        });
+
+    if let Some(path) = import_path {
+        ui_state.status = Some(match imported.import_file(&mut images, &path) {
+            Ok(_) => format!("Imported {}", path.display()),
+            Err(err) => err,
+        });
+    }
+
+    let texture_for = |handle: Handle<Image>| MatterTexture {
+        image: handle,
+        mode: ui_state.mode,
+        tile_size: ui_state.tile_size,
+    };
+
+    if let Some(handle) = apply {
+        for (_, mut matter) in selected_query.iter_mut() {
+            matter.texture = Some(texture_for(handle.clone()));
+        }
+    }
+
+    if clear {
+        for (_, mut matter) in selected_query.iter_mut() {
+            matter.texture = None;
+        }
+    }
+
+    if outline {
+        for (entity, mut matter) in selected_query.iter_mut() {
+            let Some(handle) = matter.texture.as_ref().map(|t| t.image.clone()) else {
+                ui_state.status = Some("Selected body doesn't have an image".to_string());
+                continue;
+            };
+            let Some(image) = images.get(&handle) else {
+                continue;
+            };
+            let size = image_body_size(image);
+            match alpha_outline_collider(image, size) {
+                Some(collider) => {
+                    // the image is the shape now
+                    matter.shape = MatterShape::Image(handle);
+                    matter.size = size;
+                    commands.entity(entity).insert(collider);
+                    ui_state.status = Some("Made a collider from the outline".to_string());
+                }
+                None => {
+                    ui_state.status = Some("Couldn't find an outline in that image".to_string());
+                }
+            }
+        }
+    }
+
+    if let Some(handle) = spawn {
+        let Some(image) = images.get(&handle) else {
+            return;
+        };
+        let size = image_body_size(image);
+        let collider = alpha_outline_collider(image, size)
+            .unwrap_or_else(|| Collider::cuboid(size.x / 2., size.y / 2.));
+        let position = camera_query.single().translation().truncate();
+        let mut matter = Matter::textured(size, Color::WHITE, handle.clone());
+        matter.texture = Some(MatterTexture {
+            image: handle,
+            mode: TextureMode::Stretch,
+            tile_size: ui_state.tile_size,
+        });
+        commands.spawn((
+            SpatialBundle::from_transform(Transform::from_translation(position.extend(0.))),
+            matter,
+            collider,
+            RigidBody::Dynamic,
+        ));
+    }
+}
+
+/// World size we give a body made from this image
+pub fn image_body_size(image: &Image) -> Vec2 {
+    let aspect = image.aspect_ratio();
+    Vec2::new(IMAGE_BODY_WIDTH, IMAGE_BODY_WIDTH * aspect)
+}
+
+/// Trace the outline of the opaque part of an image and turn it into a (possibly concave) collider of `size`.
+/// Only the biggest blob is traced.
+pub fn alpha_outline_collider(image: &Image, size: Vec2) -> Option<Collider> {
+    let rgba = image.clone().try_into_dynamic().ok()?.to_rgba8();
+    let (width, height) = (rgba.width(), rgba.height());
+    if width == 0 || height == 0 {
+        return None;
+    }
+
+    // downsample to a grid, a cell is solid if most of it is opaque
+    let step = (width.max(height) as f32 / OUTLINE_RESOLUTION as f32).max(1.);
+    let grid_w = (width as f32 / step).ceil() as i32;
+    let grid_h = (height as f32 / step).ceil() as i32;
+    let solid = |x: i32, y: i32| -> bool {
+        if x < 0 || y < 0 || x >= grid_w || y >= grid_h {
+            return false;
+        }
+        let px = ((x as f32 + 0.5) * step) as u32;
+        let py = ((y as f32 + 0.5) * step) as u32;
+        rgba.get_pixel(px.min(width - 1), py.min(height - 1)).0[3] > 127
+    };
+
+    let contour = trace_contour(grid_w, grid_h, solid)?;
+
+    // grid corners to local body space, image y goes down, ours goes up
+    let cell = Vec2::new(size.x / grid_w as f32, size.y / grid_h as f32);
+    let points: Vec<Vec2> = contour
+        .iter()
+        .map(|&(x, y)| {
+            Vec2::new(
+                x as f32 * cell.x - size.x / 2.,
+                size.y / 2. - y as f32 * cell.y,
+            )
+        })
+        .collect();
+    let points = simplify_closed(&points, cell.x.min(cell.y) * 0.75);
+    if points.len() < 3 {
+        return None;
+    }
+
+    let indices: Vec<[u32; 2]> = (0..points.len() as u32)
+        .map(|i| [i, (i + 1) % points.len() as u32])
+        .collect();
+    Some(Collider::convex_decomposition(&points, &indices))
+}
+
+/// Walks the boundary of the first solid region found (scanning top to bottom) along grid cell edges.
+/// Returns grid corner coordinates in order.
+fn trace_contour(
+    grid_w: i32,
+    grid_h: i32,
+    solid: impl Fn(i32, i32) -> bool,
+) -> Option<Vec<(i32, i32)>> {
+    let start = (0..grid_h)
+        .flat_map(|y| (0..grid_w).map(move |x| (x, y)))
+        .find(|&(x, y)| solid(x, y))?;
+
+    // marching squares on corners. corner (x, y) touches cells (x-1, y-1), (x, y-1), (x-1, y), (x, y)
+    let case = |x: i32, y: i32| -> u8 {
+        (solid(x - 1, y - 1) as u8)
+            | (solid(x, y - 1) as u8) << 1
+            | (solid(x, y) as u8) << 2
+            | (solid(x - 1, y) as u8) << 3
+    };
+
+    let mut contour = vec![];
+    let (mut x, mut y) = start;
+    let (mut dx, mut dy) = (1, 0);
+    let limit = (grid_w + 1) * (grid_h + 1) * 4;
+    for _ in 0..limit {
+        contour.push((x, y));
+        // keep solid on the right hand side as we walk (clockwise on screen)
+        let (ndx, ndy) = match case(x, y) {
+            4 | 12 | 13 => (1, 0),
+            8 | 9 | 11 => (0, 1),
+            1 | 3 | 7 => (-1, 0),
+            2 | 6 | 14 => (0, -1),
+            // saddles, keep going round the same cell so we stay on one blob
+            5 => {
+                if dy == 1 {
+                    (-1, 0)
+                } else {
+                    (1, 0)
+                }
+            }
+            10 => {
+                if dx == 1 {
+                    (0, 1)
+                } else {
+                    (0, -1)
+                }
+            }
+            _ => (dx, dy),
+        };
+        dx = ndx;
+        dy = ndy;
+        x += dx;
+        y += dy;
+        if (x, y) == start {
+            return Some(contour);
+        }
+    }
+    None
+}
+
+/// Ramer-Douglas-Peucker on a closed loop
+fn simplify_closed(points: &[Vec2], tolerance: f32) -> Vec<Vec2> {
+    if points.len() < 4 {
+        return points.to_vec();
+    }
+    // split at the point furthest from the first so both halves are open polylines
+    let first = points[0];
+    let far = (1..points.len())
+        .max_by(|&a, &b| {
+            points[a]
+                .distance_squared(first)
+                .total_cmp(&points[b].distance_squared(first))
+        })
+        .unwrap();
+    let mut a = simplify_open(&points[..=far], tolerance);
+    let mut second_half = points[far..].to_vec();
+    second_half.push(first);
+    let b = simplify_open(&second_half, tolerance);
+    a.pop();
+    a.extend(&b[..b.len() - 1]);
+    a
+}
+
+fn simplify_open(points: &[Vec2], tolerance: f32) -> Vec<Vec2> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+    let (start, end) = (points[0], points[points.len() - 1]);
+    let line = end - start;
+    let length = line.length();
+    let distance = |p: Vec2| {
+        if length < f32::EPSILON {
+            p.distance(start)
+        } else {
+            (line.perp_dot(p - start) / length).abs()
+        }
+    };
+    let (index, max) = points[1..points.len() - 1]
+        .iter()
+        .enumerate()
+        .map(|(i, &p)| (i + 1, distance(p)))
+        .fold(
+            (0, 0.),
+            |best, next| if next.1 > best.1 { next } else { best },
+        );
+    if max > tolerance {
+        let mut left = simplify_open(&points[..=index], tolerance);
+        let right = simplify_open(&points[index..], tolerance);
+        left.pop();
+        left.extend(right);
+        left
+    } else {
+        vec![start, end]
+    }
+}
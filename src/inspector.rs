@@ -0,0 +1,348 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_egui::{egui, EguiContexts};
+use bevy_rapier2d::prelude::*;
+
+use crate::damage::{Breakable, Health};
+use crate::forces::{force_field_ui, thruster_ui, ForceField, Thruster, ThrusterRebinding};
+use crate::joints::{breakable_joint_ui, BreakableJoint};
+use crate::layers::{layer_picker, CollisionLayer, CollisionLayers, LayerFilter};
+use crate::motor::{motor_ui, Motor, MotorRebinding};
+use crate::ragdoll::RagdollPart;
+use crate::{
+    cursor_world_position, EguiUnfocusedSystemSet, MainCamera, MultiBodySpring, Tool, Tools,
+};
+
+/// The body the inspector is showing. Clicking a body with the drag tool selects it.
+#[derive(Component)]
+pub struct Selected;
+
+pub struct InspectorPlugin;
+
+impl Plugin for InspectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, select_body.in_set(EguiUnfocusedSystemSet))
+            .add_systems(Update, (inspector_ui, draw_selection));
+    }
+}
+
+/// Which body a collider belongs to. Ragdoll parts all count as the torso.
+pub fn body_of_collider(
+    rapier_context: &RapierContext,
+    parts: &Query<&RagdollPart>,
+    collider: Entity,
+) -> Entity {
+    let body = rapier_context.collider_parent(collider).unwrap_or(collider);
+    match parts.get(body).or_else(|_| parts.get(collider)) {
+        Ok(part) => part.root,
+        Err(_) => body,
+    }
+}
+
+fn select_body(
+    mut commands: Commands,
+    buttons: Res<Input<MouseButton>>,
+    tool_res: Res<Tools>,
+    rapier_context: Res<RapierContext>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    parts: Query<&RagdollPart>,
+    selected_query: Query<Entity, With<Selected>>,
+    layer_filter: LayerFilter,
+) {
+    if tool_res.current_tool != Tool::Drag || !buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+    let (camera, camera_transform) = camera_query.single();
+    let Some(world_position) = cursor_world_position(q_window.single(), camera, camera_transform)
+    else {
+        return;
+    };
+
+    for entity in selected_query.iter() {
+        commands.entity(entity).remove::<Selected>();
+    }
+    // only what's actually under the cursor, clicking empty space just deselects
+    let grabbable = |collider| layer_filter.drag(collider);
+    let mut found = None;
+    rapier_context.intersections_with_point(
+        world_position,
+        QueryFilter::default().predicate(&grabbable),
+        |collider| {
+            found = Some(collider);
+            false
+        },
+    );
+    if let Some(collider) = found {
+        let body = body_of_collider(&rapier_context, &parts, collider);
+        commands.entity(body).insert(Selected);
+    }
+}
+
+// outline every collider of the selected body
+fn draw_selection(
+    selected_query: Query<(Entity, Option<&Children>), With<Selected>>,
+    colliders: Query<(&Collider, &GlobalTransform)>,
+    mut gizmos: Gizmos,
+) {
+    for (entity, children) in selected_query.iter() {
+        let mut collider_entities = vec![entity];
+        if let Some(children) = children {
+            collider_entities.extend(children.iter().copied());
+        }
+        for collider_entity in collider_entities {
+            let Ok((collider, global_transform)) = colliders.get(collider_entity) else {
+                continue;
+            };
+            let aabb = collider.raw.compute_local_aabb();
+            let center = Vec2::new(
+                (aabb.mins.x + aabb.maxs.x) / 2.,
+                (aabb.mins.y + aabb.maxs.y) / 2.,
+            );
+            let size = Vec2::new(aabb.maxs.x - aabb.mins.x, aabb.maxs.y - aabb.mins.y);
+            let (_, rotation, _) = global_transform.to_scale_rotation_translation();
+            gizmos.rect_2d(
+                global_transform
+                    .transform_point(center.extend(0.))
+                    .truncate(),
+                rotation.to_euler(EulerRot::XYZ).2,
+                size + Vec2::splat(1.),
+                Color::rgb(1., 0.85, 0.3),
+            );
+        }
+    }
+}
+
+fn inspector_ui(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    mut selected_query: Query<
+        (
+            Entity,
+            &Transform,
+            Option<&mut Health>,
+            Option<&mut Breakable>,
+            Option<&mut Motor>,
+            Option<&ImpulseJoint>,
+            Option<&mut BreakableJoint>,
+            Option<&mut ForceField>,
+            Option<&CollisionLayer>,
+            Option<&mut MultiBodySpring>,
+            Option<&Children>,
+        ),
+        With<Selected>,
+    >,
+    mut thrusters: Query<(&mut Thruster, &mut Transform), Without<Selected>>,
+    mut motor_rebinding: ResMut<MotorRebinding>,
+    mut thruster_rebinding: ResMut<ThrusterRebinding>,
+    layers: Res<CollisionLayers>,
+) {
+    let Ok((
+        entity,
+        transform,
+        health,
+        breakable,
+        motor,
+        joint,
+        breakable_joint,
+        force_field,
+        collision_layer,
+        spring,
+        children,
+    )) = selected_query.get_single_mut()
+    else {
+        return;
+    };
+
+    egui::Window::new("Inspector").show(contexts.ctx_mut(), |ui| {
+        ui.label(format!("Entity {:?}", entity));
+        ui.label(format!(
+            "Position: {:.1}, {:.1}",
+            transform.translation.x, transform.translation.y
+        ));
+        ui.horizontal(|ui| {
+            ui.label("Collision layer");
+            let current = collision_layer.copied().unwrap_or_default();
+            let mut layer = current.0;
+            layer_picker(ui, &layers, &mut layer);
+            if layer != current.0 {
+                commands.entity(entity).insert(CollisionLayer(layer));
+            }
+        });
+        ui.separator();
+
+        // health
+        match health {
+            Some(mut health) => {
+                // injuries redraw on any change to health, so leave it alone unless edited
+                let mut edited = health.clone();
+                ui.horizontal(|ui| {
+                    ui.label("Health");
+                    ui.add(egui::ProgressBar::new(edited.fraction()).show_percentage());
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Max");
+                    ui.add(egui::DragValue::new(&mut edited.max).clamp_range(1.0..=10000.0));
+                });
+                if edited.current > edited.max {
+                    edited.current = edited.max;
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Damage threshold");
+                    ui.add(
+                        egui::DragValue::new(&mut edited.damage_threshold)
+                            .speed(100.)
+                            .clamp_range(0.0..=f32::MAX),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Heal").clicked() {
+                        edited.current = edited.max;
+                    }
+                    if ui.button("Remove health").clicked() {
+                        commands.entity(entity).remove::<Health>();
+                    }
+                });
+                health.set_if_neq(edited);
+            }
+            None => {
+                if ui.button("Add health").clicked() {
+                    commands.entity(entity).insert(Health::default());
+                }
+            }
+        }
+        ui.separator();
+
+        // breakable
+        match breakable {
+            Some(mut breakable) => {
+                let mut edited = breakable.clone();
+                ui.horizontal(|ui| {
+                    ui.label("Break threshold");
+                    ui.add(
+                        egui::DragValue::new(&mut edited.threshold)
+                            .speed(100.)
+                            .clamp_range(0.0..=f32::MAX),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Pieces per side");
+                    ui.add(egui::DragValue::new(&mut edited.pieces).clamp_range(2..=8));
+                });
+                if ui.button("Not breakable").clicked() {
+                    commands.entity(entity).remove::<Breakable>();
+                }
+                breakable.set_if_neq(edited);
+            }
+            None => {
+                if ui.button("Make breakable").clicked() {
+                    commands.entity(entity).insert(Breakable::default());
+                }
+            }
+        }
+
+        if let Some(mut force_field) = force_field {
+            ui.separator();
+            ui.label("Force field");
+            force_field_ui(ui, &mut force_field);
+        }
+
+        // thrusters are children of the body
+        for child in children.iter().flat_map(|children| children.iter()) {
+            let Ok((mut thruster, mut thruster_transform)) = thrusters.get_mut(*child) else {
+                continue;
+            };
+            ui.separator();
+            ui.label("Thruster");
+            if thruster_ui(
+                ui,
+                *child,
+                &mut thruster,
+                &mut thruster_transform,
+                &mut thruster_rebinding,
+            ) {
+                commands.entity(*child).despawn_recursive();
+            }
+        }
+
+        if let Some(mut spring) = spring {
+            ui.separator();
+            ui.label("Spring");
+            ui.horizontal(|ui| {
+                ui.label("Stiffness");
+                ui.add(
+                    egui::DragValue::new(&mut spring.stiffness)
+                        .speed(10.)
+                        .clamp_range(0.0..=f32::MAX)
+                        .suffix(" N/m"),
+                );
+            });
+            ui.horizontal(|ui| {
+                ui.label("Damping");
+                ui.add(
+                    egui::DragValue::new(&mut spring.damping)
+                        .clamp_range(0.0..=f32::MAX)
+                        .suffix(" Ns/m"),
+                );
+            });
+            ui.horizontal(|ui| {
+                ui.label("Length");
+                ui.add(egui::DragValue::new(&mut spring.target_len).clamp_range(0.0..=f32::MAX));
+            });
+            ui.horizontal(|ui| {
+                let mut breakable = spring.break_force.is_some();
+                if ui.checkbox(&mut breakable, "Break force").changed() {
+                    spring.break_force = breakable.then_some(200.);
+                }
+                if let Some(break_force) = spring.break_force.as_mut() {
+                    ui.add(
+                        egui::DragValue::new(break_force)
+                            .speed(10.)
+                            .clamp_range(0.0..=f32::MAX)
+                            .suffix(" N"),
+                    );
+                }
+            });
+            if ui.button("Remove spring").clicked() {
+                commands.entity(entity).remove::<MultiBodySpring>();
+            }
+        }
+
+        // motor, for bodies hanging off a joint
+        if joint.is_none() {
+            return;
+        }
+        ui.separator();
+        match motor {
+            Some(mut motor) => {
+                motor_ui(ui, entity, &mut motor, &mut motor_rebinding);
+                if ui.button("Remove motor").clicked() {
+                    commands
+                        .entity(entity)
+                        .remove::<Motor>()
+                        .insert(Sleeping::default());
+                }
+            }
+            None => {
+                if ui.button("Add motor").clicked() {
+                    commands.entity(entity).insert(Motor::default());
+                }
+            }
+        }
+
+        // and how hard the joint can be pulled
+        ui.separator();
+        match breakable_joint {
+            Some(mut breakable_joint) => {
+                breakable_joint_ui(ui, &mut breakable_joint);
+                if ui.button("Unbreakable joint").clicked() {
+                    commands.entity(entity).remove::<BreakableJoint>();
+                }
+            }
+            None => {
+                if ui.button("Make joint breakable").clicked() {
+                    commands.entity(entity).insert(BreakableJoint::default());
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,111 @@
+use bevy::prelude::*;
+use bevy_egui::egui;
+use bevy_rapier2d::prelude::*;
+
+/// Snaps the `ImpulseJoint` on the same entity once it's pulled harder than this.
+/// In rapier's units: newtons and newton meters.
+#[derive(Component, Clone)]
+pub struct BreakableJoint {
+    pub max_force: Option<f32>,
+    pub max_torque: Option<f32>,
+}
+
+impl Default for BreakableJoint {
+    fn default() -> Self {
+        Self {
+            max_force: Some(200.),
+            max_torque: None,
+        }
+    }
+}
+
+/// Sent when a joint or spring comes apart.
+#[derive(Event)]
+pub struct JointBroken {
+    /// the entity that held the joint
+    pub entity: Entity,
+    /// the body it was attached to
+    pub parent: Entity,
+    pub force: f32,
+    pub torque: f32,
+}
+
+pub struct JointsPlugin;
+
+impl Plugin for JointsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<JointBroken>()
+            .add_systems(
+                PostUpdate,
+                break_joints
+                    .after(PhysicsSet::StepSimulation)
+                    .before(PhysicsSet::Writeback),
+            )
+            .add_systems(Last, log_broken_joints);
+    }
+}
+
+// rapier keeps the impulse each joint needed last step, that's what it's holding against
+fn break_joints(
+    mut commands: Commands,
+    rapier_context: Res<RapierContext>,
+    joints: Query<(Entity, &ImpulseJoint, &BreakableJoint)>,
+    mut broken: EventWriter<JointBroken>,
+) {
+    let dt = rapier_context.integration_parameters.dt;
+    for (entity, joint, breakable) in joints.iter() {
+        let Some(raw) = rapier_context
+            .entity2impulse_joint()
+            .get(&entity)
+            .and_then(|handle| rapier_context.impulse_joints.get(*handle))
+        else {
+            continue;
+        };
+        let force = Vec2::new(raw.impulses.x, raw.impulses.y).length() / dt;
+        let torque = raw.impulses.z.abs() / dt;
+        let too_much = |max: Option<f32>, measured: f32| max.is_some_and(|max| measured > max);
+        if too_much(breakable.max_force, force) || too_much(breakable.max_torque, torque) {
+            commands
+                .entity(entity)
+                .remove::<(ImpulseJoint, BreakableJoint)>();
+            broken.send(JointBroken {
+                entity,
+                parent: joint.parent,
+                force,
+                torque,
+            });
+        }
+    }
+}
+
+/// Break limits section of the inspector
+pub fn breakable_joint_ui(ui: &mut egui::Ui, breakable: &mut BreakableJoint) {
+    for (label, limit, suffix) in [
+        ("Break force", &mut breakable.max_force, " N"),
+        ("Break torque", &mut breakable.max_torque, " Nm"),
+    ] {
+        ui.horizontal(|ui| {
+            let mut enabled = limit.is_some();
+            if ui.checkbox(&mut enabled, label).changed() {
+                *limit = enabled.then_some(200.);
+            }
+            if let Some(limit) = limit.as_mut() {
+                ui.add(
+                    egui::DragValue::new(limit)
+                        .speed(10.)
+                        .clamp_range(0.0..=f32::MAX)
+                        .suffix(suffix),
+                );
+            }
+        });
+    }
+}
+
+fn log_broken_joints(mut broken: EventReader<JointBroken>) {
+    for event in broken.read() {
+        info!(
+            "joint between {:?} and {:?} broke at {:.0} N, {:.0} Nm",
+            event.entity, event.parent, event.force, event.torque
+        );
+    }
+}
@@ -0,0 +1,210 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::ragdoll::RagdollPart;
+
+pub const LAYER_COUNT: usize = 8;
+
+const ALL_LAYERS: u32 = (1 << LAYER_COUNT) - 1;
+
+/// Named collision layers and which of them collide. Saved with the settings.
+#[derive(Resource, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CollisionLayers {
+    pub names: [String; LAYER_COUNT],
+    /// bit `j` of `collides[i]` is set when layers `i` and `j` collide, kept symmetric
+    pub collides: [u32; LAYER_COUNT],
+    /// layers laser pointers hit
+    pub laser: u32,
+    /// layers the drag tool can grab and select
+    pub drag: u32,
+}
+
+impl Default for CollisionLayers {
+    fn default() -> Self {
+        let mut names: [String; LAYER_COUNT] = Default::default();
+        for (i, name) in names.iter_mut().enumerate() {
+            *name = format!("Layer {}", i + 1);
+        }
+        names[0] = "Default".to_string();
+        names[1] = "Glass-through".to_string();
+        Self {
+            names,
+            collides: [ALL_LAYERS; LAYER_COUNT],
+            laser: ALL_LAYERS & !(1 << 1),
+            drag: ALL_LAYERS,
+        }
+    }
+}
+
+impl CollisionLayers {
+    /// What rapier needs to know about a collider on `layer`
+    pub fn groups(&self, layer: usize) -> CollisionGroups {
+        CollisionGroups::new(
+            Group::from_bits_truncate(1 << layer),
+            Group::from_bits_truncate(self.collides[layer]),
+        )
+    }
+
+    pub fn collide(&self, a: usize, b: usize) -> bool {
+        self.collides[a] & (1 << b) != 0
+    }
+
+    pub fn set_collide(&mut self, a: usize, b: usize, collide: bool) {
+        for (from, to) in [(a, b), (b, a)] {
+            if collide {
+                self.collides[from] |= 1 << to;
+            } else {
+                self.collides[from] &= !(1 << to);
+            }
+        }
+    }
+}
+
+/// Which layer a body's colliders are on. Bodies without one are on the first layer.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CollisionLayer(pub usize);
+
+/// For scene queries that should only see some layers, pass one of these as the predicate:
+/// `QueryFilter::default().predicate(&|collider| layer_filter.laser(collider))`
+#[derive(SystemParam)]
+pub struct LayerFilter<'w, 's> {
+    layers: Res<'w, CollisionLayers>,
+    groups: Query<'w, 's, &'static CollisionGroups>,
+}
+
+impl LayerFilter<'_, '_> {
+    /// Whether `collider` is on one of the layers in `mask`
+    pub fn allows(&self, collider: Entity, mask: u32) -> bool {
+        !self
+            .groups
+            .get(collider)
+            .is_ok_and(|groups| groups.memberships.bits() & mask == 0)
+    }
+
+    pub fn laser(&self, collider: Entity) -> bool {
+        self.allows(collider, self.layers.laser)
+    }
+
+    pub fn drag(&self, collider: Entity) -> bool {
+        self.allows(collider, self.layers.drag)
+    }
+}
+
+pub struct LayersPlugin;
+
+impl Plugin for LayersPlugin {
+    fn build(&self, app: &mut App) {
+        // the settings plugin has usually put the saved layers in already
+        app.init_resource::<CollisionLayers>()
+            .add_systems(Update, collision_layers_ui)
+            .add_systems(PostUpdate, apply_collision_layers);
+    }
+}
+
+// like materials, ragdolls keep their colliders on child entities so the parent's layer counts,
+// and the head is its own body that only knows its ragdoll through `RagdollPart`
+fn apply_collision_layers(
+    mut commands: Commands,
+    layers: Res<CollisionLayers>,
+    colliders: Query<
+        (
+            Entity,
+            Option<&Parent>,
+            Option<&RagdollPart>,
+            Option<&CollisionGroups>,
+        ),
+        With<Collider>,
+    >,
+    changed: Query<(), Or<(Added<Collider>, Changed<CollisionLayer>)>>,
+    assigned: Query<Ref<CollisionLayer>>,
+) {
+    for (entity, parent, part, current) in colliders.iter() {
+        let owner = parent
+            .map(|parent| parent.get())
+            .or(part.map(|part| part.root));
+        let owner_changed = owner
+            .and_then(|owner| assigned.get(owner).ok())
+            .is_some_and(|layer| layer.is_changed());
+        if !layers.is_changed() && !changed.contains(entity) && !owner_changed {
+            continue;
+        }
+        let layer = assigned
+            .get(entity)
+            .ok()
+            .or_else(|| owner.and_then(|owner| assigned.get(owner).ok()))
+            .map_or(0, |layer| layer.0.min(LAYER_COUNT - 1));
+        let groups = layers.groups(layer);
+        if current != Some(&groups) {
+            commands.entity(entity).insert(groups);
+        }
+    }
+}
+
+/// Dropdown for picking a layer by name
+pub fn layer_picker(ui: &mut egui::Ui, layers: &CollisionLayers, layer: &mut usize) {
+    egui::ComboBox::from_id_source("collision_layer")
+        .selected_text(layers.names.get(*layer).map_or("", String::as_str))
+        .show_ui(ui, |ui| {
+            for (i, name) in layers.names.iter().enumerate() {
+                ui.selectable_value(layer, i, name.as_str());
+            }
+        });
+}
+
+fn collision_layers_ui(mut contexts: EguiContexts, mut layers: ResMut<CollisionLayers>) {
+    let mut edited = layers.clone();
+
+    egui::Window::new("Collision layers")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label("Names");
+            for name in edited.names.iter_mut() {
+                ui.text_edit_singleline(name);
+            }
+            ui.separator();
+
+            // only the lower triangle, the other half is the same
+            ui.label("Which layers collide");
+            egui::Grid::new("collision_matrix").show(ui, |ui| {
+                ui.label("");
+                for i in 0..LAYER_COUNT {
+                    ui.label((i + 1).to_string());
+                }
+                ui.end_row();
+                let names = edited.names.clone();
+                for (a, name) in names.iter().enumerate() {
+                    ui.label(format!("{}. {}", a + 1, name));
+                    for b in 0..=a {
+                        let mut collide = edited.collide(a, b);
+                        if ui.checkbox(&mut collide, "").changed() {
+                            edited.set_collide(a, b, collide);
+                        }
+                    }
+                    ui.end_row();
+                }
+                for (label, mask) in [
+                    ("Lasers hit", &mut edited.laser),
+                    ("Drag grabs", &mut edited.drag),
+                ] {
+                    ui.label(label);
+                    for i in 0..LAYER_COUNT {
+                        let mut hit = *mask & (1 << i) != 0;
+                        if ui.checkbox(&mut hit, "").changed() {
+                            *mask ^= 1 << i;
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+
+            if ui.button("Reset layers").clicked() {
+                edited = CollisionLayers::default();
+            }
+        });
+
+    layers.set_if_neq(edited);
+}
@@ -2,14 +2,9 @@ use std::ops::Sub;
 
 use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
 use bevy::ecs::schedule::ScheduleLabel;
-use bevy::render::primitives::Aabb;
-use bevy::render::render_resource::{AsBindGroup, ShaderRef};
-use bevy::sprite::Material2d;
-use bevy::window::PresentMode;
 use bevy::{
     prelude::*,
     render::render_resource::{Extent3d, TextureFormat},
-    sprite::MaterialMesh2dBundle,
     window::PrimaryWindow,
 };
 use bevy_egui::egui::RichText;
@@ -18,23 +13,96 @@ use bevy_embedded_assets::{EmbeddedAssetPlugin, PluginMode};
 use bevy_pancam::{PanCam, PanCamPlugin};
 use bevy_prototype_lyon::prelude::*;
 use bevy_rapier2d::prelude::*;
-use bevy_rapier2d::rapier::dynamics::{RigidBodyHandle, RigidBodySet};
 use bevy_turborand::prelude::*;
 
-#[derive(Component)]
-struct MainCamera;
+mod benchmark;
+mod body_material;
+mod camera;
+mod context_menu;
+mod damage;
+mod fluid;
+mod forces;
+mod graphics;
+mod images;
+mod inspector;
+mod joints;
+mod layers;
+mod matter;
+mod motor;
+mod performance;
+mod player;
+mod ragdoll;
+mod rope;
+mod ruler;
+mod scene;
+mod settings;
+mod snapping;
+mod soft_body;
+mod sound;
+mod storage;
+mod theme;
+mod transform_tool;
+mod water;
+
+use benchmark::BenchmarkPlugin;
+use body_material::BodyMaterialPlugin;
+use camera::CameraPlugin;
+use context_menu::ContextMenuPlugin;
+use damage::DamagePlugin;
+use fluid::FluidPlugin;
+use forces::ForcesPlugin;
+use graphics::GraphicsPlugin;
+use images::ImagesPlugin;
+use inspector::InspectorPlugin;
+use joints::{JointBroken, JointsPlugin};
+use layers::{LayerFilter, LayersPlugin};
+use matter::{Matter, MatterPlugin, MatterShape};
+use motor::MotorPlugin;
+use performance::PerformancePlugin;
+use player::{Player, PlayerPlugin};
+use ragdoll::{spawn_person, RagdollPlugin, RagdollSettings};
+use rope::RopePlugin;
+use ruler::RulerPlugin;
+use scene::ScenePlugin;
+use settings::{KeyBindings, Settings, SettingsPlugin};
+use snapping::{Snapping, SnappingPlugin};
+use soft_body::SoftBodyPlugin;
+use sound::SoundPlugin;
+use theme::ThemePlugin;
+use transform_tool::TransformToolPlugin;
+use water::WaterPlugin;
 
 #[derive(Component)]
-struct Player;
+struct MainCamera;
 
+/// A spring from this body to `body_b`, made with "Attach spring" in the context menu.
+/// Anchors and length are in pixels, the rest in rapier's units.
 #[derive(Component)]
 struct MultiBodySpring {
-    body_b_rb: RigidBodyHandle,
+    body_b: Entity,
     local_anchor_a: Vec2,
     local_anchor_b: Vec2,
+    /// newtons per meter
     stiffness: f32,
+    /// newton seconds per meter
     damping: f32,
     target_len: f32,
+    /// newtons, comes apart past this, see `JointBroken`
+    break_force: Option<f32>,
+}
+
+impl MultiBodySpring {
+    fn new(body_b: Entity, local_anchor_a: Vec2, local_anchor_b: Vec2, target_len: f32) -> Self {
+        Self {
+            body_b,
+            local_anchor_a,
+            local_anchor_b,
+            stiffness: 500.,
+            damping: 20.,
+            target_len,
+            break_force: None,
+        }
+    }
 }
 
 #[derive(Component)]
@@ -49,27 +117,6 @@ struct WorldSpring {
 #[derive(Component)]
 struct LaserPointer;
 
-#[derive(AsBindGroup, Debug, Clone, Asset, TypePath)]
-pub struct MatterMaterial {
-    #[uniform(0)]
-    color: Color,
-    #[uniform(1)]
-    strokeColor: Color,
-    #[uniform(2)]
-    strokeWidth: f32,
-    #[texture(3)]
-    #[sampler(4)]
-    color_texture: Handle<Image>,
-}
-
-// All functions on `Material2d` have default impls. You only need to implement the
-// functions that are relevant for your material.
-impl Material2d for MatterMaterial {
-    fn fragment_shader() -> ShaderRef {
-        "shaders/matter.wgsl".into()
-    }
-}
-
 #[derive(Debug, Clone, Copy, SystemSet, PartialEq, Eq, Hash)]
 pub struct EguiUnfocusedSystemSet;
 
@@ -80,6 +127,13 @@ enum Tool {
     Rectangle,
     Circle,
     Test,
+    Ruler,
+    Transform,
+    Wheel,
+    Water,
+    Pour,
+    SoftBody,
+    Rope,
 }
 
 #[derive(Resource)]
@@ -120,50 +174,82 @@ fn check_egui_wants_focus(
 }
 
 fn main() {
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(benchmarks) = benchmark::from_args() {
+        match benchmarks {
+            Ok(benchmarks) => benchmark::run_headless(benchmarks),
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     let mut app = App::new();
-    app.insert_resource(Msaa::Sample4)
-        .insert_resource(ClearColor(Color::rgb(
-            0.20392156862745098,
-            0.12941176470588237,
-            0.23921568627450981,
-        )))
-        .insert_resource(UIState {
-            closed_welcome: false,
-        })
-        .add_plugins(
-            (EmbeddedAssetPlugin {
-                mode: PluginMode::ReplaceDefault,
-            }),
-        )
-        .add_plugins(DefaultPlugins.set(WindowPlugin {
-            primary_window: Some(Window {
-                resizable: true,
-                title: "Simulo".to_string(),
-                mode: bevy::window::WindowMode::Windowed,
-                #[cfg(target_arch = "wasm32")]
-                present_mode: PresentMode::default(), // wasm32-unknown-unknown doesn't support PresentMode::Immediate
-                // on everything other than wasm32-unknown-unknown, immediate is used up
-                #[cfg(not(target_arch = "wasm32"))]
-                present_mode: PresentMode::Immediate,
-                fit_canvas_to_parent: true,
-                ..Default::default()
-            }),
+    app.insert_resource(ClearColor(Color::rgb(
+        0.20392156862745098,
+        0.12941176470588237,
+        0.23921568627450981,
+    )))
+    .add_plugins(
+        (EmbeddedAssetPlugin {
+            mode: PluginMode::ReplaceDefault,
+        }),
+    )
+    .add_plugins(DefaultPlugins.set(WindowPlugin {
+        primary_window: Some(Window {
+            resizable: true,
+            title: "Simulo".to_string(),
+            mode: bevy::window::WindowMode::Windowed,
+            // present mode comes from the saved settings, see the Graphics window
+            fit_canvas_to_parent: true,
             ..Default::default()
-        }))
-        .add_plugins(RngPlugin::default())
-        .add_plugins(EguiPlugin)
-        .add_plugins(ShapePlugin)
-        .add_plugins(PanCamPlugin::default())
-        .add_plugins(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(12.0))
-        //.add_plugins(RapierDebugRenderPlugin::default())
-        .add_systems(Update, simulate_springs)
-        //.add_plugins(RapierDebugRenderPlugin::default())
-        .add_plugins(LogDiagnosticsPlugin::default())
-        .add_plugins(FrameTimeDiagnosticsPlugin::default())
-        .add_systems(Startup, setup)
-        .add_systems(Update, ui_system)
-        .add_systems(Update, keyboard_input.in_set(EguiUnfocusedSystemSet))
-        .add_systems(Update, laser_pointer);
+        }),
+        ..Default::default()
+    }))
+    .add_plugins(RngPlugin::default())
+    .add_plugins(EguiPlugin)
+    // before the other plugins so they keep the saved values
+    .add_plugins(SettingsPlugin)
+    .add_plugins(ThemePlugin)
+    .add_plugins(GraphicsPlugin)
+    .add_plugins(ShapePlugin)
+    .add_plugins(MatterPlugin)
+    .add_plugins(PanCamPlugin::default())
+    .add_plugins(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(12.0))
+    .add_plugins(RagdollPlugin)
+    .add_plugins(PlayerPlugin)
+    .add_plugins(CameraPlugin)
+    .add_plugins(DamagePlugin)
+    .add_plugins(InspectorPlugin)
+    .add_plugins(ImagesPlugin)
+    .add_plugins(ScenePlugin)
+    .add_plugins(BodyMaterialPlugin)
+    .add_plugins(LayersPlugin)
+    .add_plugins(ContextMenuPlugin)
+    .add_plugins(SnappingPlugin)
+    .add_plugins(RulerPlugin)
+    .add_plugins(TransformToolPlugin)
+    .add_plugins(MotorPlugin)
+    .add_plugins(JointsPlugin)
+    .add_plugins(ForcesPlugin)
+    .add_plugins(WaterPlugin)
+    .add_plugins(FluidPlugin)
+    .add_plugins(SoftBodyPlugin)
+    .add_plugins(RopePlugin)
+    .add_plugins(SoundPlugin)
+    .add_plugins(PerformancePlugin)
+    .add_plugins(BenchmarkPlugin)
+    //.add_plugins(RapierDebugRenderPlugin::default())
+    .add_systems(Update, simulate_springs)
+    //.add_plugins(RapierDebugRenderPlugin::default())
+    .add_plugins(LogDiagnosticsPlugin::default())
+    .add_plugins(FrameTimeDiagnosticsPlugin::default())
+    .add_systems(Startup, setup)
+    .add_systems(Update, ui_system)
+    .add_systems(Update, keyboard_input.in_set(EguiUnfocusedSystemSet))
+    .add_systems(Update, laser_pointer);
 
     app.init_resource::<EguiWantsFocus>()
         .add_systems(PostUpdate, check_egui_wants_focus)
@@ -172,17 +258,10 @@ fn main() {
             EguiUnfocusedSystemSet.run_if(resource_equals(EguiWantsFocus(false))),
         );
 
-    // matter material
-    app.init_asset::<MatterMaterial>();
-
     app.run();
 }
 
-fn setup(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut materials: ResMut<Assets<MatterMaterial>>,
-) {
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
     commands.insert_resource(Tools {
         current_tool: Tool::Drag,
     });
@@ -195,21 +274,18 @@ fn setup(
             MainCamera,
         ))
         .insert(PanCam {
-            grab_buttons: vec![MouseButton::Right, MouseButton::Middle],
+            grab_buttons: vec![MouseButton::Middle],
             ..Default::default()
         });
 
     /* Create the ground. */
     commands.spawn((
-        SpriteBundle {
-            sprite: Sprite {
-                color: Color::rgb(0.7254901960784313, 0.6313725490196078, 0.7686274509803922),
-                custom_size: Some(Vec2::new(10000.0, 1000.0)),
-                ..default()
-            },
-            transform: Transform::from_translation(Vec3::new(0., -1000., 0.)),
-            ..default()
-        },
+        SpatialBundle::from_transform(Transform::from_translation(Vec3::new(0., -1000., 0.))),
+        Matter::new(Color::rgb(
+            0.7254901960784313,
+            0.6313725490196078,
+            0.7686274509803922,
+        )),
         Collider::cuboid(5000.0, 500.0),
     ));
 
@@ -245,47 +321,12 @@ fn setup(
 
     // LaserPointer with a rigidbody cuboid
     commands.spawn((
-        SpriteBundle {
-            sprite: Sprite {
-                color: Color::rgb(0.25, 0.25, 0.75),
-                custom_size: Some(Vec2::new(16., 4.)),
-                ..default()
-            },
-            transform: Transform::from_translation(Vec3::new(0., 0., 0.00)),
-            ..default()
-        },
+        SpatialBundle::from_transform(Transform::from_translation(Vec3::new(0., 0., 0.00))),
+        Matter::new(Color::rgb(0.25, 0.25, 0.75)),
         Collider::cuboid(8.0, 2.0),
         RigidBody::Dynamic,
         LaserPointer,
     ));
-
-    // 1000 rigidbody boxes stacked on Y axis
-    /*for i in 0..50 {
-        commands.spawn((
-            SpriteBundle {
-                sprite: Sprite {
-                    color: Color::rgb(0.25, 0.25, 0.75),
-                    custom_size: Some(Vec2::new(16., 4.)),
-                    ..default()
-                },
-                transform: Transform::from_translation(Vec3::new(140., 1. + i as f32 * 4.1, 0.00)),
-                ..default()
-            },
-            Collider::cuboid(8.0, 2.0),
-            RigidBody::Dynamic,
-        ));
-    }*/
-    /*
-    commands.spawn(MaterialMesh2dBundle {
-        material: materials.add(MatterMaterial {
-            color: Color::RED,
-            strokeColor: Color::BLACK,
-            strokeWidth: 1.0,
-            color_texture: asset_server.load("icon_square.png"),
-        }),
-        transform: Transform::from_translation(Vec3::new(0., 10., 0.)),
-        ..Default::default()
-    });*/
 }
 
 /*     getLocalPoint(bodyPosition: RAPIER.Vector2, bodyRotation: number, worldPoint: RAPIER.Vector2) {
@@ -298,6 +339,18 @@ fn setup(
     return new RAPIER.Vector2(localX, localY);
 } */
 
+/// Where the cursor is in world space, if it's in the window
+fn cursor_world_position(
+    window: &Window,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+) -> Option<Vec2> {
+    window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor))
+        .map(|ray| ray.origin.truncate())
+}
+
 fn get_local_point(body_position: Vec2, body_rotation: f32, world_point: Vec2) -> Vec2 {
     let cos = body_rotation.cos();
     let sin = body_rotation.sin();
@@ -312,6 +365,7 @@ fn ui_system(
     mut contexts: EguiContexts,
     mut tool_res: ResMut<Tools>,
     mut ui_state: ResMut<UIState>,
+    mut ragdoll_settings: ResMut<RagdollSettings>,
 ) {
     if !ui_state.closed_welcome {
         egui::Window::new("Welcome to the new Simulo!").show(contexts.ctx_mut(), |ui| {
@@ -332,13 +386,13 @@ fn ui_system(
 
         ui.add_space(list_spacing);
 
-        ui.label(" - Vsync is intentionally disabled for now so there's less latency, but you might get screen tearing.");
+        ui.label(" - Vsync is off by default so there's less latency. If you see screen tearing, turn it on in the Graphics window.");
         ui.add_space(list_spacing);
 
-        ui.label(" - The UI will have a theme soon, right now this is just the default egui theme.");
+        ui.label(" - You can switch between light and dark themes and change the UI scale in the Appearance window.");
         ui.add_space(list_spacing);
 
-        ui.label(" - Middle click to pan, scroll to zoom. Currently it lets you right click to pan, but this will be changed when the right click menu is added, so don't get used to it.");
+        ui.label(" - Middle click to pan, scroll to zoom. Right click a body (or empty space) for more options.");
         ui.add_space(list_spacing);
 
         ui.separator();
@@ -368,71 +422,22 @@ fn ui_system(
         ui.radio_value(&mut tool_res.current_tool, Tool::Rectangle, "Rectangle");
         ui.radio_value(&mut tool_res.current_tool, Tool::Circle, "Circle");
         ui.radio_value(&mut tool_res.current_tool, Tool::Test, "Test");
+        ui.radio_value(&mut tool_res.current_tool, Tool::Ruler, "Ruler");
+        ui.radio_value(&mut tool_res.current_tool, Tool::Transform, "Transform");
+        ui.radio_value(&mut tool_res.current_tool, Tool::Wheel, "Wheel");
+        ui.radio_value(&mut tool_res.current_tool, Tool::Water, "Water");
+        ui.radio_value(&mut tool_res.current_tool, Tool::Pour, "Pour fluid");
+        ui.radio_value(&mut tool_res.current_tool, Tool::SoftBody, "Soft body");
+        ui.radio_value(&mut tool_res.current_tool, Tool::Rope, "Rope");
+        ui.separator();
+        ui.checkbox(
+            &mut ragdoll_settings.spawn_alive,
+            "People spawn alive (L to toggle)",
+        );
+        ui.label("F to possess the body under the cursor, A/D to move, W to jump, Esc to let go");
     });
 }
 
-fn spawn_person(
-    commands: &mut Commands,
-    asset_server: &Res<AssetServer>,
-    color: Color,
-    world_position: Vec2,
-) {
-    let body = commands
-        .spawn((
-            RigidBody::Dynamic,
-            SpriteBundle {
-                sprite: Sprite {
-                    custom_size: Some(Vec2::new(6., 2.09941520468 * 6.)),
-                    color,
-                    ..default()
-                },
-                texture: asset_server.load("body.png"),
-                transform: Transform::from_translation(Vec3::new(
-                    world_position.x,
-                    world_position.y,
-                    0.,
-                )),
-                ..default()
-            },
-        ))
-        .with_children(|children| {
-            children.spawn((
-                Collider::round_cuboid(2.4, 2.1, 0.04),
-                Transform::from_translation(Vec3::new(0., -3.7, 0.)),
-            ));
-            children.spawn((
-                Collider::ball(2.9),
-                Transform::from_translation(Vec3::new(0., -1.8, 0.)),
-            ));
-        })
-        .id();
-
-    let joint = RevoluteJointBuilder::new()
-        .local_anchor1(Vec2::new(0.0, 1.8)) // anchor on body
-        .local_anchor2(Vec2::new(0.0, -2.5)); // anchor on head
-
-    // now the head, its circle of same radius 2.9. no head.png, we just circle.png like normal
-    commands.spawn((
-        RigidBody::Dynamic,
-        SpriteBundle {
-            sprite: Sprite {
-                custom_size: Some(Vec2::new(2.9 * 2., 2.9 * 2.)),
-                color,
-                ..default()
-            },
-            texture: asset_server.load("circle.png"),
-            transform: Transform::from_translation(Vec3::new(
-                world_position.x,
-                world_position.y + 2.09941520468 * 6. / 2. + 2.9 / 2.,
-                0.,
-            )),
-            ..default()
-        },
-        Collider::ball(2.9),
-        ImpulseJoint::new(body, joint),
-    ));
-}
-
 fn keyboard_input(
     mut commands: Commands,
     keys: Res<Input<KeyCode>>,
@@ -454,7 +459,7 @@ fn keyboard_input(
     mut tool_res: ResMut<Tools>,
     mut drawing_rectangle_query: Query<(
         &DrawingRectangle,
-        &mut Sprite,
+        &mut Matter,
         Entity,
         &mut Transform,
         Without<WorldSpring>,
@@ -464,7 +469,7 @@ fn keyboard_input(
     )>,
     mut drawing_circle_query: Query<(
         &DrawingCircle,
-        &mut Sprite,
+        &mut Matter,
         Entity,
         &mut Transform,
         Without<WorldSpring>,
@@ -476,6 +481,14 @@ fn keyboard_input(
     mut global_rng: ResMut<GlobalRng>,
     // asset server real
     asset_server: Res<AssetServer>,
+    // grouped so we stay under the system param limit
+    (ragdoll_settings, snapping, bindings, settings, layer_filter): (
+        Res<RagdollSettings>,
+        Res<Snapping>,
+        Res<KeyBindings>,
+        Res<Settings>,
+        LayerFilter,
+    ),
 ) {
     // There is only one primary window, so we can similarly get it from the query:
     let window = q_window.single();
@@ -490,13 +503,13 @@ fn keyboard_input(
         _,
     ) = camera_query.single_mut();
 
-    if keys.just_pressed(KeyCode::Key1) {
+    if keys.just_pressed(bindings.drag_tool) {
         tool_res.current_tool = Tool::Drag;
-    } else if keys.just_pressed(KeyCode::Key2) {
+    } else if keys.just_pressed(bindings.rectangle_tool) {
         tool_res.current_tool = Tool::Rectangle;
     }
 
-    if keys.just_pressed(KeyCode::Space) {
+    if keys.just_pressed(bindings.toggle_physics) {
         rapier_config.physics_pipeline_active = !rapier_config.physics_pipeline_active;
     }
 
@@ -519,87 +532,82 @@ fn keyboard_input(
         for (_, mut spring) in world_spring_query.iter_mut() {
             spring.world_anchor_b = world_position;
         }
+        // where shapes get drawn, stuck to the grid or nearby edges if snapping is on
+        let draw_position = snapping.snap_point(
+            world_position,
+            camera.1.scale,
+            &rapier_context,
+            QueryFilter::default(),
+        );
         // e to spawn a person real
-        if keys.just_pressed(KeyCode::P) {
+        if keys.just_pressed(bindings.spawn_person) {
             spawn_person(
                 &mut commands,
                 &asset_server,
                 Color::rgb(0.6627450980392157, 0.7372549019607844, 0.4),
                 world_position,
+                ragdoll_settings.spawn_alive,
             );
         }
-        if keys.pressed(KeyCode::V) {
+        if keys.pressed(bindings.vertical_box) {
             let mut color = Color::rgb(1., 1., 1.);
             if keys.pressed(KeyCode::ShiftLeft) {
                 color = Color::rgb(0.5, 0.5, 1.);
             }
             gizmos.rect_2d(world_position, 0.0, Vec2::new(8., 16.), color);
         }
-        if keys.pressed(KeyCode::H) {
+        if keys.pressed(bindings.horizontal_box) {
             let mut color = Color::rgb(1., 1., 1.);
             if keys.pressed(KeyCode::ShiftLeft) {
                 color = Color::rgb(0.5, 0.5, 1.);
             }
             gizmos.rect_2d(world_position, 0.0, Vec2::new(16., 8.), color);
         }
-        if keys.just_released(KeyCode::V) {
+        if keys.just_released(bindings.vertical_box) {
             let mut color = Color::rgb(1., 1., 1.);
             if keys.pressed(KeyCode::ShiftLeft) {
                 color = Color::rgb(0.5, 0.5, 1.);
             }
             let mut ent = commands.spawn((
-                SpriteBundle {
-                    sprite: Sprite {
-                        custom_size: Some(Vec2::new(8., 16.)),
-                        color,
-                        ..default()
-                    },
-                    transform: Transform::from_translation(Vec3::new(
-                        world_position.x,
-                        world_position.y,
-                        0.,
-                    )),
-
-                    ..default()
-                },
+                SpatialBundle::from_transform(Transform::from_translation(Vec3::new(
+                    world_position.x,
+                    world_position.y,
+                    0.,
+                ))),
+                Matter::new(color),
                 Collider::cuboid(4., 8.),
+                settings.default_material,
             ));
             if !keys.pressed(KeyCode::ShiftLeft) {
                 ent.insert(RigidBody::Dynamic);
             }
         }
-        if keys.just_released(KeyCode::H) {
+        if keys.just_released(bindings.horizontal_box) {
             let mut color = Color::rgb(1., 1., 1.);
             if keys.pressed(KeyCode::ShiftLeft) {
                 color = Color::rgb(0.5, 0.5, 1.);
             }
             let mut ent = commands.spawn((
-                SpriteBundle {
-                    sprite: Sprite {
-                        custom_size: Some(Vec2::new(16., 8.)),
-                        color,
-                        ..default()
-                    },
-                    transform: Transform::from_translation(Vec3::new(
-                        world_position.x,
-                        world_position.y,
-                        0.,
-                    )),
-
-                    ..default()
-                },
+                SpatialBundle::from_transform(Transform::from_translation(Vec3::new(
+                    world_position.x,
+                    world_position.y,
+                    0.,
+                ))),
+                Matter::new(color),
                 Collider::cuboid(8., 4.),
+                settings.default_material,
             ));
             if !keys.pressed(KeyCode::ShiftLeft) {
                 ent.insert(RigidBody::Dynamic);
             }
         }
-        if keys.just_pressed(KeyCode::M) {
+        if keys.just_pressed(bindings.spawn_red_person) {
             spawn_person(
                 &mut commands,
                 &asset_server,
                 Color::rgb(232. / 255., 80. / 255., 74. / 255.),
                 world_position,
+                ragdoll_settings.spawn_alive,
             );
         }
         if buttons.pressed(MouseButton::Left) {
@@ -631,19 +639,12 @@ fn keyboard_input(
             if current_tool == Tool::Test {
                 for _ in 0..5 {
                     commands.spawn((
-                        SpriteBundle {
-                            sprite: Sprite {
-                                color: Color::rgb(0.75, 0.25, 0.25),
-                                custom_size: Some(Vec2::new(4., 4.)),
-                                ..default()
-                            },
-                            transform: Transform::from_translation(Vec3::new(
-                                world_position.x + global_rng.f32() * 30. - 15.,
-                                world_position.y + global_rng.f32() * 30. - 15.,
-                                0.00,
-                            )),
-                            ..default()
-                        },
+                        SpatialBundle::from_transform(Transform::from_translation(Vec3::new(
+                            world_position.x + global_rng.f32() * 30. - 15.,
+                            world_position.y + global_rng.f32() * 30. - 15.,
+                            0.00,
+                        ))),
+                        Matter::new(Color::rgb(0.75, 0.25, 0.25)),
                         Collider::cuboid(2.0, 2.0),
                         RigidBody::Dynamic,
                     ));
@@ -653,109 +654,98 @@ fn keyboard_input(
         if buttons.just_released(MouseButton::Left) {
             if current_tool == Tool::Rectangle {
                 // query time
-                let (drawing_rectangle, mut sprite, entity, mut transform, _, _, _, _) =
+                let (drawing_rectangle, mut matter, entity, mut transform, _, _, _, _) =
                     drawing_rectangle_query.single_mut();
                 let start = drawing_rectangle.start;
-                let end = world_position;
+                let end = draw_position;
                 let width = (start.x - end.x).abs();
                 let height = (start.y - end.y).abs();
                 let size = Vec2::new(width, height);
                 let center = (start + end) / 2.;
-                sprite.custom_size = Some(size);
+                matter.size = size;
                 // same color but alpha 1
-                sprite.color =
-                    Color::rgba(sprite.color.r(), sprite.color.g(), sprite.color.b(), 1.);
+                let fill = matter.fill.with_a(1.);
+                matter.set_fill(fill);
+                // its got a collider now, draw that instead
+                matter.shape = MatterShape::Collider;
                 let mut ent = commands.get_entity(entity).unwrap();
                 ent.remove::<DrawingRectangle>();
                 ent.insert((
                     Collider::cuboid(width / 2., height / 2.),
                     RigidBody::Dynamic,
+                    settings.default_material,
                 ));
                 // transform it up
                 transform.translation = Vec3::new(center.x, center.y, 0.);
-                ent.remove::<Aabb>(); // force recalculation
             }
             // the the the
             if current_tool == Tool::Circle {
-                let (drawing_circle, mut sprite, entity, mut transform, _, _, _, _, _) =
+                let (drawing_circle, mut matter, entity, mut transform, _, _, _, _, _) =
                     drawing_circle_query.single_mut();
 
                 let start = drawing_circle.start;
-                let end = world_position;
+                let end = draw_position;
                 let width = (start.x - end.x);
                 let height = (start.y - end.y);
                 let size = width.abs().max(height);
                 let mut center = (start + Vec2::new(size, size));
 
-                sprite.custom_size = Some(Vec2::new(size / 2., size / 2.));
+                matter.size = Vec2::new(size / 2., size / 2.);
                 // same color but alpha 1
-                sprite.color =
-                    Color::rgba(sprite.color.r(), sprite.color.g(), sprite.color.b(), 1.);
+                let fill = matter.fill.with_a(1.);
+                matter.set_fill(fill);
+                // its got a collider now, draw that instead
+                matter.shape = MatterShape::Collider;
                 let mut ent = commands.get_entity(entity).unwrap();
                 ent.remove::<DrawingRectangle>();
-                ent.insert((Collider::ball(size / 2.), RigidBody::Dynamic));
+                ent.insert((
+                    Collider::ball(size / 2.),
+                    RigidBody::Dynamic,
+                    settings.default_material,
+                ));
                 // transform it up
                 transform.translation = Vec3::new(center.x, center.y, 0.);
-                ent.remove::<Aabb>(); // force recalculation
             }
         }
         if buttons.just_pressed(MouseButton::Left) {
             if (current_tool == Tool::Rectangle) {
                 // spawn just a display of a transparent rectangle with 0 size, no collider or rb or anything, when mouse moves, update the size, when mouse is released, spawn the actual thing
                 commands.spawn((
-                    SpriteBundle {
-                        sprite: Sprite {
-                            color: Color::rgba(
-                                global_rng.f32(),
-                                global_rng.f32(),
-                                global_rng.f32(),
-                                0.5,
-                            ),
-                            custom_size: Some(Vec2::new(0., 0.)),
-                            ..default()
-                        },
-                        transform: Transform::from_translation(Vec3::new(
-                            world_position.x,
-                            world_position.y,
-                            0.00,
-                        )),
-                        ..default()
-                    },
+                    SpatialBundle::from_transform(Transform::from_translation(Vec3::new(
+                        draw_position.x,
+                        draw_position.y,
+                        0.00,
+                    ))),
+                    Matter::rect(
+                        Vec2::new(0., 0.),
+                        Color::rgba(global_rng.f32(), global_rng.f32(), global_rng.f32(), 0.5),
+                    ),
                     DrawingRectangle {
-                        start: world_position,
+                        start: draw_position,
                     },
                 ));
             }
             if (current_tool == Tool::Circle) {
                 // spawn just a display of a transparent circle with 0 size, no collider or rb or anything, when mouse moves, update the size, when mouse is released, spawn the actual thing
                 commands.spawn((
-                    SpriteBundle {
-                        sprite: Sprite {
-                            color: Color::rgba(
-                                global_rng.f32(),
-                                global_rng.f32(),
-                                global_rng.f32(),
-                                0.5,
-                            ),
-                            custom_size: Some(Vec2::new(0., 0.)),
-                            ..default()
-                        },
-                        texture: asset_server.load("circle.png"),
-                        transform: Transform::from_translation(Vec3::new(
-                            world_position.x,
-                            world_position.y,
-                            0.00,
-                        )),
-                        ..default()
-                    },
+                    SpatialBundle::from_transform(Transform::from_translation(Vec3::new(
+                        draw_position.x,
+                        draw_position.y,
+                        0.00,
+                    ))),
+                    Matter::ellipse(
+                        Vec2::new(0., 0.),
+                        Color::rgba(global_rng.f32(), global_rng.f32(), global_rng.f32(), 0.5),
+                    ),
                     DrawingCircle {
-                        start: world_position,
+                        start: draw_position,
                     },
                 ));
             }
             if current_tool == Tool::Drag {
                 let solid = true;
-                let filter = QueryFilter::default();
+                let grabbable = |collider| layer_filter.drag(collider);
+                let filter = QueryFilter::default().predicate(&grabbable);
                 if let Some((entity, projection)) =
                     rapier_context.project_point(world_position, solid, filter)
                 {
@@ -811,40 +801,32 @@ fn keyboard_input(
         sprite.custom_size = Some(size);
         // transform it up
         transform.translation = Vec3::new(center.x, center.y, 0.);*/
-        for (drawing_rectangle, mut sprite, entity, mut transform, _, _, _, _) in
+        for (drawing_rectangle, mut matter, _, mut transform, _, _, _, _) in
             drawing_rectangle_query.iter_mut()
         {
             let start = drawing_rectangle.start;
-            let end = world_position;
+            let end = draw_position;
             let width = (start.x - end.x).abs();
             let height = (start.y - end.y).abs();
             let size = Vec2::new(width, height);
             let center = (start + end) / 2.;
-            sprite.custom_size = Some(size);
+            matter.size = size;
             // transform it up
             transform.translation = Vec3::new(center.x, center.y, 0.);
-
-            let mut ent = commands.get_entity(entity).unwrap();
-
-            ent.remove::<Aabb>(); // force recalculation so it doesnt cull incorrectly (size starts at 0, if we dont do this it will be culled when center is outside of the screen)
         }
 
-        for (drawing_circle, mut sprite, entity, mut transform, _, _, _, _, _) in
+        for (drawing_circle, mut matter, _, mut transform, _, _, _, _, _) in
             drawing_circle_query.iter_mut()
         {
             let start = drawing_circle.start;
-            let end = world_position;
+            let end = draw_position;
             let width = (start.x - end.x).abs();
             let height = (start.y - end.y).abs();
             let size = width.max(height);
             let center = (start + end) / 2.;
-            sprite.custom_size = Some(Vec2::new(size, size));
+            matter.size = Vec2::new(size, size);
             // transform it up
             transform.translation = Vec3::new(center.x, center.y, 0.);
-
-            let mut ent = commands.get_entity(entity).unwrap();
-
-            ent.remove::<Aabb>(); // force recalculation so it doesnt cull incorrectly (size starts at 0, if we dont do this it will be culled when center is outside of the screen)
         }
     }
 }
@@ -854,42 +836,106 @@ fn gcross(vec_a: Vec2, vec_b: Vec2) -> f32 {
 }
 
 fn simulate_springs(
-    mut multibody_spring_query: Query<(
-        &mut MultiBodySpring,
-        &ExternalImpulse,
-        Without<WorldSpring>,
-    )>,
+    mut commands: Commands,
+    multibody_spring_query: Query<(Entity, &MultiBodySpring)>,
     mut world_spring_query: Query<(
         &WorldSpring,
         &Velocity,
         &GlobalTransform,
         &mut ExternalImpulse,
-        Without<MultiBodySpring>,
         &ReadMassProperties,
     )>,
-    mut other_impulse_query: Query<(
-        &mut ExternalImpulse,
-        Without<MultiBodySpring>,
-        Without<WorldSpring>,
+    mut other_impulse_query: Query<&mut ExternalImpulse, Without<WorldSpring>>,
+    bodies: Query<(
+        &GlobalTransform,
+        Option<&Velocity>,
+        Option<&ReadMassProperties>,
     )>,
     rapier_context: Res<RapierContext>,
+    mut broken: EventWriter<JointBroken>,
     mut gizmos: Gizmos,
 ) {
+    let scale = rapier_context.physics_scale();
+    let dt = rapier_context.integration_parameters.dt;
+    // worked out first and applied after the world springs, which overwrite the impulse
+    let mut spring_impulses: Vec<(Entity, Vec2, f32)> = Vec::new();
+
     // iterate over all springs
-    for (mut spring, rigidbody_a_impulse, _) in multibody_spring_query.iter_mut() {
-        // get the other impulser of the spring
-        let entity = rapier_context.rigid_body_entity(spring.body_b_rb).unwrap();
-        let mut rigidbody_b_stuff = other_impulse_query.get_mut(entity).unwrap();
-        let rigidbody_b_impulse = rigidbody_b_stuff.0.as_mut();
-        {
-            // Apply an impulse of (10, 10) to the rigidbody
-            rigidbody_b_impulse.impulse.x = 10.;
-            rigidbody_b_impulse.impulse.y = 10.;
+    for (entity, spring) in multibody_spring_query.iter() {
+        let entity_b = spring.body_b;
+        let (Ok((transform_a, velocity_a, mass_a)), Ok((transform_b, velocity_b, mass_b))) =
+            (bodies.get(entity), bodies.get(entity_b))
+        else {
+            // the other end was deleted
+            if bodies.contains(entity) {
+                commands.entity(entity).remove::<MultiBodySpring>();
+            }
+            continue;
+        };
+
+        let point_a_world = transform_a
+            .transform_point(spring.local_anchor_a.extend(0.))
+            .truncate();
+        let point_b_world = transform_b
+            .transform_point(spring.local_anchor_b.extend(0.))
+            .truncate();
+
+        gizmos.line_2d(point_a_world, point_b_world, Color::WHITE);
+
+        let spring_vector = point_b_world - point_a_world;
+        let distance = spring_vector.length();
+        if distance < 0.001 {
+            continue;
         }
+        let direction = spring_vector / distance;
+
+        let com_a = transform_a
+            .transform_point(mass_a.map_or(Vec3::ZERO, |m| m.local_center_of_mass.extend(0.)))
+            .truncate();
+        let com_b = transform_b
+            .transform_point(mass_b.map_or(Vec3::ZERO, |m| m.local_center_of_mass.extend(0.)))
+            .truncate();
+
+        // velocity of each anchor point, including what the spin adds
+        let anchor_velocity = |velocity: Option<&Velocity>, point: Vec2, com: Vec2| {
+            velocity.map_or(Vec2::ZERO, |v| v.linvel + (point - com).perp() * v.angvel)
+        };
+        let u = anchor_velocity(velocity_b, point_b_world, com_b)
+            - anchor_velocity(velocity_a, point_a_world, com_a);
+        // in meters, so the force comes out in newtons like joint forces do
+        let f = direction
+            * ((-spring.stiffness * (distance - spring.target_len) / scale)
+                - (spring.damping * u.dot(direction) / scale));
+
+        if spring.break_force.is_some_and(|max| f.length() > max) {
+            commands.entity(entity).remove::<MultiBodySpring>();
+            broken.send(JointBroken {
+                entity,
+                parent: entity_b,
+                force: f.length(),
+                torque: 0.,
+            });
+            continue;
+        }
+
+        let force_a = f * -1.;
+        let force_b = f;
+
+        // `ExternalImpulse` is in pixels, rapier divides by the scale (and its square for torque)
+        spring_impulses.push((
+            entity,
+            force_a * dt * scale,
+            gcross(point_a_world - com_a, force_a) * dt * scale,
+        ));
+        spring_impulses.push((
+            entity_b,
+            force_b * dt * scale,
+            gcross(point_b_world - com_b, force_b) * dt * scale,
+        ));
     }
 
     // world ones
-    for (spring, velocity, global_transform, mut rigidbody_impulse, _, mass_props) in
+    for (spring, velocity, global_transform, mut rigidbody_impulse, mass_props) in
         world_spring_query.iter_mut()
     {
         let point_a_world = global_transform
@@ -971,6 +1017,21 @@ fn simulate_springs(
             force_a,
         ) / 250.;
     }
+
+    for (entity, impulse, torque_impulse) in spring_impulses {
+        if let Ok(mut external) = other_impulse_query.get_mut(entity) {
+            external.impulse += impulse;
+            external.torque_impulse += torque_impulse;
+        } else if let Ok((_, _, _, mut external, _)) = world_spring_query.get_mut(entity) {
+            external.impulse += impulse;
+            external.torque_impulse += torque_impulse;
+        } else {
+            commands.entity(entity).insert(ExternalImpulse {
+                impulse,
+                torque_impulse,
+            });
+        }
+    }
 }
 
 // laser pointer system, we raycast and reflect only once
@@ -979,14 +1040,18 @@ fn laser_pointer(
     mut laser_pointer_query: Query<(
         &mut GlobalTransform,
         &mut RigidBody,
-        &mut Sprite,
+        &mut Matter,
         &mut ExternalImpulse,
         &mut LaserPointer,
     )>,
     rapier_context: Res<RapierContext>,
+    layer_filter: LayerFilter,
     mut gizmos: Gizmos,
 ) {
-    for (mut transform, mut rigidbody, mut sprite, mut impulse, _) in laser_pointer_query.iter_mut()
+    // lasers go straight through layers they don't hit
+    let hits = |collider| layer_filter.laser(collider);
+    let filter = QueryFilter::default().predicate(&hits);
+    for (mut transform, mut rigidbody, mut matter, mut impulse, _) in laser_pointer_query.iter_mut()
     {
         // get the position of the laser pointer
         let global_position = transform.translation().truncate();
@@ -994,7 +1059,7 @@ fn laser_pointer(
         let up = transform.right().truncate();
         let origin = global_position + up * 16.;
         if let Some((entity, intersection)) =
-            rapier_context.cast_ray_and_get_normal(origin, up, 1000., true, QueryFilter::default())
+            rapier_context.cast_ray_and_get_normal(origin, up, 1000., true, filter)
         {
             let hit_point = intersection.point;
             let hit_normal = intersection.normal;
@@ -1016,13 +1081,9 @@ fn laser_pointer(
 
             gizmos.line_2d(origin, hit_point, Color::RED); // everyone knows lasers are red
                                                            // now one more
-            if let Some((entity, intersection)) = rapier_context.cast_ray_and_get_normal(
-                hit_point,
-                hit_normal,
-                1000.,
-                true,
-                QueryFilter::default(),
-            ) {
+            if let Some((entity, intersection)) =
+                rapier_context.cast_ray_and_get_normal(hit_point, hit_normal, 1000., true, filter)
+            {
                 let hit_point = intersection.point;
                 let hit_normal = intersection.normal;
                 gizmos.line_2d(hit_point, hit_point + hit_normal * 1000., Color::GREEN);
@@ -0,0 +1,738 @@
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::render::primitives::Aabb;
+use bevy::render::render_resource::{AsBindGroup, ShaderRef};
+use bevy::render::view::VisibilitySystems;
+use bevy::sprite::{Material2d, Material2dPlugin, Mesh2dHandle};
+use bevy::utils::HashMap;
+use bevy_prototype_lyon::plugin::BuildShapes;
+use bevy_prototype_lyon::prelude::*;
+use bevy_rapier2d::parry::shape::TypedShape;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// `MatterMaterial::mask`: the shape is the alpha of `color_texture`
+pub const MASK_TEXTURE: u32 = 0;
+/// `MatterMaterial::mask`: the shape is an ellipse filling the quad, worked out in the shader so
+/// its edge stays smooth at any zoom
+pub const MASK_ELLIPSE: u32 = 1;
+
+/// Outlined fill. The shape comes from the texture's alpha, so a plain white texture gives a rectangle, or
+/// it's an ellipse drawn without a texture.
+#[derive(AsBindGroup, Debug, Clone, Asset, TypePath)]
+pub struct MatterMaterial {
+    #[uniform(0)]
+    pub color: Color,
+    #[uniform(0)]
+    pub stroke_color: Color,
+    /// in world units
+    #[uniform(0)]
+    pub stroke_width: f32,
+    /// world size of the quad, the shader needs it to keep the stroke the same width on stretched shapes
+    #[uniform(0)]
+    pub size: Vec2,
+    /// a `TextureMode`
+    #[uniform(0)]
+    pub fill_mode: u32,
+    /// world width of one tile when tiling
+    #[uniform(0)]
+    pub tile_size: f32,
+    /// `MASK_TEXTURE` or `MASK_ELLIPSE`
+    #[uniform(0)]
+    pub mask: u32,
+    /// only the alpha of this is used, it's the shape
+    #[texture(1)]
+    #[sampler(2)]
+    pub color_texture: Handle<Image>,
+    /// drawn inside the shape, tinted by `color`
+    #[texture(3)]
+    #[sampler(4)]
+    pub fill_texture: Handle<Image>,
+}
+
+// All functions on `Material2d` have default impls. You only need to implement the
+// functions that are relevant for your material.
+impl Material2d for MatterMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/matter.wgsl".into()
+    }
+}
+
+/// What the outline of a `Matter` comes from.
+#[derive(Clone, PartialEq)]
+pub enum MatterShape {
+    /// whatever `Collider` the entity has, drawn as vector shapes
+    Collider,
+    /// `size` rectangle, for previews that dont have a collider yet
+    Rect,
+    /// `size` ellipse, same deal
+    Ellipse,
+    /// alpha of an image stretched over `size`, drawn with `MatterMaterial`
+    Image(Handle<Image>),
+}
+
+/// How an image fills the shape it's put on. Values match `matter.wgsl`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TextureMode {
+    Stretch = 0,
+    /// whole image visible, keeps its aspect ratio
+    Fit = 1,
+    Tile = 2,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct MatterTexture {
+    pub image: Handle<Image>,
+    pub mode: TextureMode,
+    /// world width of one tile for `TextureMode::Tile`
+    pub tile_size: f32,
+}
+
+/// How a body is drawn. Change this (or the collider) and the mesh and material follow.
+#[derive(Component, Clone)]
+pub struct Matter {
+    pub shape: MatterShape,
+    /// only used by `Rect`, `Ellipse` and `Image`
+    pub size: Vec2,
+    pub fill: Color,
+    pub stroke: Color,
+    pub stroke_width: f32,
+    /// image drawn inside the shape. anything with one of these goes through `MatterMaterial` instead of lyon
+    pub texture: Option<MatterTexture>,
+}
+
+impl Matter {
+    /// Drawn from the collider
+    pub fn new(fill: Color) -> Self {
+        Self {
+            shape: MatterShape::Collider,
+            size: Vec2::ZERO,
+            fill,
+            stroke: stroke_for(fill),
+            stroke_width: 0.5,
+            texture: None,
+        }
+    }
+
+    pub fn rect(size: Vec2, fill: Color) -> Self {
+        Self {
+            shape: MatterShape::Rect,
+            size,
+            ..Self::new(fill)
+        }
+    }
+
+    pub fn ellipse(size: Vec2, fill: Color) -> Self {
+        Self {
+            shape: MatterShape::Ellipse,
+            size,
+            ..Self::new(fill)
+        }
+    }
+
+    pub fn textured(size: Vec2, fill: Color, texture: Handle<Image>) -> Self {
+        Self {
+            shape: MatterShape::Image(texture),
+            size,
+            ..Self::new(fill)
+        }
+    }
+
+    /// Textured things are drawn as a quad with `MatterMaterial`, the rest with lyon
+    pub fn uses_material(&self) -> bool {
+        matches!(self.shape, MatterShape::Image(_)) || self.texture.is_some()
+    }
+
+    pub fn set_fill(&mut self, fill: Color) {
+        self.fill = fill;
+        self.stroke = stroke_for(fill);
+    }
+
+    /// Plain untextured boxes and balls, with their size
+    fn primitive(&self, collider: Option<&Collider>) -> Option<(Primitive, Vec2)> {
+        if self.shape != MatterShape::Collider || self.texture.is_some() {
+            return None;
+        }
+        let collider = collider?;
+        if let Some(ball) = collider.as_ball() {
+            return Some((Primitive::Ball, Vec2::splat(ball.radius() * 2.)));
+        }
+        collider
+            .as_cuboid()
+            .map(|cuboid| (Primitive::Box, cuboid.half_extents() * 2.))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Primitive {
+    Box,
+    Ball,
+}
+
+/// Everything that makes two primitives look the same. Floats go in as bits so it can be hashed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct BatchKey {
+    primitive: Primitive,
+    size: [u32; 2],
+    fill: [u32; 4],
+    stroke: [u32; 4],
+    stroke_width: u32,
+}
+
+impl BatchKey {
+    fn new(primitive: Primitive, size: Vec2, matter: &Matter) -> Self {
+        Self {
+            primitive,
+            size: size.to_array().map(f32::to_bits),
+            fill: matter.fill.as_rgba_f32().map(f32::to_bits),
+            stroke: matter.stroke.as_rgba_f32().map(f32::to_bits),
+            stroke_width: matter.stroke_width.to_bits(),
+        }
+    }
+}
+
+/// Mesh and material shared by every primitive with the same `BatchKey`, so bevy draws them all
+/// in one instanced batch. Only the ids are kept, the assets go away with the last body using them.
+#[derive(Resource, Default)]
+struct MatterBatches(HashMap<BatchKey, (AssetId<Mesh>, AssetId<MatterMaterial>)>);
+
+/// On entities drawn with a shared batch mesh and material, which must never be edited in place
+#[derive(Component)]
+struct Batched;
+
+/// Darker version of the fill, like old Simulo
+pub fn stroke_for(fill: Color) -> Color {
+    Color::rgba(fill.r() * 0.6, fill.g() * 0.6, fill.b() * 0.6, fill.a())
+}
+
+pub struct MatterPlugin;
+
+impl Plugin for MatterPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(Material2dPlugin::<MatterMaterial>::default())
+            .init_resource::<MatterBatches>()
+            .add_systems(
+                PostUpdate,
+                (sync_matter_batches, sync_matter_images, sync_matter_shapes)
+                    .before(BuildShapes)
+                    .before(VisibilitySystems::CalculateBounds),
+            );
+    }
+}
+
+fn local_bounds(min: Vec2, max: Vec2, stroke_width: f32) -> Aabb {
+    let pad = Vec2::splat(stroke_width / 2.);
+    Aabb::from_min_max((min - pad).extend(0.), (max + pad).extend(0.))
+}
+
+// a quad like `shape::Quad` but not necessarily centered on the origin
+fn quad_mesh(center: Vec2, size: Vec2) -> Mesh {
+    let min = center - size / 2.;
+    let max = center + size / 2.;
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        vec![
+            [min.x, min.y, 0.],
+            [min.x, max.y, 0.],
+            [max.x, max.y, 0.],
+            [max.x, min.y, 0.],
+        ],
+    );
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0., 0., 1.]; 4]);
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_UV_0,
+        vec![[0., 1.], [0., 0.], [1., 0.], [1., 1.]],
+    );
+    mesh.set_indices(Some(Indices::U32(vec![0, 2, 1, 0, 3, 2])));
+    mesh
+}
+
+// boxes and balls share a quad and `MatterMaterial` with everything that looks the same
+fn sync_matter_batches(
+    mut commands: Commands,
+    mut batches: ResMut<MatterBatches>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<MatterMaterial>>,
+    matter_query: Query<
+        (Entity, &Matter, Option<&Collider>),
+        Or<(Changed<Matter>, Changed<Collider>)>,
+    >,
+) {
+    for (entity, matter, collider) in matter_query.iter() {
+        let Some((primitive, size)) = matter.primitive(collider) else {
+            continue;
+        };
+        let key = BatchKey::new(primitive, size, matter);
+        let shared = batches.0.get(&key).and_then(|(mesh, material)| {
+            Some((
+                meshes.get_strong_handle(*mesh)?,
+                materials.get_strong_handle(*material)?,
+            ))
+        });
+        let (mesh, material) = match shared {
+            Some(handles) => handles,
+            None => {
+                let mesh = meshes.add(quad_mesh(Vec2::ZERO, size));
+                let material = materials.add(MatterMaterial {
+                    color: matter.fill,
+                    stroke_color: matter.stroke,
+                    stroke_width: matter.stroke_width,
+                    size,
+                    fill_mode: TextureMode::Stretch as u32,
+                    tile_size: 1.,
+                    mask: match primitive {
+                        Primitive::Box => MASK_TEXTURE,
+                        Primitive::Ball => MASK_ELLIPSE,
+                    },
+                    color_texture: Handle::default(),
+                    fill_texture: Handle::default(),
+                });
+                // forget batches nobody is using anymore while we're at it
+                batches.0.retain(|_, (mesh_id, material_id)| {
+                    meshes.contains(*mesh_id) && materials.contains(*material_id)
+                });
+                batches.0.insert(key, (mesh.id(), material.id()));
+                (mesh, material)
+            }
+        };
+        // the mesh is shared so bevy can't work out bounds per entity, they have to come from us
+        commands
+            .entity(entity)
+            .remove::<(Path, Fill, Stroke, Handle<ColorMaterial>)>()
+            .insert((
+                Mesh2dHandle(mesh),
+                material,
+                Batched,
+                local_bounds(-size / 2., size / 2., 0.),
+            ));
+    }
+}
+
+// textured ones get a quad with `MatterMaterial`
+fn sync_matter_images(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<MatterMaterial>>,
+    mut matter_query: Query<
+        (
+            Entity,
+            &Matter,
+            Option<&Collider>,
+            Option<&Handle<MatterMaterial>>,
+            Option<&mut Mesh2dHandle>,
+            Option<&mut Aabb>,
+            Option<&Batched>,
+        ),
+        Or<(Changed<Matter>, Changed<Collider>)>,
+    >,
+) {
+    for (entity, matter, collider, material_handle, mesh_handle, aabb, batched) in
+        matter_query.iter_mut()
+    {
+        if !matter.uses_material() || matter.primitive(collider).is_some() {
+            continue;
+        }
+        // the quad covers the shape, and the mask cuts the shape out of it
+        let (center, size, mask, mask_texture) = match (&matter.shape, collider) {
+            (MatterShape::Image(image), _) => {
+                (Vec2::ZERO, matter.size, MASK_TEXTURE, image.clone())
+            }
+            (MatterShape::Collider, Some(collider)) => {
+                let aabb = collider.raw.compute_local_aabb();
+                let min = Vec2::new(aabb.mins.x, aabb.mins.y);
+                let max = Vec2::new(aabb.maxs.x, aabb.maxs.y);
+                let mask = if collider.as_ball().is_some() {
+                    MASK_ELLIPSE
+                } else {
+                    MASK_TEXTURE
+                };
+                ((min + max) / 2., max - min, mask, Handle::default())
+            }
+            (MatterShape::Ellipse, _) => (Vec2::ZERO, matter.size, MASK_ELLIPSE, Handle::default()),
+            _ => (Vec2::ZERO, matter.size, MASK_TEXTURE, Handle::default()),
+        };
+        let bounds = local_bounds(center - size / 2., center + size / 2., 0.);
+        let (fill_texture, fill_mode, tile_size) = match &matter.texture {
+            Some(texture) => (
+                texture.image.clone(),
+                texture.mode as u32,
+                texture.tile_size,
+            ),
+            None => (Handle::default(), TextureMode::Stretch as u32, 1.),
+        };
+        let new_material = MatterMaterial {
+            color: matter.fill,
+            stroke_color: matter.stroke,
+            stroke_width: matter.stroke_width,
+            size,
+            fill_mode,
+            tile_size,
+            mask,
+            color_texture: mask_texture,
+            fill_texture,
+        };
+        let mesh = meshes.add(quad_mesh(center, size));
+
+        let (Some(material_handle), Some(mut mesh_handle), Some(mut aabb), None) =
+            (material_handle, mesh_handle, aabb, batched)
+        else {
+            // first time (or it used to be a vector shape or batched), make everything
+            commands
+                .entity(entity)
+                .remove::<(Path, Fill, Stroke, Handle<ColorMaterial>, Batched)>()
+                .insert((Mesh2dHandle(mesh), materials.add(new_material), bounds));
+            continue;
+        };
+
+        let Some(material) = materials.get_mut(material_handle) else {
+            continue;
+        };
+        *material = new_material;
+        mesh_handle.0 = mesh;
+        // the old bounds are wrong now, fix them here so it doesnt get culled (previews start at 0 size)
+        *aabb = bounds;
+    }
+}
+
+// everything else is tessellated by lyon so it stays sharp at any zoom
+fn sync_matter_shapes(
+    mut commands: Commands,
+    mut matter_query: Query<
+        (
+            Entity,
+            &Matter,
+            Option<&Collider>,
+            Option<&mut Path>,
+            Option<&mut Fill>,
+            Option<&mut Stroke>,
+            Option<&mut Aabb>,
+        ),
+        Or<(Changed<Matter>, Changed<Collider>)>,
+    >,
+) {
+    for (entity, matter, collider, path, fill, stroke, aabb) in matter_query.iter_mut() {
+        let (new_path, bounds) = match (&matter.shape, collider) {
+            _ if matter.uses_material() || matter.primitive(collider).is_some() => continue,
+            (MatterShape::Collider, Some(collider)) => {
+                let aabb = collider.raw.compute_local_aabb();
+                (
+                    collider_path(collider),
+                    local_bounds(
+                        Vec2::new(aabb.mins.x, aabb.mins.y),
+                        Vec2::new(aabb.maxs.x, aabb.maxs.y),
+                        matter.stroke_width,
+                    ),
+                )
+            }
+            (MatterShape::Ellipse, _) => (
+                GeometryBuilder::build_as(&shapes::Ellipse {
+                    radii: matter.size / 2.,
+                    center: Vec2::ZERO,
+                }),
+                local_bounds(-matter.size / 2., matter.size / 2., matter.stroke_width),
+            ),
+            // rect, or no collider to go off
+            _ => (
+                GeometryBuilder::build_as(&rect_polygon(matter.size / 2., Vec2::ZERO, 0.)),
+                local_bounds(-matter.size / 2., matter.size / 2., matter.stroke_width),
+            ),
+        };
+        let new_fill = Fill::color(matter.fill);
+        let new_stroke = Stroke::new(matter.stroke, matter.stroke_width);
+
+        match (path, fill, stroke, aabb) {
+            (Some(mut path), Some(mut fill), Some(mut stroke), Some(mut aabb)) => {
+                *path = new_path;
+                *fill = new_fill;
+                *stroke = new_stroke;
+                *aabb = bounds;
+            }
+            _ => {
+                // first time (or it used to be textured). lyon's own bundle knows which material to use
+                let ShapeBundle { mesh, material, .. } = ShapeBundle::default();
+                commands
+                    .entity(entity)
+                    .remove::<(Handle<MatterMaterial>, Batched)>()
+                    .insert((new_path, new_fill, new_stroke, mesh, material, bounds));
+            }
+        }
+    }
+}
+
+fn rect_polygon(half_extents: Vec2, offset: Vec2, angle: f32) -> shapes::Polygon {
+    let rotation = Vec2::from_angle(angle);
+    shapes::Polygon {
+        points: [
+            Vec2::new(-half_extents.x, -half_extents.y),
+            Vec2::new(half_extents.x, -half_extents.y),
+            Vec2::new(half_extents.x, half_extents.y),
+            Vec2::new(-half_extents.x, half_extents.y),
+        ]
+        .into_iter()
+        .map(|p| offset + rotation.rotate(p))
+        .collect(),
+        closed: true,
+    }
+}
+
+/// Vector outline of any collider, in its local space
+pub fn collider_path(collider: &Collider) -> Path {
+    add_shape(
+        GeometryBuilder::new(),
+        collider.raw.as_typed_shape(),
+        Vec2::ZERO,
+        0.,
+    )
+    .build()
+}
+
+/// Convex pieces of a collider as polygons in its local space, curves flattened to line segments
+pub fn collider_polygons(collider: &Collider) -> Vec<Vec<Vec2>> {
+    let mut polygons = Vec::new();
+    add_polygons(&mut polygons, collider.raw.as_typed_shape(), Vec2::ZERO, 0.);
+    polygons
+}
+
+fn add_polygons(polygons: &mut Vec<Vec<Vec2>>, shape: TypedShape, offset: Vec2, angle: f32) {
+    let rotation = Vec2::from_angle(angle);
+    let to_world = |x: f32, y: f32| offset + rotation.rotate(Vec2::new(x, y));
+    match shape {
+        TypedShape::Ball(ball) => {
+            let segments = CORNER_SEGMENTS * 4;
+            polygons.push(
+                (0..segments)
+                    .map(|i| {
+                        let t = std::f32::consts::TAU * i as f32 / segments as f32;
+                        offset + Vec2::from_angle(t) * ball.radius
+                    })
+                    .collect(),
+            );
+        }
+        TypedShape::Cuboid(cuboid) => polygons.push(
+            rect_polygon(
+                Vec2::new(cuboid.half_extents.x, cuboid.half_extents.y),
+                offset,
+                angle,
+            )
+            .points,
+        ),
+        TypedShape::RoundCuboid(round) => {
+            let half = Vec2::new(
+                round.inner_shape.half_extents.x,
+                round.inner_shape.half_extents.y,
+            );
+            let corners = [
+                Vec2::new(half.x, half.y),
+                Vec2::new(-half.x, half.y),
+                Vec2::new(-half.x, -half.y),
+                Vec2::new(half.x, -half.y),
+            ];
+            polygons.push(rounded_polygon(&corners, round.border_radius, offset, angle).points);
+        }
+        TypedShape::Capsule(capsule) => {
+            let a = to_world(capsule.segment.a.x, capsule.segment.a.y);
+            let b = to_world(capsule.segment.b.x, capsule.segment.b.y);
+            polygons.push(capsule_polygon(a, b, capsule.radius).points);
+        }
+        TypedShape::Triangle(triangle) => polygons.push(vec![
+            to_world(triangle.a.x, triangle.a.y),
+            to_world(triangle.b.x, triangle.b.y),
+            to_world(triangle.c.x, triangle.c.y),
+        ]),
+        TypedShape::ConvexPolygon(polygon) => polygons.push(
+            polygon
+                .points()
+                .iter()
+                .map(|p| to_world(p.x, p.y))
+                .collect(),
+        ),
+        TypedShape::RoundConvexPolygon(round) => {
+            let corners: Vec<Vec2> = round
+                .inner_shape
+                .points()
+                .iter()
+                .map(|p| Vec2::new(p.x, p.y))
+                .collect();
+            polygons.push(rounded_polygon(&corners, round.border_radius, offset, angle).points);
+        }
+        TypedShape::Compound(compound) => {
+            for (isometry, shape) in compound.shapes() {
+                let local = Vec2::new(isometry.translation.vector.x, isometry.translation.vector.y);
+                add_polygons(
+                    polygons,
+                    shape.as_typed_shape(),
+                    to_world(local.x, local.y),
+                    angle + isometry.rotation.angle(),
+                );
+            }
+        }
+        // lines have no area
+        _ => {}
+    }
+}
+
+// how many points we use for each rounded corner
+const CORNER_SEGMENTS: usize = 6;
+
+fn add_shape(
+    builder: GeometryBuilder,
+    shape: TypedShape,
+    offset: Vec2,
+    angle: f32,
+) -> GeometryBuilder {
+    let rotation = Vec2::from_angle(angle);
+    let to_world = |x: f32, y: f32| offset + rotation.rotate(Vec2::new(x, y));
+    match shape {
+        TypedShape::Ball(ball) => builder.add(&shapes::Circle {
+            radius: ball.radius,
+            center: offset,
+        }),
+        TypedShape::Cuboid(cuboid) => builder.add(&rect_polygon(
+            Vec2::new(cuboid.half_extents.x, cuboid.half_extents.y),
+            offset,
+            angle,
+        )),
+        TypedShape::RoundCuboid(round) => {
+            let half = Vec2::new(
+                round.inner_shape.half_extents.x,
+                round.inner_shape.half_extents.y,
+            );
+            let corners = [
+                Vec2::new(half.x, half.y),
+                Vec2::new(-half.x, half.y),
+                Vec2::new(-half.x, -half.y),
+                Vec2::new(half.x, -half.y),
+            ];
+            builder.add(&rounded_polygon(
+                &corners,
+                round.border_radius,
+                offset,
+                angle,
+            ))
+        }
+        TypedShape::Capsule(capsule) => {
+            let a = to_world(capsule.segment.a.x, capsule.segment.a.y);
+            let b = to_world(capsule.segment.b.x, capsule.segment.b.y);
+            builder.add(&capsule_polygon(a, b, capsule.radius))
+        }
+        TypedShape::Triangle(triangle) => builder.add(&shapes::Polygon {
+            points: vec![
+                to_world(triangle.a.x, triangle.a.y),
+                to_world(triangle.b.x, triangle.b.y),
+                to_world(triangle.c.x, triangle.c.y),
+            ],
+            closed: true,
+        }),
+        TypedShape::ConvexPolygon(polygon) => builder.add(&shapes::Polygon {
+            points: polygon
+                .points()
+                .iter()
+                .map(|p| to_world(p.x, p.y))
+                .collect(),
+            closed: true,
+        }),
+        TypedShape::RoundConvexPolygon(round) => {
+            let corners: Vec<Vec2> = round
+                .inner_shape
+                .points()
+                .iter()
+                .map(|p| Vec2::new(p.x, p.y))
+                .collect();
+            builder.add(&rounded_polygon(
+                &corners,
+                round.border_radius,
+                offset,
+                angle,
+            ))
+        }
+        TypedShape::Polyline(polyline) => builder.add(&shapes::Polygon {
+            points: polyline
+                .vertices()
+                .iter()
+                .map(|p| to_world(p.x, p.y))
+                .collect(),
+            closed: false,
+        }),
+        TypedShape::Segment(segment) => builder.add(&shapes::Polygon {
+            points: vec![
+                to_world(segment.a.x, segment.a.y),
+                to_world(segment.b.x, segment.b.y),
+            ],
+            closed: false,
+        }),
+        TypedShape::Compound(compound) => {
+            let mut builder = builder;
+            for (isometry, shape) in compound.shapes() {
+                let local = Vec2::new(isometry.translation.vector.x, isometry.translation.vector.y);
+                builder = add_shape(
+                    builder,
+                    shape.as_typed_shape(),
+                    to_world(local.x, local.y),
+                    angle + isometry.rotation.angle(),
+                );
+            }
+            builder
+        }
+        // heightfields, trimeshes, halfspaces etc, we dont make any of those
+        _ => builder,
+    }
+}
+
+// corners must be in order around the shape, the result is offset outwards by `radius`
+fn rounded_polygon(corners: &[Vec2], radius: f32, offset: Vec2, angle: f32) -> shapes::Polygon {
+    let rotation = Vec2::from_angle(angle);
+    let count = corners.len();
+    let mut points = Vec::with_capacity(count * (CORNER_SEGMENTS + 1));
+    for i in 0..count {
+        let prev = corners[(i + count - 1) % count];
+        let corner = corners[i];
+        let next = corners[(i + 1) % count];
+        // outward normals of the two edges meeting here
+        let n1 = (corner - prev).perp().normalize_or_zero() * -1.;
+        let n2 = (next - corner).perp().normalize_or_zero() * -1.;
+        let (start, mut end) = (angle_of(n1), angle_of(n2));
+        // we always sweep the short way round
+        while end - start > std::f32::consts::PI {
+            end -= std::f32::consts::TAU;
+        }
+        while start - end > std::f32::consts::PI {
+            end += std::f32::consts::TAU;
+        }
+        for s in 0..=CORNER_SEGMENTS {
+            let a = start + (end - start) * s as f32 / CORNER_SEGMENTS as f32;
+            points.push(offset + rotation.rotate(corner + Vec2::from_angle(a) * radius));
+        }
+    }
+    shapes::Polygon {
+        points,
+        closed: true,
+    }
+}
+
+fn angle_of(v: Vec2) -> f32 {
+    v.y.atan2(v.x)
+}
+
+fn capsule_polygon(a: Vec2, b: Vec2, radius: f32) -> shapes::Polygon {
+    let axis = (b - a).normalize_or_zero();
+    let axis = if axis == Vec2::ZERO { Vec2::Y } else { axis };
+    let base = angle_of(axis);
+    let mut points = Vec::with_capacity((CORNER_SEGMENTS * 2 + 2) * 2);
+    // half circle around b, then around a
+    for s in 0..=CORNER_SEGMENTS * 2 {
+        let t = base - std::f32::consts::FRAC_PI_2
+            + std::f32::consts::PI * s as f32 / (CORNER_SEGMENTS * 2) as f32;
+        points.push(b + Vec2::from_angle(t) * radius);
+    }
+    for s in 0..=CORNER_SEGMENTS * 2 {
+        let t = base
+            + std::f32::consts::FRAC_PI_2
+            + std::f32::consts::PI * s as f32 / (CORNER_SEGMENTS * 2) as f32;
+        points.push(a + Vec2::from_angle(t) * radius);
+    }
+    shapes::Polygon {
+        points,
+        closed: true,
+    }
+}
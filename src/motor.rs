@@ -0,0 +1,332 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_egui::egui;
+use bevy_rapier2d::prelude::*;
+
+use crate::body_material::BodyMaterial;
+use crate::inspector::body_of_collider;
+use crate::matter::Matter;
+use crate::ragdoll::RagdollPart;
+use crate::settings::{next_bound_key, KeyCaptureSet};
+use crate::snapping::Snapping;
+use crate::{cursor_world_position, EguiUnfocusedSystemSet, MainCamera, Tool, Tools};
+
+const WHEEL_RADIUS: f32 = 4.;
+// how hard velocity motors chase their target speed
+const VELOCITY_GAIN: f32 = 5.;
+// spring and damper pulling angle motors to their target
+const ANGLE_STIFFNESS: f32 = 50.;
+const ANGLE_DAMPING: f32 = 5.;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MotorMode {
+    /// spin at `speed`
+    Velocity,
+    /// hold `target_angle`
+    Angle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MotorKey {
+    Forward,
+    Backward,
+}
+
+/// Powers the `ImpulseJoint` on the same entity, turning it around its pivot.
+#[derive(Component, Clone)]
+pub struct Motor {
+    pub mode: MotorMode,
+    /// radians per second, positive is counter-clockwise
+    pub speed: f32,
+    /// radians from where the joint was attached, for `MotorMode::Angle`
+    pub target_angle: f32,
+    pub max_torque: f32,
+    /// with keys bound, a velocity motor only runs while one is held (backward runs it at -`speed`),
+    /// and an angle motor's target turns at `speed` while they're held
+    pub forward: Option<KeyCode>,
+    pub backward: Option<KeyCode>,
+}
+
+impl Default for Motor {
+    fn default() -> Self {
+        Self {
+            mode: MotorMode::Velocity,
+            speed: 10.,
+            target_angle: 0.,
+            max_torque: 500.,
+            forward: None,
+            backward: None,
+        }
+    }
+}
+
+impl Motor {
+    /// -1 to 1 from the bound keys, `None` if there aren't any
+    fn input(&self, keys: &Input<KeyCode>) -> Option<f32> {
+        if self.forward.is_none() && self.backward.is_none() {
+            return None;
+        }
+        let held = |key: Option<KeyCode>| key.is_some_and(|key| keys.pressed(key)) as i32 as f32;
+        Some(held(self.forward) - held(self.backward))
+    }
+
+    fn key_mut(&mut self, key: MotorKey) -> &mut Option<KeyCode> {
+        match key {
+            MotorKey::Forward => &mut self.forward,
+            MotorKey::Backward => &mut self.backward,
+        }
+    }
+}
+
+/// Which motor key is waiting for a key press
+#[derive(Resource, Default)]
+pub struct MotorRebinding(Option<(Entity, MotorKey)>);
+
+impl MotorRebinding {
+    pub fn waiting(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+pub struct MotorPlugin;
+
+impl Plugin for MotorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MotorRebinding>()
+            .add_systems(Update, place_wheel.in_set(EguiUnfocusedSystemSet))
+            .add_systems(
+                Update,
+                (
+                    capture_motor_key.in_set(KeyCaptureSet),
+                    keep_motors_awake,
+                    release_removed_motors,
+                    drive_motors,
+                )
+                    .chain(),
+            );
+    }
+}
+
+// click a body with the wheel tool to stick a powered wheel on it
+fn place_wheel(
+    mut commands: Commands,
+    buttons: Res<Input<MouseButton>>,
+    tool_res: Res<Tools>,
+    snapping: Res<Snapping>,
+    rapier_context: Res<RapierContext>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform, &OrthographicProjection), With<MainCamera>>,
+    parts: Query<&RagdollPart>,
+    transforms: Query<&GlobalTransform>,
+) {
+    if tool_res.current_tool != Tool::Wheel || !buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+    let (camera, camera_transform, projection) = camera_query.single();
+    let Some(world_position) = cursor_world_position(q_window.single(), camera, camera_transform)
+    else {
+        return;
+    };
+    let point = snapping.snap_point(
+        world_position,
+        projection.scale,
+        &rapier_context,
+        QueryFilter::default(),
+    );
+    let mut collider = None;
+    rapier_context.intersections_with_point(point, QueryFilter::default(), |entity| {
+        collider = Some(entity);
+        false
+    });
+    let Some(body) = collider.map(|collider| body_of_collider(&rapier_context, &parts, collider))
+    else {
+        return;
+    };
+    let Ok(body_transform) = transforms.get(body) else {
+        return;
+    };
+    let anchor = body_transform
+        .affine()
+        .inverse()
+        .transform_point3(point.extend(0.))
+        .truncate();
+
+    let mut joint: GenericJoint = RevoluteJointBuilder::new()
+        .local_anchor1(anchor)
+        .local_anchor2(Vec2::ZERO)
+        .build()
+        .into();
+    // the wheel sits inside the body, they'd just push each other apart
+    joint.set_contacts_enabled(false);
+    commands.spawn((
+        SpatialBundle::from_transform(Transform::from_translation(point.extend(0.))),
+        Matter::new(Color::rgb(0.2, 0.2, 0.22)),
+        Collider::ball(WHEEL_RADIUS),
+        RigidBody::Dynamic,
+        BodyMaterial::Rubber,
+        ImpulseJoint::new(body, joint),
+        // arrow keys drive it like a car, right is clockwise
+        Motor {
+            speed: -10.,
+            forward: Some(KeyCode::Right),
+            backward: Some(KeyCode::Left),
+            ..default()
+        },
+    ));
+}
+
+// a sleeping motor can't be woken by pressing its keys, so they stay up
+fn keep_motors_awake(mut commands: Commands, motors: Query<Entity, Added<Motor>>) {
+    for entity in motors.iter() {
+        commands.entity(entity).insert(Sleeping::disabled());
+    }
+}
+
+// the joint keeps whatever motor rapier has, so turn it off when the `Motor` goes
+fn release_removed_motors(
+    mut removed: RemovedComponents<Motor>,
+    mut joints: Query<&mut ImpulseJoint>,
+) {
+    for entity in removed.read() {
+        if let Ok(mut joint) = joints.get_mut(entity) {
+            joint.data.set_motor(JointAxis::AngX, 0., 0., 0., 0.);
+        }
+    }
+}
+
+fn drive_motors(
+    time: Res<Time>,
+    keys: Res<Input<KeyCode>>,
+    mut motors: Query<(&mut Motor, &mut ImpulseJoint)>,
+) {
+    let dt = time.delta_seconds();
+    for (mut motor, mut joint) in motors.iter_mut() {
+        let input = motor.input(&keys);
+        let (target_pos, target_vel, stiffness, damping) = match motor.mode {
+            MotorMode::Velocity => (0., motor.speed * input.unwrap_or(1.), 0., VELOCITY_GAIN),
+            MotorMode::Angle => {
+                if let Some(input) = input.filter(|input| *input != 0.) {
+                    motor.target_angle += motor.speed * input * dt;
+                }
+                (motor.target_angle, 0., ANGLE_STIFFNESS, ANGLE_DAMPING)
+            }
+        };
+        set_motor_if_changed(
+            &mut joint,
+            target_pos,
+            target_vel,
+            stiffness,
+            damping,
+            motor.max_torque,
+        );
+    }
+}
+
+/// Sets the joint's angular motor, unless it already is exactly that. Any write to the joint
+/// makes rapier resync it, so doing it every frame for nothing adds up.
+pub fn set_motor_if_changed(
+    joint: &mut Mut<ImpulseJoint>,
+    target_pos: f32,
+    target_vel: f32,
+    stiffness: f32,
+    damping: f32,
+    max_force: f32,
+) {
+    let unchanged = joint.data.motor(JointAxis::AngX).is_some_and(|current| {
+        current.target_pos == target_pos
+            && current.target_vel == target_vel
+            && current.stiffness == stiffness
+            && current.damping == damping
+            && current.max_force == max_force
+    });
+    if !unchanged {
+        joint
+            .data
+            .set_motor(JointAxis::AngX, target_pos, target_vel, stiffness, damping)
+            .set_motor_max_force(JointAxis::AngX, max_force);
+    }
+}
+
+// the next bindable key pressed after clicking a motor key takes its place
+fn capture_motor_key(
+    keys: Res<Input<KeyCode>>,
+    mut rebinding: ResMut<MotorRebinding>,
+    mut motors: Query<&mut Motor>,
+) {
+    let Some((entity, which)) = rebinding.0 else {
+        return;
+    };
+    let Ok(mut motor) = motors.get_mut(entity) else {
+        rebinding.0 = None;
+        return;
+    };
+    let Some(pressed) = next_bound_key(&keys) else {
+        return;
+    };
+    if let Ok(key) = pressed {
+        *motor.key_mut(which) = Some(key);
+    }
+    rebinding.0 = None;
+}
+
+/// Motor section of the inspector
+pub fn motor_ui(
+    ui: &mut egui::Ui,
+    entity: Entity,
+    motor: &mut Motor,
+    rebinding: &mut MotorRebinding,
+) {
+    ui.horizontal(|ui| {
+        ui.selectable_value(&mut motor.mode, MotorMode::Velocity, "Spin");
+        ui.selectable_value(&mut motor.mode, MotorMode::Angle, "Hold angle");
+    });
+    ui.horizontal(|ui| {
+        ui.label("Speed");
+        ui.add(
+            egui::DragValue::new(&mut motor.speed)
+                .speed(0.1)
+                .suffix(" rad/s"),
+        );
+    });
+    if motor.mode == MotorMode::Angle {
+        ui.horizontal(|ui| {
+            ui.label("Target angle");
+            let mut degrees = motor.target_angle.to_degrees();
+            if ui
+                .add(egui::DragValue::new(&mut degrees).suffix("°"))
+                .changed()
+            {
+                motor.target_angle = degrees.to_radians();
+            }
+        });
+    }
+    ui.horizontal(|ui| {
+        ui.label("Max torque");
+        ui.add(
+            egui::DragValue::new(&mut motor.max_torque)
+                .speed(10.)
+                .clamp_range(0.0..=f32::MAX),
+        );
+    });
+    for (label, which) in [
+        ("Forward", MotorKey::Forward),
+        ("Backward", MotorKey::Backward),
+    ] {
+        ui.horizontal(|ui| {
+            ui.label(label);
+            let text = if rebinding.0 == Some((entity, which)) {
+                "Press a key...".to_string()
+            } else {
+                match motor.key_mut(which) {
+                    Some(key) => format!("{:?}", key),
+                    None => "None".to_string(),
+                }
+            };
+            if ui.button(text).clicked() {
+                rebinding.0 = Some((entity, which));
+            }
+            if motor.key_mut(which).is_some() && ui.small_button("x").clicked() {
+                *motor.key_mut(which) = None;
+            }
+        });
+    }
+}
@@ -0,0 +1,240 @@
+use std::collections::VecDeque;
+
+use bevy::diagnostic::{
+    Diagnostic, DiagnosticId, Diagnostics, DiagnosticsStore, FrameTimeDiagnosticsPlugin,
+    RegisterDiagnostic,
+};
+use bevy::prelude::*;
+use bevy::utils::Instant;
+use bevy_egui::{egui, EguiContexts};
+use bevy_rapier2d::prelude::*;
+
+use crate::settings::KeyBindings;
+use crate::EguiUnfocusedSystemSet;
+
+/// How long rapier (and anything in `StepTimed`) took to step the world, in milliseconds
+pub const PHYSICS_STEP_TIME: DiagnosticId =
+    DiagnosticId::from_u128(196482375918237465019283746501928374);
+
+// how many frames the graph shows
+const GRAPH_FRAMES: usize = 240;
+const GRAPH_SIZE: egui::Vec2 = egui::vec2(240., 60.);
+
+#[derive(Resource, Default)]
+pub struct PerformanceOverlay {
+    pub visible: bool,
+    /// frame times in ms, newest at the back
+    frame_times: VecDeque<f32>,
+}
+
+#[derive(Resource, Default)]
+struct StepStart(Option<Instant>);
+
+/// Runs right before rapier's step and counts toward `PHYSICS_STEP_TIME`, for simulation we do ourselves
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StepTimed;
+
+/// Times rapier's step into `PHYSICS_STEP_TIME`. Doesn't need a window, so benchmarks use it too.
+pub struct StepTimePlugin;
+
+impl Plugin for StepTimePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_diagnostic(
+            Diagnostic::new(PHYSICS_STEP_TIME, "physics_step_time", 60).with_suffix("ms"),
+        )
+        .init_resource::<StepStart>()
+        .configure_sets(
+            PostUpdate,
+            StepTimed
+                .after(PhysicsSet::SyncBackend)
+                .before(PhysicsSet::StepSimulation),
+        )
+        .add_systems(
+            PostUpdate,
+            (
+                start_step_timer
+                    .after(PhysicsSet::SyncBackend)
+                    .before(StepTimed),
+                end_step_timer
+                    .after(PhysicsSet::StepSimulation)
+                    .before(PhysicsSet::Writeback),
+            ),
+        );
+    }
+}
+
+pub struct PerformancePlugin;
+
+impl Plugin for PerformancePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(StepTimePlugin)
+            .init_resource::<PerformanceOverlay>()
+            .add_systems(Update, toggle_overlay.in_set(EguiUnfocusedSystemSet))
+            .add_systems(Update, (record_frame_time, overlay_ui).chain());
+    }
+}
+
+fn start_step_timer(mut start: ResMut<StepStart>) {
+    start.0 = Some(Instant::now());
+}
+
+fn end_step_timer(mut start: ResMut<StepStart>, mut diagnostics: Diagnostics) {
+    if let Some(start) = start.0.take() {
+        diagnostics.add_measurement(PHYSICS_STEP_TIME, || start.elapsed().as_secs_f64() * 1000.);
+    }
+}
+
+fn toggle_overlay(
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut overlay: ResMut<PerformanceOverlay>,
+) {
+    if keys.just_pressed(bindings.toggle_performance) {
+        overlay.visible = !overlay.visible;
+    }
+}
+
+fn record_frame_time(time: Res<Time>, mut overlay: ResMut<PerformanceOverlay>) {
+    // keep recording while hidden so the graph isn't empty when it opens
+    let overlay = overlay.bypass_change_detection();
+    overlay.frame_times.push_back(time.delta_seconds() * 1000.);
+    while overlay.frame_times.len() > GRAPH_FRAMES {
+        overlay.frame_times.pop_front();
+    }
+}
+
+/// Counts of what rapier is simulating right now
+#[derive(Default)]
+struct WorldCounts {
+    bodies: usize,
+    colliders: usize,
+    joints: usize,
+    awake: usize,
+    sleeping: usize,
+}
+
+impl WorldCounts {
+    fn of(rapier_context: &RapierContext) -> Self {
+        let mut counts = Self {
+            bodies: rapier_context.bodies.len(),
+            colliders: rapier_context.colliders.len(),
+            joints: rapier_context.impulse_joints.len(),
+            ..default()
+        };
+        // fixed bodies never sleep or wake, so only count the ones that can move
+        for (_, body) in rapier_context.bodies.iter() {
+            if body.is_fixed() {
+                continue;
+            }
+            if body.is_sleeping() {
+                counts.sleeping += 1;
+            } else {
+                counts.awake += 1;
+            }
+        }
+        counts
+    }
+}
+
+fn overlay_ui(
+    mut contexts: EguiContexts,
+    overlay: Res<PerformanceOverlay>,
+    diagnostics: Res<DiagnosticsStore>,
+    rapier_context: Res<RapierContext>,
+) {
+    if !overlay.visible {
+        return;
+    }
+    let fps = diagnostics
+        .get(FrameTimeDiagnosticsPlugin::FPS)
+        .and_then(|fps| fps.smoothed());
+    let step_time = diagnostics
+        .get(PHYSICS_STEP_TIME)
+        .and_then(|step| step.smoothed());
+    let counts = WorldCounts::of(&rapier_context);
+
+    egui::Window::new("Performance")
+        .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-10., 10.))
+        .title_bar(false)
+        .resizable(false)
+        .interactable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            egui::Grid::new("performance").show(ui, |ui| {
+                ui.label("FPS");
+                ui.label(fps.map_or("-".to_string(), |fps| format!("{:.0}", fps)));
+                ui.end_row();
+                ui.label("Frame");
+                ui.label(
+                    overlay
+                        .frame_times
+                        .back()
+                        .map_or("-".to_string(), |ms| format!("{:.1} ms", ms)),
+                );
+                ui.end_row();
+                ui.label("Physics step");
+                ui.label(step_time.map_or("-".to_string(), |ms| format!("{:.2} ms", ms)));
+                ui.end_row();
+                ui.label("Bodies");
+                ui.label(format!(
+                    "{} ({} awake, {} sleeping)",
+                    counts.bodies, counts.awake, counts.sleeping
+                ));
+                ui.end_row();
+                ui.label("Colliders");
+                ui.label(counts.colliders.to_string());
+                ui.end_row();
+                ui.label("Joints");
+                ui.label(counts.joints.to_string());
+                ui.end_row();
+            });
+            frame_graph(ui, &overlay.frame_times);
+        });
+}
+
+// bar per frame, scaled so 60fps sits in the middle
+fn frame_graph(ui: &mut egui::Ui, frame_times: &VecDeque<f32>) {
+    let (rect, _) = ui.allocate_exact_size(GRAPH_SIZE, egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    let visuals = ui.visuals();
+    painter.rect_filled(rect, 2., visuals.extreme_bg_color);
+
+    let target = 1000. / 60.;
+    let top = frame_times.iter().copied().fold(target * 2., f32::max);
+    let to_y = |ms: f32| rect.bottom() - rect.height() * (ms / top).min(1.);
+
+    let bar_width = rect.width() / GRAPH_FRAMES as f32;
+    // right aligned, so new frames come in from the right
+    let first = GRAPH_FRAMES - frame_times.len();
+    for (i, ms) in frame_times.iter().enumerate() {
+        let x = rect.left() + (first + i) as f32 * bar_width;
+        let color = if *ms > target * 1.5 {
+            egui::Color32::from_rgb(230, 90, 80)
+        } else {
+            visuals.selection.bg_fill
+        };
+        painter.rect_filled(
+            egui::Rect::from_min_max(
+                egui::pos2(x, to_y(*ms)),
+                egui::pos2(x + bar_width, rect.bottom()),
+            ),
+            0.,
+            color,
+        );
+    }
+
+    let target_y = to_y(target);
+    painter.line_segment(
+        [
+            egui::pos2(rect.left(), target_y),
+            egui::pos2(rect.right(), target_y),
+        ],
+        egui::Stroke::new(1., visuals.weak_text_color()),
+    );
+    painter.text(
+        egui::pos2(rect.left() + 2., target_y - 1.),
+        egui::Align2::LEFT_BOTTOM,
+        "60 fps",
+        egui::FontId::proportional(10.),
+        visuals.weak_text_color(),
+    );
+}
@@ -0,0 +1,203 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_pancam::PanCam;
+use bevy_rapier2d::prelude::*;
+
+use crate::camera::CameraControl;
+use crate::context_menu::body_at_point;
+use crate::ragdoll::{Ragdoll, RagdollPart};
+use crate::settings::{KeyBindings, KeyCapture, KeyCaptureSet};
+use crate::{cursor_world_position, EguiUnfocusedSystemSet, MainCamera};
+
+/// The body we're currently controlling. There's only ever one.
+#[derive(Component)]
+pub struct Player {
+    pub move_speed: f32,
+    pub acceleration: f32,
+    pub jump_speed: f32,
+    /// distance from the body origin down to its lowest point, worked out when we possess it
+    feet_offset: f32,
+    probe_radius: f32,
+    grounded: bool,
+    /// we locked rotation ourselves, so we have to unlock it on release
+    locked_rotation: bool,
+}
+
+impl Player {
+    pub fn is_grounded(&self) -> bool {
+        self.grounded
+    }
+}
+
+// how far below the feet still counts as standing on something
+const GROUND_MARGIN: f32 = 0.6;
+
+pub struct PlayerPlugin;
+
+impl Plugin for PlayerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                possess.before(KeyCaptureSet),
+                (detect_ground, move_player).chain(),
+            )
+                .in_set(EguiUnfocusedSystemSet),
+        );
+    }
+}
+
+// F to possess whatever is under the cursor (or let go of the current one), escape also lets go.
+// while a key is being rebound those presses are meant for that instead
+fn possess(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    rapier_context: Res<RapierContext>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    mut camera_query: Query<(&Camera, &GlobalTransform, &mut PanCam), With<MainCamera>>,
+    player_query: Query<(Entity, &Player)>,
+    mut control: ResMut<CameraControl>,
+    body_query: Query<(&RigidBody, Option<&Ragdoll>, Option<&Velocity>)>,
+    parts: Query<&RagdollPart>,
+    colliders: Query<(&Collider, &GlobalTransform)>,
+    children_query: Query<&Children>,
+    transforms: Query<&GlobalTransform>,
+    key_capture: KeyCapture,
+) {
+    if key_capture.waiting() {
+        return;
+    }
+    let release = keys.just_pressed(KeyCode::Escape);
+    if !keys.just_pressed(bindings.possess) && !release {
+        return;
+    }
+    let (camera, camera_transform, mut pancam) = camera_query.single_mut();
+
+    // let go of whoever we had
+    let had_player = !player_query.is_empty();
+    for (entity, player) in player_query.iter() {
+        let mut ent = commands.entity(entity);
+        ent.remove::<Player>();
+        if player.locked_rotation {
+            ent.remove::<LockedAxes>();
+        }
+        if control.follow == Some(entity) {
+            control.follow = None;
+        }
+    }
+    pancam.enabled = true;
+    if had_player || release {
+        return;
+    }
+
+    let Some(world_position) = cursor_world_position(q_window.single(), camera, camera_transform)
+    else {
+        return;
+    };
+    let Some(entity) = body_at_point(&rapier_context, &parts, world_position) else {
+        return;
+    };
+    let Ok((rigidbody, ragdoll, velocity)) = body_query.get(entity) else {
+        return;
+    };
+    if *rigidbody != RigidBody::Dynamic {
+        return;
+    }
+    let Ok(body_transform) = transforms.get(entity) else {
+        return;
+    };
+
+    // find the lowest point of all the colliders on this body, in world space. we assume it's roughly upright right now
+    let origin_y = body_transform.translation().y;
+    let mut lowest = origin_y;
+    let mut narrowest = f32::MAX;
+    let mut collider_entities = vec![entity];
+    if let Ok(children) = children_query.get(entity) {
+        collider_entities.extend(children.iter().copied());
+    }
+    for collider_entity in collider_entities {
+        if let Ok((collider, global_transform)) = colliders.get(collider_entity) {
+            let aabb = collider.raw.compute_local_aabb();
+            let center = global_transform.translation().y;
+            lowest = lowest.min(center + aabb.mins.y);
+            narrowest = narrowest.min(aabb.maxs.x - aabb.mins.x);
+        }
+    }
+    let probe_radius = if narrowest == f32::MAX {
+        1.
+    } else {
+        (narrowest * 0.4).clamp(0.2, 4.)
+    };
+
+    let locked_rotation = ragdoll.is_none();
+    let mut ent = commands.entity(entity);
+    ent.insert(Player {
+        move_speed: 40.,
+        acceleration: 10.,
+        jump_speed: 50.,
+        feet_offset: origin_y - lowest,
+        probe_radius,
+        grounded: false,
+        locked_rotation,
+    });
+    if velocity.is_none() {
+        ent.insert(Velocity::default());
+    }
+    if locked_rotation {
+        ent.insert(LockedAxes::ROTATION_LOCKED);
+    }
+    pancam.enabled = false;
+    control.goal = None;
+    control.follow = Some(entity);
+}
+
+// shape cast a little ball straight down from the middle of the body to see if there's ground under our feet
+fn detect_ground(
+    rapier_context: Res<RapierContext>,
+    mut player_query: Query<(Entity, &mut Player, &GlobalTransform)>,
+) {
+    for (entity, mut player, global_transform) in player_query.iter_mut() {
+        let origin = global_transform.translation().truncate();
+        let max_toi = (player.feet_offset - player.probe_radius).max(0.) + GROUND_MARGIN;
+        let shape = Collider::ball(player.probe_radius);
+        let filter = QueryFilter::default()
+            .exclude_rigid_body(entity)
+            .exclude_sensors();
+        player.grounded = rapier_context
+            .cast_shape(origin, 0., Vec2::NEG_Y, &shape, max_toi, filter)
+            .is_some();
+    }
+}
+
+// A/D to walk, W to jump, S to drop faster
+fn move_player(
+    time: Res<Time>,
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut player_query: Query<(&Player, &mut Velocity)>,
+) {
+    for (player, mut velocity) in player_query.iter_mut() {
+        let mut direction = 0.;
+        if keys.pressed(bindings.move_left) {
+            direction -= 1.;
+        }
+        if keys.pressed(bindings.move_right) {
+            direction += 1.;
+        }
+
+        // less control in the air
+        let control = if player.grounded { 1. } else { 0.3 };
+        let target = direction * player.move_speed;
+        let blend = (player.acceleration * control * time.delta_seconds()).min(1.);
+        velocity.linvel.x += (target - velocity.linvel.x) * blend;
+
+        if player.grounded && keys.just_pressed(bindings.jump) {
+            velocity.linvel.y = player.jump_speed;
+        } else if !player.grounded && keys.pressed(bindings.move_down) {
+            let target = -player.jump_speed;
+            if velocity.linvel.y > target {
+                velocity.linvel.y += (target - velocity.linvel.y) * blend;
+            }
+        }
+    }
+}
@@ -0,0 +1,318 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::context_menu::body_at_point;
+use crate::damage::Health;
+use crate::matter::Matter;
+use crate::motor::set_motor_if_changed;
+use crate::settings::KeyBindings;
+use crate::{cursor_world_position, EguiUnfocusedSystemSet, MainCamera};
+
+// how tall the body sprite is compared to its width (from body.png)
+const BODY_ASPECT: f32 = 2.09941520468;
+const HEAD_RADIUS: f32 = 2.9;
+// contacts softer than this dont even make an event, otherwise standing on the ground spams them every frame
+const IMPACT_EVENT_THRESHOLD: f32 = 5000.;
+
+/// Root of a person, lives on the torso.
+#[derive(Component)]
+pub struct Ragdoll {
+    pub head: Entity,
+}
+
+/// The upright torque we put in the torso's `ExternalForce` last frame. Thrusters add to the
+/// same torque, so we swap ours out instead of overwriting theirs.
+#[derive(Component, Default)]
+struct UprightTorque(f32);
+
+/// Every entity with a collider that belongs to a ragdoll gets this, so contact events can find their way back to the torso.
+#[derive(Component)]
+pub struct RagdollPart {
+    pub root: Entity,
+}
+
+/// Optional "alive" controller. When present, the ragdoll tries to stand up and keep its head straight, and goes limp for a bit after a big hit.
+#[derive(Component)]
+pub struct ActiveRagdoll {
+    /// PD gains for keeping the torso upright, these get multiplied by the inertia so they work for any size
+    pub upright_stiffness: f32,
+    pub upright_damping: f32,
+    /// motor gains for the neck
+    pub neck_stiffness: f32,
+    pub neck_damping: f32,
+    /// contact force above which we go limp
+    pub limp_threshold: f32,
+    /// how long we stay limp after a hit, in seconds
+    pub limp_time: f32,
+    /// how long it takes to get back to full strength after being limp
+    pub recovery_time: f32,
+    state: RagdollState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RagdollState {
+    Standing,
+    Limp { timer: f32 },
+    Recovering { timer: f32 },
+}
+
+impl Default for ActiveRagdoll {
+    fn default() -> Self {
+        Self {
+            upright_stiffness: 250.,
+            upright_damping: 25.,
+            neck_stiffness: 40.,
+            neck_damping: 4.,
+            limp_threshold: 40000.,
+            limp_time: 1.5,
+            recovery_time: 1.,
+            state: RagdollState::Standing,
+        }
+    }
+}
+
+impl ActiveRagdoll {
+    pub fn is_limp(&self) -> bool {
+        matches!(self.state, RagdollState::Limp { .. })
+    }
+
+    /// Knock it out for `limp_time` seconds
+    pub fn go_limp(&mut self) {
+        self.state = RagdollState::Limp {
+            timer: self.limp_time,
+        };
+    }
+
+    /// 0 when limp, ramps up to 1 while recovering
+    fn strength(&self) -> f32 {
+        match self.state {
+            RagdollState::Standing => 1.,
+            RagdollState::Limp { .. } => 0.,
+            RagdollState::Recovering { timer } => {
+                1. - (timer / self.recovery_time.max(0.001)).clamp(0., 1.)
+            }
+        }
+    }
+}
+
+#[derive(Resource, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RagdollSettings {
+    /// new people from P/M get an `ActiveRagdoll`
+    pub spawn_alive: bool,
+}
+
+impl Default for RagdollSettings {
+    fn default() -> Self {
+        Self { spawn_alive: true }
+    }
+}
+
+pub struct RagdollPlugin;
+
+impl Plugin for RagdollPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RagdollSettings>()
+            .add_systems(Update, (ragdoll_impacts, drive_active_ragdolls).chain())
+            .add_systems(Update, toggle_alive.in_set(EguiUnfocusedSystemSet));
+    }
+}
+
+pub fn spawn_person(
+    commands: &mut Commands,
+    asset_server: &Res<AssetServer>,
+    color: Color,
+    world_position: Vec2,
+    alive: bool,
+) -> Entity {
+    let body = commands
+        .spawn((
+            RigidBody::Dynamic,
+            SpatialBundle::from_transform(Transform::from_translation(Vec3::new(
+                world_position.x,
+                world_position.y,
+                0.,
+            ))),
+            Matter::textured(
+                Vec2::new(6., BODY_ASPECT * 6.),
+                color,
+                asset_server.load("body.png"),
+            ),
+            Velocity::default(),
+            ReadMassProperties::default(),
+            ExternalForce::default(),
+        ))
+        .id();
+
+    commands.entity(body).with_children(|children| {
+        children.spawn((
+            Collider::round_cuboid(2.4, 2.1, 0.04),
+            Transform::from_translation(Vec3::new(0., -3.7, 0.)),
+            RagdollPart { root: body },
+            ActiveEvents::CONTACT_FORCE_EVENTS,
+            ContactForceEventThreshold(IMPACT_EVENT_THRESHOLD),
+        ));
+        children.spawn((
+            Collider::ball(HEAD_RADIUS),
+            Transform::from_translation(Vec3::new(0., -1.8, 0.)),
+            RagdollPart { root: body },
+            ActiveEvents::CONTACT_FORCE_EVENTS,
+            ContactForceEventThreshold(IMPACT_EVENT_THRESHOLD),
+        ));
+    });
+
+    let joint = RevoluteJointBuilder::new()
+        .local_anchor1(Vec2::new(0.0, 1.8)) // anchor on body
+        .local_anchor2(Vec2::new(0.0, -2.5)); // anchor on head
+
+    // now the head, its circle of same radius 2.9. no head.png, we just circle.png like normal
+    let head = commands
+        .spawn((
+            RigidBody::Dynamic,
+            SpatialBundle::from_transform(Transform::from_translation(Vec3::new(
+                world_position.x,
+                world_position.y + BODY_ASPECT * 6. / 2. + HEAD_RADIUS / 2.,
+                0.,
+            ))),
+            Matter::textured(
+                Vec2::new(HEAD_RADIUS * 2., HEAD_RADIUS * 2.),
+                color,
+                asset_server.load("circle.png"),
+            ),
+            Collider::ball(HEAD_RADIUS),
+            ImpulseJoint::new(body, joint),
+            RagdollPart { root: body },
+            ActiveEvents::CONTACT_FORCE_EVENTS,
+            ContactForceEventThreshold(IMPACT_EVENT_THRESHOLD),
+        ))
+        .id();
+
+    commands.entity(body).insert((
+        Ragdoll { head },
+        UprightTorque::default(),
+        Health::default(),
+    ));
+    if alive {
+        commands.entity(body).insert(ActiveRagdoll::default());
+    }
+
+    body
+}
+
+// big hits knock people out
+fn ragdoll_impacts(
+    mut contact_force_events: EventReader<ContactForceEvent>,
+    parts: Query<&RagdollPart>,
+    mut active_query: Query<&mut ActiveRagdoll>,
+) {
+    for event in contact_force_events.read() {
+        for collider in [event.collider1, event.collider2] {
+            let Ok(part) = parts.get(collider) else {
+                continue;
+            };
+            let Ok(mut active) = active_query.get_mut(part.root) else {
+                continue;
+            };
+            if event.total_force_magnitude > active.limp_threshold && !active.is_limp() {
+                active.go_limp();
+            }
+        }
+    }
+}
+
+fn drive_active_ragdolls(
+    time: Res<Time>,
+    mut ragdoll_query: Query<(
+        &Ragdoll,
+        Option<&mut ActiveRagdoll>,
+        &Transform,
+        &Velocity,
+        &ReadMassProperties,
+        &mut ExternalForce,
+        &mut UprightTorque,
+    )>,
+    mut joint_query: Query<&mut ImpulseJoint>,
+) {
+    let dt = time.delta_seconds();
+    for (ragdoll, active, transform, velocity, mass_props, mut force, mut upright) in
+        ragdoll_query.iter_mut()
+    {
+        let Some(mut active) = active else {
+            // not alive (anymore), make sure we aren't still pushing
+            if upright.0 != 0. {
+                force.torque -= upright.0;
+                upright.0 = 0.;
+            }
+            if let Ok(mut neck) = joint_query.get_mut(ragdoll.head) {
+                set_motor_if_changed(&mut neck, 0., 0., 0., 0., f32::MAX);
+            }
+            continue;
+        };
+
+        active.state = match active.state {
+            RagdollState::Limp { timer } if timer - dt <= 0. => RagdollState::Recovering {
+                timer: active.recovery_time,
+            },
+            RagdollState::Limp { timer } => RagdollState::Limp { timer: timer - dt },
+            RagdollState::Recovering { timer } if timer - dt <= 0. => RagdollState::Standing,
+            RagdollState::Recovering { timer } => RagdollState::Recovering { timer: timer - dt },
+            RagdollState::Standing => RagdollState::Standing,
+        };
+        let strength = active.strength();
+
+        // PD controller on the torso angle, target is 0 (upright)
+        let angle = transform.rotation.to_euler(EulerRot::XYZ).2;
+        let inertia = mass_props.principal_inertia;
+        let torque = strength
+            * inertia
+            * (-active.upright_stiffness * angle - active.upright_damping * velocity.angvel);
+        force.torque += torque - upright.0;
+        upright.0 = torque;
+
+        // neck motor pulls the head back to the standing pose
+        if let Ok(mut neck) = joint_query.get_mut(ragdoll.head) {
+            set_motor_if_changed(
+                &mut neck,
+                0.,
+                0.,
+                active.neck_stiffness * strength,
+                active.neck_damping * strength,
+                f32::MAX,
+            );
+        }
+    }
+}
+
+// L toggles alive mode on the person under the cursor
+fn toggle_alive(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    rapier_context: Res<RapierContext>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    parts: Query<&RagdollPart>,
+    ragdolls: Query<(), With<Ragdoll>>,
+    active_query: Query<(), With<ActiveRagdoll>>,
+) {
+    if !keys.just_pressed(bindings.toggle_alive) {
+        return;
+    }
+    let (camera, camera_transform) = camera_query.single();
+    let Some(world_position) = cursor_world_position(q_window.single(), camera, camera_transform)
+    else {
+        return;
+    };
+    let Some(root) = body_at_point(&rapier_context, &parts, world_position) else {
+        return;
+    };
+    if !ragdolls.contains(root) {
+        return;
+    }
+    if active_query.contains(root) {
+        commands.entity(root).remove::<ActiveRagdoll>();
+    } else {
+        commands.entity(root).insert(ActiveRagdoll::default());
+    }
+}
@@ -0,0 +1,366 @@
+use bevy::utils::HashSet;
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_egui::{egui, EguiContexts};
+use bevy_prototype_lyon::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::context_menu::body_at_point;
+use crate::joints::BreakableJoint;
+use crate::matter::{stroke_for, Matter};
+use crate::ragdoll::RagdollPart;
+use crate::snapping::Snapping;
+use crate::{cursor_world_position, EguiUnfocusedSystemSet, MainCamera, Tool, Tools};
+
+// in front of bodies so the ends show over what they're tied to
+const ROPE_Z: f32 = 0.1;
+
+/// What the rope tool makes next
+#[derive(Resource)]
+pub struct RopeSettings {
+    pub segment_length: f32,
+    pub thickness: f32,
+    /// kg per segment
+    pub segment_mass: f32,
+    /// newtons, a joint pulled harder than this snaps
+    pub break_force: Option<f32>,
+    pub color: Color,
+}
+
+impl Default for RopeSettings {
+    fn default() -> Self {
+        Self {
+            segment_length: 4.,
+            thickness: 1.,
+            segment_mass: 0.1,
+            break_force: None,
+            color: Color::rgb(0.6, 0.45, 0.3),
+        }
+    }
+}
+
+/// A line of small bodies pinned end to end, drawn as one curve through them
+#[derive(Component)]
+pub struct Rope {
+    segments: Vec<Entity>,
+    /// one per gap, from the start to the end: the entity holding the joint and the body it's
+    /// pinned to. gap `i` joins segment `i - 1` and segment `i`
+    links: Vec<(Entity, Entity)>,
+    half_length: f32,
+}
+
+/// Segments, and the fixed bodies holding ends tied to nothing
+#[derive(Component)]
+pub struct RopePart {
+    pub rope: Entity,
+}
+
+/// Where the rope being dragged out starts
+#[derive(Resource, Default)]
+struct RopeDraft {
+    start: Option<Vec2>,
+    end: Vec2,
+}
+
+pub struct RopePlugin;
+
+impl Plugin for RopePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RopeSettings>()
+            .init_resource::<RopeDraft>()
+            .add_systems(Update, draw_rope.in_set(EguiUnfocusedSystemSet))
+            .add_systems(
+                Update,
+                (rope_ui, draw_rope_draft.after(draw_rope), clean_up_ropes),
+            )
+            .add_systems(
+                PostUpdate,
+                update_rope_paths
+                    .after(PhysicsSet::Writeback)
+                    .before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+// drag with the rope tool from one point to another, ends on bodies get tied to them
+fn draw_rope(
+    mut commands: Commands,
+    buttons: Res<Input<MouseButton>>,
+    tool_res: Res<Tools>,
+    snapping: Res<Snapping>,
+    settings: Res<RopeSettings>,
+    rapier_context: Res<RapierContext>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform, &OrthographicProjection), With<MainCamera>>,
+    parts: Query<&RagdollPart>,
+    bodies: Query<(&GlobalTransform, Option<&ImpulseJoint>), With<RigidBody>>,
+    mut draft: ResMut<RopeDraft>,
+) {
+    if tool_res.current_tool != Tool::Rope {
+        draft.start = None;
+        return;
+    }
+    let (camera, camera_transform, projection) = camera_query.single();
+    let Some(world_position) = cursor_world_position(q_window.single(), camera, camera_transform)
+    else {
+        return;
+    };
+    let point = snapping.snap_point(
+        world_position,
+        projection.scale,
+        &rapier_context,
+        QueryFilter::default(),
+    );
+
+    if buttons.just_pressed(MouseButton::Left) {
+        draft.start = Some(point);
+    }
+    draft.end = point;
+    if !buttons.just_released(MouseButton::Left) {
+        return;
+    }
+    let Some(start) = draft.start.take() else {
+        return;
+    };
+    if start.distance(point) < settings.segment_length / 2. {
+        return;
+    }
+
+    let rope = commands.spawn_empty().id();
+    // (body, anchor in its local space, whether it already holds a joint)
+    let mut end_at = |point: Vec2| -> (Entity, Vec2, bool) {
+        if let Some(body) = body_at_point(&rapier_context, &parts, point) {
+            if let Ok((transform, joint)) = bodies.get(body) {
+                let anchor = transform
+                    .affine()
+                    .inverse()
+                    .transform_point3(point.extend(0.))
+                    .truncate();
+                return (body, anchor, joint.is_some());
+            }
+        }
+        // nothing there (or something without a rigid body, like the ground), pin it in place
+        let anchor = commands
+            .spawn((
+                SpatialBundle::from_transform(Transform::from_translation(point.extend(ROPE_Z))),
+                Matter::ellipse(
+                    Vec2::splat(settings.thickness * 2.),
+                    stroke_for(settings.color),
+                ),
+                RigidBody::Fixed,
+                RopePart { rope },
+            ))
+            .id();
+        (anchor, Vec2::ZERO, false)
+    };
+    let mut ends = [(start, end_at(start)), (point, end_at(point))];
+
+    // the last joint goes on the body at the end, and each body can only hold one
+    let [(_, (_, _, start_taken)), (_, (_, _, end_taken))] = ends;
+    if end_taken {
+        if start_taken {
+            warn!("both ends of the rope already have a joint");
+            commands.entity(rope).despawn();
+            return;
+        }
+        ends.swap(0, 1);
+    }
+    let [(start_point, (start_body, start_anchor, _)), (end_point, (end_body, end_anchor, _))] =
+        ends;
+
+    let delta = end_point - start_point;
+    let count = (delta.length() / settings.segment_length).ceil().max(1.) as usize;
+    let half_length = delta.length() / count as f32 / 2.;
+    let direction = delta.normalize();
+    let rotation = Quat::from_rotation_z(direction.y.atan2(direction.x));
+    let pin = |anchor1: Vec2, anchor2: Vec2| {
+        let mut joint: GenericJoint = RevoluteJointBuilder::new()
+            .local_anchor1(anchor1)
+            .local_anchor2(anchor2)
+            .build()
+            .into();
+        // neighbours overlap a little at the pins
+        joint.set_contacts_enabled(false);
+        joint
+    };
+
+    let breakable = settings.break_force.map(|max_force| BreakableJoint {
+        max_force: Some(max_force),
+        max_torque: None,
+    });
+    let mut segments = Vec::with_capacity(count);
+    let mut links = Vec::with_capacity(count + 1);
+    let mut previous = (start_body, start_anchor);
+    for i in 0..count {
+        let center = start_point + direction * half_length * (2. * i as f32 + 1.);
+        let segment = commands
+            .spawn((
+                TransformBundle::from_transform(
+                    Transform::from_translation(center.extend(0.)).with_rotation(rotation),
+                ),
+                RigidBody::Dynamic,
+                Collider::cuboid(half_length, settings.thickness / 2.),
+                ColliderMassProperties::Mass(settings.segment_mass),
+                ImpulseJoint::new(previous.0, pin(previous.1, Vec2::new(-half_length, 0.))),
+                RopePart { rope },
+            ))
+            .id();
+        if let Some(breakable) = breakable.clone() {
+            commands.entity(segment).insert(breakable);
+        }
+        links.push((segment, previous.0));
+        segments.push(segment);
+        previous = (segment, Vec2::new(half_length, 0.));
+    }
+    commands
+        .entity(end_body)
+        .insert(ImpulseJoint::new(previous.0, pin(previous.1, end_anchor)));
+    if let Some(breakable) = breakable {
+        commands.entity(end_body).insert(breakable);
+    }
+    links.push((end_body, previous.0));
+
+    commands.entity(rope).insert((
+        ShapeBundle {
+            spatial: SpatialBundle::from_transform(Transform::from_xyz(0., 0., ROPE_Z)),
+            ..default()
+        },
+        Stroke {
+            color: settings.color,
+            options: StrokeOptions::default()
+                .with_line_width(settings.thickness)
+                .with_line_cap(LineCap::Round)
+                .with_line_join(LineJoin::Round),
+        },
+        Rope {
+            segments,
+            links,
+            half_length,
+        },
+    ));
+}
+
+fn draw_rope_draft(draft: Res<RopeDraft>, settings: Res<RopeSettings>, mut gizmos: Gizmos) {
+    if let Some(start) = draft.start {
+        gizmos.line_2d(start, draft.end, settings.color);
+    }
+}
+
+// deleting a rope takes its segments and anchors with it, and a rope with no segments left goes too
+fn clean_up_ropes(
+    mut commands: Commands,
+    mut removed: RemovedComponents<Rope>,
+    ropes: Query<(Entity, &Rope)>,
+    rope_parts: Query<(Entity, &RopePart)>,
+    joints: Query<(Entity, &ImpulseJoint), Without<RopePart>>,
+) {
+    let removed: HashSet<Entity> = removed.read().collect();
+    if !removed.is_empty() {
+        let gone: HashSet<Entity> = rope_parts
+            .iter()
+            .filter(|(_, part)| removed.contains(&part.rope))
+            .map(|(entity, _)| entity)
+            .collect();
+        for entity in gone.iter() {
+            commands.entity(*entity).despawn_recursive();
+        }
+        // the joint tying the far end on lives on whatever it was tied to
+        for (entity, joint) in joints.iter() {
+            if gone.contains(&joint.parent) {
+                commands
+                    .entity(entity)
+                    .remove::<(ImpulseJoint, BreakableJoint)>();
+            }
+        }
+    }
+    for (entity, rope) in ropes.iter() {
+        if !rope
+            .segments
+            .iter()
+            .any(|segment| rope_parts.contains(*segment))
+        {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+// one smooth curve through the pins of every unbroken run of segments
+fn update_rope_paths(
+    mut ropes: Query<(&Rope, &mut Path)>,
+    segments: Query<&Transform, With<RopePart>>,
+    joints: Query<&ImpulseJoint>,
+) {
+    for (rope, mut path) in ropes.iter_mut() {
+        let mut runs: Vec<Vec<Vec2>> = Vec::new();
+        let mut current: Vec<Vec2> = Vec::new();
+        for (i, segment) in rope.segments.iter().enumerate() {
+            let Ok(transform) = segments.get(*segment) else {
+                runs.push(std::mem::take(&mut current));
+                continue;
+            };
+            let end = |x: f32| transform.transform_point(Vec3::new(x, 0., 0.)).truncate();
+            let (holder, parent) = rope.links[i];
+            let pinned = joints.get(holder).is_ok_and(|joint| joint.parent == parent);
+            match current.last_mut() {
+                // the pin lets the ends drift apart a little, meet them in the middle
+                Some(last) if pinned => *last = (*last + end(-rope.half_length)) / 2.,
+                _ => {
+                    runs.push(std::mem::take(&mut current));
+                    current.push(end(-rope.half_length));
+                }
+            }
+            current.push(end(rope.half_length));
+        }
+        runs.push(current);
+
+        let mut builder = PathBuilder::new();
+        for run in runs.iter().filter(|run| run.len() >= 2) {
+            builder.move_to(run[0]);
+            // catmull-rom through the points, as cubic beziers
+            for i in 0..run.len() - 1 {
+                let before = run[i.saturating_sub(1)];
+                let from = run[i];
+                let to = run[i + 1];
+                let after = run[(i + 2).min(run.len() - 1)];
+                builder.cubic_bezier_to(from + (to - before) / 6., to - (after - from) / 6., to);
+            }
+        }
+        *path = builder.build();
+    }
+}
+
+fn rope_ui(mut contexts: EguiContexts, tool_res: Res<Tools>, mut settings: ResMut<RopeSettings>) {
+    if tool_res.current_tool != Tool::Rope {
+        return;
+    }
+    egui::Window::new("Rope").show(contexts.ctx_mut(), |ui| {
+        ui.label("Drag between two points. Ends on a body get tied to it.");
+        ui.add(egui::Slider::new(&mut settings.segment_length, 1.0..=20.).text("Segment length"));
+        ui.add(egui::Slider::new(&mut settings.thickness, 0.2..=5.).text("Thickness"));
+        ui.add(
+            egui::Slider::new(&mut settings.segment_mass, 0.01..=5.)
+                .logarithmic(true)
+                .text("Segment mass (kg)"),
+        );
+        ui.horizontal(|ui| {
+            let mut breakable = settings.break_force.is_some();
+            if ui.checkbox(&mut breakable, "Breakable").changed() {
+                settings.break_force = breakable.then_some(200.);
+            }
+            if let Some(break_force) = settings.break_force.as_mut() {
+                ui.add(
+                    egui::DragValue::new(break_force)
+                        .speed(10.)
+                        .clamp_range(0.0..=f32::MAX)
+                        .suffix(" N"),
+                );
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Color");
+            let mut color = settings.color.as_rgba_f32();
+            if ui.color_edit_button_rgba_unmultiplied(&mut color).changed() {
+                settings.color = Color::rgba(color[0], color[1], color[2], color[3]);
+            }
+        });
+    });
+}
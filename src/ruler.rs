@@ -0,0 +1,123 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_egui::{egui, EguiContexts};
+use bevy_rapier2d::prelude::*;
+
+use crate::snapping::Snapping;
+use crate::{cursor_world_position, EguiUnfocusedSystemSet, MainCamera, Tool, Tools};
+
+/// The last thing measured with the ruler tool
+#[derive(Resource, Default)]
+struct Ruler {
+    start: Option<Vec2>,
+    end: Vec2,
+    /// mouse is still held down
+    measuring: bool,
+}
+
+impl Ruler {
+    fn distance(&self, start: Vec2) -> f32 {
+        start.distance(self.end)
+    }
+
+    /// degrees counter-clockwise from the x axis
+    fn angle(&self, start: Vec2) -> f32 {
+        let delta = self.end - start;
+        delta.y.atan2(delta.x).to_degrees()
+    }
+}
+
+pub struct RulerPlugin;
+
+impl Plugin for RulerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Ruler>()
+            .add_systems(Update, measure.in_set(EguiUnfocusedSystemSet))
+            .add_systems(Update, draw_ruler.after(measure));
+    }
+}
+
+// drag with the ruler tool to measure, the line stays until the next drag
+fn measure(
+    buttons: Res<Input<MouseButton>>,
+    tool_res: Res<Tools>,
+    snapping: Res<Snapping>,
+    rapier_context: Res<RapierContext>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform, &OrthographicProjection), With<MainCamera>>,
+    mut ruler: ResMut<Ruler>,
+) {
+    if tool_res.current_tool != Tool::Ruler {
+        ruler.start = None;
+        return;
+    }
+    let (camera, camera_transform, projection) = camera_query.single();
+    let Some(world_position) = cursor_world_position(q_window.single(), camera, camera_transform)
+    else {
+        return;
+    };
+    let point = snapping.snap_point(
+        world_position,
+        projection.scale,
+        &rapier_context,
+        QueryFilter::default(),
+    );
+
+    if buttons.just_pressed(MouseButton::Left) {
+        ruler.start = Some(point);
+        ruler.measuring = true;
+    }
+    if buttons.just_released(MouseButton::Left) {
+        ruler.measuring = false;
+    }
+    if ruler.measuring {
+        if let Some(start) = ruler.start {
+            // keep the length but round the direction when angle snapping
+            let delta = point - start;
+            let angle = snapping.snap_angle(delta.y.atan2(delta.x));
+            ruler.end = start + Vec2::new(angle.cos(), angle.sin()) * delta.length();
+        }
+    }
+}
+
+fn draw_ruler(
+    mut contexts: EguiContexts,
+    ruler: Res<Ruler>,
+    camera_query: Query<&OrthographicProjection, With<MainCamera>>,
+    mut gizmos: Gizmos,
+) {
+    let Some(start) = ruler.start else {
+        return;
+    };
+    const RULER_COLOR: Color = Color::rgb(1., 0.85, 0.3);
+    let scale = camera_query.single().scale;
+    gizmos.line_2d(start, ruler.end, RULER_COLOR);
+    // little ticks at both ends so short measurements are still visible
+    let delta = ruler.end - start;
+    let normal = if delta.length_squared() > 0. {
+        Vec2::new(-delta.y, delta.x).normalize() * 6. * scale
+    } else {
+        Vec2::Y * 6. * scale
+    };
+    for end in [start, ruler.end] {
+        gizmos.line_2d(end - normal, end + normal, RULER_COLOR);
+    }
+
+    let text = format!(
+        "{:.2} units, {:.1}°",
+        ruler.distance(start),
+        ruler.angle(start)
+    );
+    if ruler.measuring {
+        egui::show_tooltip_at_pointer(contexts.ctx_mut(), egui::Id::new("ruler"), |ui| {
+            ui.label(&text);
+        });
+    } else {
+        egui::Area::new("ruler_result")
+            .anchor(egui::Align2::CENTER_BOTTOM, egui::vec2(0., -10.))
+            .show(contexts.ctx_mut(), |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.label(format!("Ruler: {}", text));
+                });
+            });
+    }
+}
@@ -0,0 +1,600 @@
+use std::path::{Path, PathBuf};
+
+use bevy::utils::HashMap;
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use bevy_rapier2d::parry::shape::TypedShape;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::body_material::BodyMaterial;
+use crate::camera::{CameraBookmark, CameraBookmarks};
+use crate::fluid::Fluid;
+use crate::images::ImportedImages;
+use crate::layers::{CollisionLayer, LAYER_COUNT};
+use crate::matter::{Matter, MatterShape, MatterTexture, TextureMode};
+use crate::ragdoll::{spawn_person, ActiveRagdoll, Ragdoll, RagdollPart};
+use crate::rope::Rope;
+use crate::soft_body::SoftBody;
+use crate::water::{spawn_water, WaterRegion};
+use crate::{DrawingCircle, DrawingRectangle, LaserPointer};
+
+/// Everything we write to a `.ron` scene file. Imported images go in a folder next to it.
+#[derive(Serialize, Deserialize, Default)]
+pub struct SceneFile {
+    #[serde(default)]
+    pub bodies: Vec<SavedBody>,
+    #[serde(default)]
+    pub people: Vec<SavedPerson>,
+    /// file names inside the images folder
+    #[serde(default)]
+    pub images: Vec<String>,
+    #[serde(default)]
+    pub bookmarks: Vec<CameraBookmark>,
+    #[serde(default)]
+    pub water: Vec<SavedWater>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SavedBody {
+    pub position: [f32; 2],
+    pub rotation: f32,
+    pub dynamic: bool,
+    pub shape: SavedShape,
+    pub fill: [f32; 4],
+    pub stroke: [f32; 4],
+    pub stroke_width: f32,
+    /// the body is drawn as this image's alpha instead of its collider
+    #[serde(default)]
+    pub image_shape: Option<SavedImageShape>,
+    #[serde(default)]
+    pub texture: Option<SavedTexture>,
+    #[serde(default)]
+    pub linvel: [f32; 2],
+    #[serde(default)]
+    pub angvel: f32,
+    #[serde(default)]
+    pub material: Option<BodyMaterial>,
+    /// index into the collision layers in the settings
+    #[serde(default)]
+    pub layer: usize,
+}
+
+impl SavedBody {
+    /// Snapshot a body. Images are referred to by their imported name.
+    pub fn capture(
+        transform: &Transform,
+        collider: &Collider,
+        matter: &Matter,
+        rigidbody: Option<&RigidBody>,
+        velocity: Option<&Velocity>,
+        material: Option<&BodyMaterial>,
+        imported: &ImportedImages,
+    ) -> Option<Self> {
+        let image_name =
+            |handle: &Handle<Image>| imported.by_handle(handle).map(|image| image.name.clone());
+        let image_shape = match &matter.shape {
+            MatterShape::Image(handle) => image_name(handle).map(|image| SavedImageShape {
+                image,
+                size: matter.size.to_array(),
+            }),
+            _ => None,
+        };
+        let texture = matter.texture.as_ref().and_then(|texture| {
+            Some(SavedTexture {
+                image: image_name(&texture.image)?,
+                mode: texture.mode,
+                tile_size: texture.tile_size,
+            })
+        });
+        let velocity = velocity.copied().unwrap_or_default();
+        Some(SavedBody {
+            position: transform.translation.truncate().to_array(),
+            rotation: transform.rotation.to_euler(EulerRot::XYZ).2,
+            dynamic: rigidbody == Some(&RigidBody::Dynamic),
+            shape: SavedShape::from_collider(collider)?,
+            fill: color_to_array(matter.fill),
+            stroke: color_to_array(matter.stroke),
+            stroke_width: matter.stroke_width,
+            image_shape,
+            texture,
+            linvel: velocity.linvel.to_array(),
+            angvel: velocity.angvel,
+            material: material.copied(),
+            layer: 0,
+        })
+    }
+
+    pub fn spawn(&self, commands: &mut Commands, imported: &ImportedImages) -> Entity {
+        let image_handle = |name: &str| imported.by_name(name).map(|image| image.handle.clone());
+
+        let mut matter = Matter::new(array_to_color(self.fill));
+        matter.stroke = array_to_color(self.stroke);
+        matter.stroke_width = self.stroke_width;
+        if let Some(image_shape) = &self.image_shape {
+            if let Some(handle) = image_handle(&image_shape.image) {
+                matter.shape = MatterShape::Image(handle);
+                matter.size = Vec2::from_array(image_shape.size);
+            }
+        }
+        if let Some(texture) = &self.texture {
+            if let Some(handle) = image_handle(&texture.image) {
+                matter.texture = Some(MatterTexture {
+                    image: handle,
+                    mode: texture.mode,
+                    tile_size: texture.tile_size,
+                });
+            }
+        }
+        let mut ent = commands.spawn((
+            SpatialBundle::from_transform(Transform {
+                translation: Vec2::from_array(self.position).extend(0.),
+                rotation: Quat::from_rotation_z(self.rotation),
+                ..default()
+            }),
+            matter,
+            self.shape.to_collider(),
+        ));
+        if self.dynamic {
+            ent.insert((
+                RigidBody::Dynamic,
+                Velocity {
+                    linvel: Vec2::from_array(self.linvel),
+                    angvel: self.angvel,
+                },
+            ));
+        }
+        if let Some(material) = self.material {
+            ent.insert(material);
+        }
+        // an edited or older scene file can name a layer that doesn't exist
+        if self.layer != 0 {
+            ent.insert(CollisionLayer(self.layer.min(LAYER_COUNT - 1)));
+        }
+        ent.id()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SavedImageShape {
+    pub image: String,
+    pub size: [f32; 2],
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SavedTexture {
+    pub image: String,
+    pub mode: TextureMode,
+    pub tile_size: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SavedPerson {
+    pub position: [f32; 2],
+    pub color: [f32; 4],
+    pub alive: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SavedWater {
+    pub center: [f32; 2],
+    pub size: [f32; 2],
+    pub density: f32,
+    pub linear_drag: f32,
+    pub angular_drag: f32,
+}
+
+impl SavedWater {
+    fn capture(transform: &Transform, region: &WaterRegion) -> Self {
+        Self {
+            center: transform.translation.truncate().to_array(),
+            size: region.size.to_array(),
+            density: region.density,
+            linear_drag: region.linear_drag,
+            angular_drag: region.angular_drag,
+        }
+    }
+
+    fn spawn(&self, commands: &mut Commands) {
+        spawn_water(
+            commands,
+            Vec2::from_array(self.center),
+            WaterRegion {
+                size: Vec2::from_array(self.size),
+                density: self.density,
+                linear_drag: self.linear_drag,
+                angular_drag: self.angular_drag,
+            },
+        );
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub enum SavedShape {
+    Ball {
+        radius: f32,
+    },
+    Cuboid {
+        half_extents: [f32; 2],
+    },
+    RoundCuboid {
+        half_extents: [f32; 2],
+        border_radius: f32,
+    },
+    Capsule {
+        a: [f32; 2],
+        b: [f32; 2],
+        radius: f32,
+    },
+    Polygon {
+        points: Vec<[f32; 2]>,
+    },
+    Compound(Vec<SavedShapePart>),
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SavedShapePart {
+    pub offset: [f32; 2],
+    pub rotation: f32,
+    pub shape: SavedShape,
+}
+
+impl SavedShape {
+    pub fn from_collider(collider: &Collider) -> Option<Self> {
+        Self::from_typed(collider.raw.as_typed_shape())
+    }
+
+    fn from_typed(shape: TypedShape) -> Option<Self> {
+        Some(match shape {
+            TypedShape::Ball(ball) => SavedShape::Ball {
+                radius: ball.radius,
+            },
+            TypedShape::Cuboid(cuboid) => SavedShape::Cuboid {
+                half_extents: [cuboid.half_extents.x, cuboid.half_extents.y],
+            },
+            TypedShape::RoundCuboid(round) => SavedShape::RoundCuboid {
+                half_extents: [
+                    round.inner_shape.half_extents.x,
+                    round.inner_shape.half_extents.y,
+                ],
+                border_radius: round.border_radius,
+            },
+            TypedShape::Capsule(capsule) => SavedShape::Capsule {
+                a: [capsule.segment.a.x, capsule.segment.a.y],
+                b: [capsule.segment.b.x, capsule.segment.b.y],
+                radius: capsule.radius,
+            },
+            TypedShape::ConvexPolygon(polygon) => SavedShape::Polygon {
+                points: polygon.points().iter().map(|p| [p.x, p.y]).collect(),
+            },
+            TypedShape::Compound(compound) => SavedShape::Compound(
+                compound
+                    .shapes()
+                    .iter()
+                    .filter_map(|(isometry, shape)| {
+                        Some(SavedShapePart {
+                            offset: [isometry.translation.vector.x, isometry.translation.vector.y],
+                            rotation: isometry.rotation.angle(),
+                            shape: Self::from_typed(shape.as_typed_shape())?,
+                        })
+                    })
+                    .collect(),
+            ),
+            _ => return None,
+        })
+    }
+
+    /// Same shape stretched by `factor` along each local axis. Round things can't stretch
+    /// unevenly, so they use the average (or smallest, for thickness).
+    pub fn scaled(&self, factor: Vec2) -> SavedShape {
+        let scale = |p: &[f32; 2]| [p[0] * factor.x, p[1] * factor.y];
+        let average = (factor.x.abs() + factor.y.abs()) / 2.;
+        let smallest = factor.x.abs().min(factor.y.abs());
+        match self {
+            SavedShape::Ball { radius } => SavedShape::Ball {
+                radius: radius * average,
+            },
+            SavedShape::Cuboid { half_extents } => SavedShape::Cuboid {
+                half_extents: scale(half_extents).map(f32::abs),
+            },
+            SavedShape::RoundCuboid {
+                half_extents,
+                border_radius,
+            } => SavedShape::RoundCuboid {
+                half_extents: scale(half_extents).map(f32::abs),
+                border_radius: border_radius * smallest,
+            },
+            SavedShape::Capsule { a, b, radius } => SavedShape::Capsule {
+                a: scale(a),
+                b: scale(b),
+                radius: radius * smallest,
+            },
+            SavedShape::Polygon { points } => SavedShape::Polygon {
+                points: points.iter().map(scale).collect(),
+            },
+            SavedShape::Compound(parts) => SavedShape::Compound(
+                parts
+                    .iter()
+                    .map(|part| SavedShapePart {
+                        offset: scale(&part.offset),
+                        rotation: part.rotation,
+                        shape: part.shape.scaled(factor),
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    pub fn to_collider(&self) -> Collider {
+        match self {
+            SavedShape::Ball { radius } => Collider::ball(*radius),
+            SavedShape::Cuboid { half_extents } => {
+                Collider::cuboid(half_extents[0], half_extents[1])
+            }
+            SavedShape::RoundCuboid {
+                half_extents,
+                border_radius,
+            } => Collider::round_cuboid(half_extents[0], half_extents[1], *border_radius),
+            SavedShape::Capsule { a, b, radius } => {
+                Collider::capsule(Vec2::from_array(*a), Vec2::from_array(*b), *radius)
+            }
+            SavedShape::Polygon { points } => {
+                let points: Vec<Vec2> = points.iter().map(|p| Vec2::from_array(*p)).collect();
+                Collider::convex_hull(&points).unwrap_or_else(|| Collider::ball(1.))
+            }
+            SavedShape::Compound(parts) => Collider::compound(
+                parts
+                    .iter()
+                    .map(|part| {
+                        (
+                            Vec2::from_array(part.offset),
+                            part.rotation,
+                            part.shape.to_collider(),
+                        )
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+pub fn color_to_array(color: Color) -> [f32; 4] {
+    [color.r(), color.g(), color.b(), color.a()]
+}
+
+pub fn array_to_color(color: [f32; 4]) -> Color {
+    Color::rgba(color[0], color[1], color[2], color[3])
+}
+
+/// Folder the scene's images are copied into, next to the scene file
+pub fn images_folder(scene_path: &Path) -> PathBuf {
+    let stem = scene_path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("scene");
+    scene_path.with_file_name(format!("{}_images", stem))
+}
+
+#[derive(Event)]
+pub enum SceneCommand {
+    Save(PathBuf),
+    Load(PathBuf),
+}
+
+#[derive(Resource)]
+struct SceneUiState {
+    path: String,
+    status: Option<String>,
+}
+
+impl Default for SceneUiState {
+    fn default() -> Self {
+        Self {
+            path: "scene.ron".to_string(),
+            status: None,
+        }
+    }
+}
+
+pub struct ScenePlugin;
+
+impl Plugin for ScenePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SceneCommand>()
+            .init_resource::<SceneUiState>()
+            .add_systems(Update, (scene_ui, save_scene, load_scene).chain());
+    }
+}
+
+fn scene_ui(
+    mut contexts: EguiContexts,
+    mut ui_state: ResMut<SceneUiState>,
+    mut scene_commands: EventWriter<SceneCommand>,
+) {
+    egui::Window::new("Scene")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            #[cfg(target_arch = "wasm32")]
+            ui.label("Saving scenes isn't supported in the browser yet.");
+            #[cfg(not(target_arch = "wasm32"))]
+            {
+                ui.text_edit_singleline(&mut ui_state.path);
+                ui.horizontal(|ui| {
+                    if ui.button("Save").clicked() {
+                        scene_commands
+                            .send(SceneCommand::Save(PathBuf::from(ui_state.path.trim())));
+                    }
+                    if ui.button("Load").clicked() {
+                        scene_commands
+                            .send(SceneCommand::Load(PathBuf::from(ui_state.path.trim())));
+                    }
+                });
+            }
+            if let Some(status) = &ui_state.status {
+                ui.label(status);
+            }
+        });
+}
+
+fn save_scene(
+    mut scene_commands: EventReader<SceneCommand>,
+    mut ui_state: ResMut<SceneUiState>,
+    imported: Res<ImportedImages>,
+    bookmarks: Res<CameraBookmarks>,
+    body_query: Query<
+        (
+            &Transform,
+            &Collider,
+            &Matter,
+            Option<&RigidBody>,
+            Option<&Velocity>,
+            Option<&BodyMaterial>,
+            Option<&CollisionLayer>,
+        ),
+        (
+            Without<RagdollPart>,
+            Without<Ragdoll>,
+            Without<LaserPointer>,
+            Without<DrawingRectangle>,
+            Without<DrawingCircle>,
+        ),
+    >,
+    people_query: Query<(&Transform, &Matter, Option<&ActiveRagdoll>), With<Ragdoll>>,
+    water_query: Query<(&Transform, &WaterRegion)>,
+) {
+    for command in scene_commands.read() {
+        let SceneCommand::Save(path) = command else {
+            continue;
+        };
+        let mut scene = SceneFile {
+            bookmarks: bookmarks.bookmarks.clone(),
+            ..default()
+        };
+        for (transform, collider, matter, rigidbody, velocity, material, layer) in body_query.iter()
+        {
+            if let Some(mut body) = SavedBody::capture(
+                transform, collider, matter, rigidbody, velocity, material, &imported,
+            ) {
+                body.layer = layer.map_or(0, |layer| layer.0);
+                scene.bodies.push(body);
+            }
+        }
+        for (transform, matter, active) in people_query.iter() {
+            scene.people.push(SavedPerson {
+                position: transform.translation.truncate().to_array(),
+                color: color_to_array(matter.fill),
+                alive: active.is_some(),
+            });
+        }
+        for (transform, region) in water_query.iter() {
+            scene.water.push(SavedWater::capture(transform, region));
+        }
+
+        ui_state.status = Some(match write_scene(path, scene, &imported) {
+            Ok(()) => format!("Saved {}", path.display()),
+            Err(err) => err,
+        });
+    }
+}
+
+fn write_scene(path: &Path, mut scene: SceneFile, imported: &ImportedImages) -> Result<(), String> {
+    if !imported.images.is_empty() {
+        let folder = images_folder(path);
+        std::fs::create_dir_all(&folder)
+            .map_err(|err| format!("Couldn't make {}: {}", folder.display(), err))?;
+        for image in imported.images.iter() {
+            let image_path = folder.join(&image.name);
+            std::fs::write(&image_path, &image.bytes)
+                .map_err(|err| format!("Couldn't write {}: {}", image_path.display(), err))?;
+            scene.images.push(image.name.clone());
+        }
+    }
+
+    let text = ron::ser::to_string_pretty(&scene, ron::ser::PrettyConfig::default())
+        .map_err(|err| format!("Couldn't save scene: {}", err))?;
+    std::fs::write(path, text).map_err(|err| format!("Couldn't write {}: {}", path.display(), err))
+}
+
+fn load_scene(
+    mut commands: Commands,
+    mut scene_commands: EventReader<SceneCommand>,
+    mut ui_state: ResMut<SceneUiState>,
+    asset_server: Res<AssetServer>,
+    mut imported: ResMut<ImportedImages>,
+    mut bookmarks: ResMut<CameraBookmarks>,
+    mut images: ResMut<Assets<Image>>,
+    existing: Query<Entity, (With<Matter>, Without<LaserPointer>)>,
+    // soft body nodes and rope segments go with whatever they're part of
+    other_existing: Query<Entity, Or<(With<WaterRegion>, With<SoftBody>, With<Rope>)>>,
+    mut fluid: ResMut<Fluid>,
+) {
+    for command in scene_commands.read() {
+        let SceneCommand::Load(path) = command else {
+            continue;
+        };
+        let mut scene = match std::fs::read_to_string(path)
+            .map_err(|err| err.to_string())
+            .and_then(|text| ron::from_str::<SceneFile>(&text).map_err(|err| err.to_string()))
+        {
+            Ok(scene) => scene,
+            Err(err) => {
+                ui_state.status = Some(format!("Couldn't load {}: {}", path.display(), err));
+                continue;
+            }
+        };
+
+        for entity in existing.iter().chain(other_existing.iter()) {
+            commands.entity(entity).despawn_recursive();
+        }
+        fluid.clear();
+
+        // an image we already have under the same name but with other pixels comes in renamed
+        let folder = images_folder(path);
+        let mut renamed = HashMap::new();
+        for name in scene.images.iter() {
+            match imported.import_file(&mut images, &folder.join(name)) {
+                Ok(handle) => {
+                    if let Some(image) = imported.by_handle(&handle) {
+                        if image.name != *name {
+                            renamed.insert(name.clone(), image.name.clone());
+                        }
+                    }
+                }
+                Err(err) => warn!("{}", err),
+            }
+        }
+        for body in scene.bodies.iter_mut() {
+            let names = body
+                .image_shape
+                .iter_mut()
+                .map(|shape| &mut shape.image)
+                .chain(body.texture.iter_mut().map(|texture| &mut texture.image));
+            for name in names {
+                if let Some(new_name) = renamed.get(name) {
+                    *name = new_name.clone();
+                }
+            }
+        }
+        for body in scene.bodies.iter() {
+            body.spawn(&mut commands, &imported);
+        }
+
+        for person in scene.people.iter() {
+            spawn_person(
+                &mut commands,
+                &asset_server,
+                array_to_color(person.color),
+                Vec2::from_array(person.position),
+                person.alive,
+            );
+        }
+
+        for water in scene.water.iter() {
+            water.spawn(&mut commands);
+        }
+
+        bookmarks.bookmarks = scene.bookmarks;
+        ui_state.status = Some(format!("Loaded {}", path.display()));
+    }
+}
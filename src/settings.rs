@@ -0,0 +1,542 @@
+use bevy::app::AppExit;
+use bevy::ecs::system::SystemParam;
+use bevy::window::{PresentMode, PrimaryWindow};
+use bevy::{prelude::*, render::view::Msaa};
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+
+use crate::body_material::BodyMaterial;
+use crate::camera::CameraSettings;
+use crate::forces::ThrusterRebinding;
+use crate::graphics::GraphicsSettings;
+use crate::layers::CollisionLayers;
+use crate::motor::MotorRebinding;
+use crate::ragdoll::RagdollSettings;
+use crate::snapping::Snapping;
+use crate::sound::SoundSettings;
+use crate::storage;
+use crate::theme::Theme;
+use crate::UIState;
+
+/// Everything we remember between launches. The live values are in their own resources,
+/// this is the copy that gets written to disk whenever one of them changes.
+#[derive(Resource, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub closed_welcome: bool,
+    pub key_bindings: KeyBindings,
+    pub theme: Theme,
+    pub present_mode: PresentModeSetting,
+    pub msaa: MsaaSetting,
+    pub graphics: GraphicsSettings,
+    /// material given to newly drawn bodies
+    pub default_material: BodyMaterial,
+    pub snapping: Snapping,
+    pub camera: CameraSettings,
+    pub ragdolls: RagdollSettings,
+    pub collision_layers: CollisionLayers,
+    pub sound: SoundSettings,
+}
+
+// wait this long after the last change before writing, so dragging a slider doesn't rewrite
+// the file every frame
+const SAVE_DELAY: f32 = 1.;
+
+/// When the settings last changed, if that hasn't been written out yet
+#[derive(Default)]
+struct UnsavedSince(Option<f32>);
+
+/// `PresentMode` we can save
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PresentModeSetting {
+    AutoVsync,
+    AutoNoVsync,
+    Fifo,
+    Immediate,
+    Mailbox,
+}
+
+impl Default for PresentModeSetting {
+    fn default() -> Self {
+        // wasm32-unknown-unknown doesn't support Immediate
+        if cfg!(target_arch = "wasm32") {
+            PresentModeSetting::AutoVsync
+        } else {
+            PresentModeSetting::Immediate
+        }
+    }
+}
+
+impl From<PresentModeSetting> for PresentMode {
+    fn from(setting: PresentModeSetting) -> Self {
+        match setting {
+            PresentModeSetting::AutoVsync => PresentMode::AutoVsync,
+            PresentModeSetting::AutoNoVsync => PresentMode::AutoNoVsync,
+            PresentModeSetting::Fifo => PresentMode::Fifo,
+            PresentModeSetting::Immediate => PresentMode::Immediate,
+            PresentModeSetting::Mailbox => PresentMode::Mailbox,
+        }
+    }
+}
+
+impl From<PresentMode> for PresentModeSetting {
+    fn from(mode: PresentMode) -> Self {
+        match mode {
+            PresentMode::AutoVsync => PresentModeSetting::AutoVsync,
+            PresentMode::AutoNoVsync => PresentModeSetting::AutoNoVsync,
+            PresentMode::Fifo => PresentModeSetting::Fifo,
+            PresentMode::Immediate => PresentModeSetting::Immediate,
+            PresentMode::Mailbox => PresentModeSetting::Mailbox,
+        }
+    }
+}
+
+/// `Msaa` we can save
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MsaaSetting {
+    Off,
+    Sample2,
+    #[default]
+    Sample4,
+    Sample8,
+}
+
+impl From<MsaaSetting> for Msaa {
+    fn from(setting: MsaaSetting) -> Self {
+        match setting {
+            MsaaSetting::Off => Msaa::Off,
+            MsaaSetting::Sample2 => Msaa::Sample2,
+            MsaaSetting::Sample4 => Msaa::Sample4,
+            MsaaSetting::Sample8 => Msaa::Sample8,
+        }
+    }
+}
+
+impl From<Msaa> for MsaaSetting {
+    fn from(msaa: Msaa) -> Self {
+        match msaa {
+            Msaa::Off => MsaaSetting::Off,
+            Msaa::Sample2 => MsaaSetting::Sample2,
+            Msaa::Sample4 => MsaaSetting::Sample4,
+            Msaa::Sample8 => MsaaSetting::Sample8,
+        }
+    }
+}
+
+/// Keys for everything that isn't hardwired. Escape always lets go/cancels.
+#[derive(Resource, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyBindings {
+    #[serde(with = "key_name")]
+    pub toggle_physics: KeyCode,
+    #[serde(with = "key_name")]
+    pub drag_tool: KeyCode,
+    #[serde(with = "key_name")]
+    pub rectangle_tool: KeyCode,
+    #[serde(with = "key_name")]
+    pub spawn_person: KeyCode,
+    #[serde(with = "key_name")]
+    pub spawn_red_person: KeyCode,
+    #[serde(with = "key_name")]
+    pub vertical_box: KeyCode,
+    #[serde(with = "key_name")]
+    pub horizontal_box: KeyCode,
+    #[serde(with = "key_name")]
+    pub toggle_alive: KeyCode,
+    #[serde(with = "key_name")]
+    pub possess: KeyCode,
+    #[serde(with = "key_name")]
+    pub move_left: KeyCode,
+    #[serde(with = "key_name")]
+    pub move_right: KeyCode,
+    #[serde(with = "key_name")]
+    pub jump: KeyCode,
+    #[serde(with = "key_name")]
+    pub move_down: KeyCode,
+    #[serde(with = "key_name")]
+    pub focus_selected: KeyCode,
+    #[serde(with = "key_name")]
+    pub follow_selected: KeyCode,
+    #[serde(with = "key_name")]
+    pub zoom_to_fit: KeyCode,
+    #[serde(with = "key_name")]
+    pub reset_camera: KeyCode,
+    #[serde(with = "key_name")]
+    pub toggle_fullscreen: KeyCode,
+    #[serde(with = "key_name")]
+    pub toggle_performance: KeyCode,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            toggle_physics: KeyCode::Space,
+            drag_tool: KeyCode::Key1,
+            rectangle_tool: KeyCode::Key2,
+            spawn_person: KeyCode::P,
+            spawn_red_person: KeyCode::M,
+            vertical_box: KeyCode::V,
+            horizontal_box: KeyCode::H,
+            toggle_alive: KeyCode::L,
+            possess: KeyCode::F,
+            move_left: KeyCode::A,
+            move_right: KeyCode::D,
+            jump: KeyCode::W,
+            move_down: KeyCode::S,
+            focus_selected: KeyCode::C,
+            follow_selected: KeyCode::G,
+            zoom_to_fit: KeyCode::Z,
+            reset_camera: KeyCode::Home,
+            toggle_fullscreen: KeyCode::F11,
+            toggle_performance: KeyCode::F3,
+        }
+    }
+}
+
+impl KeyBindings {
+    /// (label, binding) for the settings window
+    fn entries_mut(&mut self) -> [(&'static str, &mut KeyCode); 19] {
+        [
+            ("Pause/resume physics", &mut self.toggle_physics),
+            ("Drag tool", &mut self.drag_tool),
+            ("Rectangle tool", &mut self.rectangle_tool),
+            ("Spawn person", &mut self.spawn_person),
+            ("Spawn red person", &mut self.spawn_red_person),
+            ("Vertical box", &mut self.vertical_box),
+            ("Horizontal box", &mut self.horizontal_box),
+            ("Toggle people spawning alive", &mut self.toggle_alive),
+            ("Possess", &mut self.possess),
+            ("Move left", &mut self.move_left),
+            ("Move right", &mut self.move_right),
+            ("Jump", &mut self.jump),
+            ("Drop down", &mut self.move_down),
+            ("Focus selected", &mut self.focus_selected),
+            ("Follow selected", &mut self.follow_selected),
+            ("Zoom to fit", &mut self.zoom_to_fit),
+            ("Reset camera", &mut self.reset_camera),
+            ("Toggle fullscreen", &mut self.toggle_fullscreen),
+            ("Performance overlay", &mut self.toggle_performance),
+        ]
+    }
+}
+
+// `KeyCode` only has serde impls with bevy's "serialize" feature, so we go by the Debug name
+mod key_name {
+    use bevy::prelude::KeyCode;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::BINDABLE_KEYS;
+
+    pub fn serialize<S: Serializer>(key: &KeyCode, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{:?}", key))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<KeyCode, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        BINDABLE_KEYS
+            .iter()
+            .find(|key| format!("{:?}", key) == name)
+            .copied()
+            .ok_or_else(|| serde::de::Error::custom(format!("unknown key {}", name)))
+    }
+}
+
+/// Keys you're allowed to bind things to
+pub const BINDABLE_KEYS: &[KeyCode] = &[
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+    KeyCode::Key0,
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F11,
+    KeyCode::F12,
+    KeyCode::Space,
+    KeyCode::Tab,
+    KeyCode::Return,
+    KeyCode::Back,
+    KeyCode::Delete,
+    KeyCode::Insert,
+    KeyCode::Home,
+    KeyCode::End,
+    KeyCode::PageUp,
+    KeyCode::PageDown,
+    KeyCode::Up,
+    KeyCode::Down,
+    KeyCode::Left,
+    KeyCode::Right,
+    KeyCode::Comma,
+    KeyCode::Period,
+    KeyCode::Slash,
+    KeyCode::Semicolon,
+    KeyCode::Apostrophe,
+    KeyCode::BracketLeft,
+    KeyCode::BracketRight,
+    KeyCode::Minus,
+    KeyCode::Equals,
+    KeyCode::Grave,
+];
+
+/// Escape was pressed while waiting for a key
+pub struct Cancelled;
+
+/// For "press a key" buttons: the first bindable key pressed this frame, if any
+pub fn next_bound_key(keys: &Input<KeyCode>) -> Option<Result<KeyCode, Cancelled>> {
+    if keys.just_pressed(KeyCode::Escape) {
+        return Some(Err(Cancelled));
+    }
+    keys.get_just_pressed()
+        .find(|key| BINDABLE_KEYS.contains(key))
+        .map(|key| Ok(*key))
+}
+
+/// Which binding is waiting for a key press, by its index in `KeyBindings::entries_mut`
+#[derive(Resource, Default)]
+struct Rebinding(Option<usize>);
+
+/// The systems behind "press a key" buttons. Anything else reading keys that a rebind would
+/// swallow (escape, or the key being bound) runs before these and checks `KeyCapture`.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct KeyCaptureSet;
+
+/// Whether some button is waiting for a key press
+#[derive(SystemParam)]
+pub struct KeyCapture<'w> {
+    settings: Res<'w, Rebinding>,
+    motors: Res<'w, MotorRebinding>,
+    thrusters: Res<'w, ThrusterRebinding>,
+}
+
+impl KeyCapture<'_> {
+    pub fn waiting(&self) -> bool {
+        self.settings.0.is_some() || self.motors.waiting() || self.thrusters.waiting()
+    }
+}
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        let settings = storage::load("settings")
+            .and_then(|text| match ron::from_str::<Settings>(&text) {
+                Ok(settings) => Some(settings),
+                Err(err) => {
+                    warn!("Couldn't read settings, using defaults: {}", err);
+                    None
+                }
+            })
+            .unwrap_or_default();
+
+        // everything else uses init_resource, so this has to be added before their plugins
+        app.insert_resource(UIState {
+            closed_welcome: settings.closed_welcome,
+        })
+        .insert_resource(settings.key_bindings.clone())
+        .insert_resource(settings.theme.clone())
+        .insert_resource(Msaa::from(settings.msaa))
+        .insert_resource(settings.graphics.clone())
+        .insert_resource(settings.snapping.clone())
+        .insert_resource(settings.camera.clone())
+        .insert_resource(settings.ragdolls.clone())
+        .insert_resource(settings.collision_layers.clone())
+        .insert_resource(settings.sound.clone())
+        .insert_resource(settings)
+        .init_resource::<Rebinding>()
+        .add_systems(Startup, apply_present_mode)
+        .add_systems(
+            Update,
+            (settings_ui, capture_rebinding.in_set(KeyCaptureSet)).chain(),
+        )
+        .add_systems(Last, save_settings);
+    }
+}
+
+fn apply_present_mode(
+    settings: Res<Settings>,
+    mut q_window: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    if let Ok(mut window) = q_window.get_single_mut() {
+        window.present_mode = settings.present_mode.into();
+    }
+}
+
+fn settings_ui(
+    mut contexts: EguiContexts,
+    mut settings: ResMut<Settings>,
+    mut ui_state: ResMut<UIState>,
+    mut bindings: ResMut<KeyBindings>,
+    mut rebinding: ResMut<Rebinding>,
+) {
+    egui::Window::new("Settings")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            if ui_state.closed_welcome && ui.button("Show the welcome window again").clicked() {
+                ui_state.closed_welcome = false;
+            }
+
+            ui.horizontal(|ui| {
+                ui.label("New bodies are made of");
+                let mut material = settings.default_material;
+                egui::ComboBox::from_id_source("default_material")
+                    .selected_text(material.name())
+                    .show_ui(ui, |ui| {
+                        for option in BodyMaterial::ALL {
+                            ui.selectable_value(&mut material, option, option.name());
+                        }
+                    });
+                if material != settings.default_material {
+                    settings.default_material = material;
+                }
+            });
+
+            ui.separator();
+            ui.label("Key bindings (click one, then press a key, one already in use swaps over)");
+            let waiting = rebinding.0;
+            egui::Grid::new("key_bindings").show(ui, |ui| {
+                // go through a copy so we don't trip change detection every frame
+                let mut shown = bindings.clone();
+                for (i, (label, key)) in shown.entries_mut().into_iter().enumerate() {
+                    ui.label(label);
+                    let text = if waiting == Some(i) {
+                        "Press a key...".to_string()
+                    } else {
+                        format!("{:?}", key)
+                    };
+                    if ui.button(text).clicked() {
+                        rebinding.0 = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+            if ui.button("Reset key bindings").clicked() {
+                *bindings = KeyBindings::default();
+                rebinding.0 = None;
+            }
+        });
+}
+
+// the next bindable key pressed after clicking a binding takes its place
+fn capture_rebinding(
+    keys: Res<Input<KeyCode>>,
+    mut rebinding: ResMut<Rebinding>,
+    mut bindings: ResMut<KeyBindings>,
+) {
+    let Some(index) = rebinding.0 else {
+        return;
+    };
+    let Some(pressed) = next_bound_key(&keys) else {
+        return;
+    };
+    if let Ok(key) = pressed {
+        let mut edited = bindings.clone();
+        let mut entries = edited.entries_mut();
+        if let Some(previous) = entries.get(index).map(|(_, binding)| **binding) {
+            // whatever had this key already swaps over to the old one, so no key does two things
+            for (i, (_, binding)) in entries.iter_mut().enumerate() {
+                if i == index {
+                    **binding = key;
+                } else if **binding == key {
+                    **binding = previous;
+                }
+            }
+        }
+        bindings.set_if_neq(edited);
+    }
+    rebinding.0 = None;
+}
+
+// copy the live values back, and write them out once they've stopped moving or we're quitting
+fn save_settings(
+    time: Res<Time>,
+    mut exit: EventReader<AppExit>,
+    mut unsaved: Local<UnsavedSince>,
+    mut settings: ResMut<Settings>,
+    ui_state: Res<UIState>,
+    bindings: Res<KeyBindings>,
+    theme: Res<Theme>,
+    msaa: Res<Msaa>,
+    graphics: Res<GraphicsSettings>,
+    snapping: Res<Snapping>,
+    camera: Res<CameraSettings>,
+    ragdolls: Res<RagdollSettings>,
+    collision_layers: Res<CollisionLayers>,
+    sound: Res<SoundSettings>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+) {
+    let mut current = settings.clone();
+    current.closed_welcome = ui_state.closed_welcome;
+    current.key_bindings = bindings.clone();
+    current.theme = theme.clone();
+    current.msaa = (*msaa).into();
+    current.graphics = graphics.clone();
+    current.snapping = snapping.clone();
+    current.camera = camera.clone();
+    current.ragdolls = ragdolls.clone();
+    current.collision_layers = collision_layers.clone();
+    current.sound = sound.clone();
+    if let Ok(window) = q_window.get_single() {
+        current.present_mode = window.present_mode.into();
+    }
+    let now = time.elapsed_seconds();
+    settings.set_if_neq(current);
+    // just loaded, nothing new to write
+    if settings.is_changed() && !settings.is_added() {
+        unsaved.0 = Some(now);
+    }
+    let exiting = exit.read().count() > 0;
+    let Some(changed_at) = unsaved.0 else {
+        return;
+    };
+    if !exiting && now - changed_at < SAVE_DELAY {
+        return;
+    }
+    unsaved.0 = None;
+    match ron::ser::to_string_pretty(&*settings, ron::ser::PrettyConfig::default()) {
+        Ok(text) => {
+            if let Err(err) = storage::save("settings", &text) {
+                warn!("{}", err);
+            }
+        }
+        Err(err) => warn!("Couldn't save settings: {}", err),
+    }
+}
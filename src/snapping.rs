@@ -0,0 +1,142 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::MainCamera;
+
+// grid lines try to stay at least this many pixels apart
+const MIN_GRID_PIXELS: f32 = 24.;
+// how close to a body edge (in pixels) the cursor has to be to stick to it
+const EDGE_SNAP_PIXELS: f32 = 10.;
+
+#[derive(Resource, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Snapping {
+    pub show_grid: bool,
+    pub to_grid: bool,
+    pub to_edges: bool,
+    pub to_angle: bool,
+    pub angle_step_degrees: f32,
+}
+
+impl Default for Snapping {
+    fn default() -> Self {
+        Self {
+            show_grid: false,
+            to_grid: false,
+            to_edges: false,
+            to_angle: false,
+            angle_step_degrees: 15.,
+        }
+    }
+}
+
+impl Snapping {
+    /// Snap a world point to nearby body edges, or failing that the grid.
+    /// `scale` is the camera's projection scale, used to keep distances the same on screen.
+    pub fn snap_point(
+        &self,
+        point: Vec2,
+        scale: f32,
+        rapier_context: &RapierContext,
+        filter: QueryFilter,
+    ) -> Vec2 {
+        if self.to_edges {
+            if let Some((_, projection)) = rapier_context.project_point(point, false, filter) {
+                if projection.point.distance(point) < EDGE_SNAP_PIXELS * scale {
+                    return projection.point;
+                }
+            }
+        }
+        if self.to_grid {
+            let spacing = grid_spacing(scale);
+            return (point / spacing).round() * spacing;
+        }
+        point
+    }
+
+    /// Round an angle in radians to the nearest step, if angle snapping is on
+    pub fn snap_angle(&self, angle: f32) -> f32 {
+        if !self.to_angle || self.angle_step_degrees <= 0. {
+            return angle;
+        }
+        let step = self.angle_step_degrees.to_radians();
+        (angle / step).round() * step
+    }
+}
+
+/// World distance between grid lines at this zoom. Always a power of 10 so the numbers stay nice.
+pub fn grid_spacing(scale: f32) -> f32 {
+    10f32.powf((MIN_GRID_PIXELS * scale).log10().ceil())
+}
+
+pub struct SnappingPlugin;
+
+impl Plugin for SnappingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Snapping>()
+            .add_systems(Update, (snapping_ui, draw_grid));
+    }
+}
+
+fn snapping_ui(
+    mut contexts: EguiContexts,
+    mut snapping: ResMut<Snapping>,
+    camera_query: Query<&OrthographicProjection, With<MainCamera>>,
+) {
+    let spacing = grid_spacing(camera_query.single().scale);
+    egui::Window::new("Grid & Snapping")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.checkbox(&mut snapping.show_grid, "Show grid");
+            ui.label(format!("Grid spacing: {}", spacing));
+            ui.separator();
+            ui.checkbox(&mut snapping.to_grid, "Snap to grid");
+            ui.checkbox(&mut snapping.to_edges, "Snap to body edges");
+            ui.checkbox(&mut snapping.to_angle, "Snap angles");
+            ui.add_enabled(
+                snapping.to_angle,
+                egui::Slider::new(&mut snapping.angle_step_degrees, 1.0..=90.)
+                    .text("Angle step (°)"),
+            );
+        });
+}
+
+// minor lines every grid step and brighter major lines every 10, over whatever the camera can see
+fn draw_grid(
+    snapping: Res<Snapping>,
+    camera_query: Query<(&GlobalTransform, &OrthographicProjection), With<MainCamera>>,
+    mut gizmos: Gizmos,
+) {
+    if !snapping.show_grid {
+        return;
+    }
+    let (camera_transform, projection) = camera_query.single();
+    let center = camera_transform.translation().truncate();
+    let min = center + projection.area.min;
+    let max = center + projection.area.max;
+    let spacing = grid_spacing(projection.scale);
+
+    let minor = Color::rgba(1., 1., 1., 0.06);
+    let major = Color::rgba(1., 1., 1., 0.15);
+    let axis = Color::rgba(1., 1., 1., 0.3);
+    let line_color = |index: i64| {
+        if index == 0 {
+            axis
+        } else if index % 10 == 0 {
+            major
+        } else {
+            minor
+        }
+    };
+
+    for i in (min.x / spacing).floor() as i64..=(max.x / spacing).ceil() as i64 {
+        let x = i as f32 * spacing;
+        gizmos.line_2d(Vec2::new(x, min.y), Vec2::new(x, max.y), line_color(i));
+    }
+    for i in (min.y / spacing).floor() as i64..=(max.y / spacing).ceil() as i64 {
+        let y = i as f32 * spacing;
+        gizmos.line_2d(Vec2::new(min.x, y), Vec2::new(max.x, y), line_color(i));
+    }
+}
@@ -0,0 +1,453 @@
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::render::view::NoFrustumCulling;
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
+use bevy::utils::HashMap;
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_egui::{egui, EguiContexts};
+use bevy_rapier2d::prelude::*;
+
+use crate::snapping::Snapping;
+use crate::{cursor_world_position, EguiUnfocusedSystemSet, MainCamera, Tool, Tools};
+
+// lattices bigger than this get coarser instead
+const MAX_NODES: usize = 400;
+// nodes are a bit smaller than the gaps between them so neighbours don't rub
+const NODE_RADIUS: f32 = 0.45;
+// past this the springs overshoot in one step and the whole thing explodes
+const MAX_FREQUENCY: f32 = 6.;
+const MAX_DAMPING_RATIO: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoftShape {
+    Box,
+    Ball,
+}
+
+/// What the soft body tool makes next
+#[derive(Resource)]
+pub struct SoftBodySettings {
+    pub shape: SoftShape,
+    /// distance between lattice nodes
+    pub spacing: f32,
+    /// how fast the springs wobble in hz, stiffer at higher values
+    pub frequency: f32,
+    /// 0 wobbles forever, 0.5 settles in a couple of wobbles
+    pub damping_ratio: f32,
+    pub color: Color,
+}
+
+impl Default for SoftBodySettings {
+    fn default() -> Self {
+        Self {
+            shape: SoftShape::Box,
+            spacing: 4.,
+            frequency: 4.,
+            damping_ratio: 0.3,
+            color: Color::rgb(0.45, 0.85, 0.4),
+        }
+    }
+}
+
+struct SoftSpring {
+    a: usize,
+    b: usize,
+    rest_length: f32,
+}
+
+/// Lattice of small bodies held together by springs, drawn as one mesh stretched over them.
+/// Lives on its own entity, the nodes are separate top-level bodies.
+#[derive(Component)]
+pub struct SoftBody {
+    nodes: Vec<Entity>,
+    springs: Vec<SoftSpring>,
+    pub frequency: f32,
+    pub damping_ratio: f32,
+}
+
+/// One of the bodies making up a `SoftBody`
+#[derive(Component)]
+pub struct SoftBodyNode {
+    pub soft_body: Entity,
+}
+
+/// The shape being dragged out with the soft body tool
+#[derive(Resource, Default)]
+struct SoftBodyDraft {
+    start: Option<Vec2>,
+    end: Vec2,
+}
+
+pub struct SoftBodyPlugin;
+
+impl Plugin for SoftBodyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SoftBodySettings>()
+            .init_resource::<SoftBodyDraft>()
+            .add_systems(Update, draw_soft_body.in_set(EguiUnfocusedSystemSet))
+            .add_systems(
+                Update,
+                (
+                    soft_body_ui,
+                    draw_soft_body_draft.after(draw_soft_body),
+                    (clean_up_soft_bodies, simulate_soft_bodies).chain(),
+                ),
+            )
+            .add_systems(
+                PostUpdate,
+                update_soft_body_meshes
+                    .after(PhysicsSet::Writeback)
+                    .before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+fn inside(shape: SoftShape, rect: Rect, point: Vec2) -> bool {
+    match shape {
+        SoftShape::Box => rect.contains(point),
+        SoftShape::Ball => {
+            let offset = (point - rect.center()) / rect.half_size();
+            offset.length_squared() <= 1.
+        }
+    }
+}
+
+/// Fills `rect` (or the ellipse inside it) with nodes and springs
+pub fn spawn_soft_body(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    settings: &SoftBodySettings,
+    rect: Rect,
+) -> Option<Entity> {
+    let mut spacing = settings.spacing;
+    while ((rect.width() / spacing) as usize + 1) * ((rect.height() / spacing) as usize + 1)
+        > MAX_NODES
+    {
+        spacing *= 1.25;
+    }
+    let columns = (rect.width() / spacing) as i32 + 1;
+    let rows = (rect.height() / spacing) as i32 + 1;
+    // centers the lattice in the rect
+    let margin = (rect.size() - Vec2::new(columns as f32 - 1., rows as f32 - 1.) * spacing) / 2.;
+
+    let mut positions = Vec::new();
+    let mut lattice: HashMap<IVec2, usize> = HashMap::new();
+    for row in 0..rows {
+        for column in 0..columns {
+            let position = rect.min + margin + Vec2::new(column as f32, row as f32) * spacing;
+            if inside(settings.shape, rect, position) {
+                lattice.insert(IVec2::new(column, row), positions.len());
+                positions.push(position);
+            }
+        }
+    }
+    if positions.len() < 3 {
+        return None;
+    }
+
+    // edges, diagonals both ways so cells can't shear flat
+    let mut springs = Vec::new();
+    let mut triangles: Vec<u32> = Vec::new();
+    for (cell, &a) in lattice.iter() {
+        for offset in [
+            IVec2::new(1, 0),
+            IVec2::new(0, 1),
+            IVec2::new(1, 1),
+            IVec2::new(1, -1),
+        ] {
+            if let Some(&b) = lattice.get(&(*cell + offset)) {
+                springs.push(SoftSpring {
+                    a,
+                    b,
+                    rest_length: positions[a].distance(positions[b]),
+                });
+            }
+        }
+        // whichever triangles of this cell have all their corners, counter-clockwise
+        let corner = |x: i32, y: i32| lattice.get(&(*cell + IVec2::new(x, y))).copied();
+        match (corner(1, 0), corner(1, 1), corner(0, 1)) {
+            (Some(right), Some(up_right), Some(up)) => {
+                triangles.extend([a, right, up_right, a, up_right, up].map(|i| i as u32))
+            }
+            (Some(right), Some(up_right), None) => {
+                triangles.extend([a, right, up_right].map(|i| i as u32))
+            }
+            (Some(right), None, Some(up)) => triangles.extend([a, right, up].map(|i| i as u32)),
+            (None, Some(up_right), Some(up)) => {
+                triangles.extend([a, up_right, up].map(|i| i as u32))
+            }
+            _ => {}
+        }
+        // the lower right triangle of the cell to the left, when its own corner is missing
+        if !lattice.contains_key(&(*cell + IVec2::new(-1, 0))) {
+            if let (Some(up), Some(up_left)) = (corner(0, 1), corner(-1, 1)) {
+                triangles.extend([a, up, up_left].map(|i| i as u32));
+            }
+        }
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        positions
+            .iter()
+            .map(|position| position.extend(0.).to_array())
+            .collect::<Vec<_>>(),
+    );
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0., 0., 1.]; positions.len()]);
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_UV_0,
+        positions
+            .iter()
+            .map(|position| {
+                let uv = (*position - rect.min) / rect.size();
+                [uv.x, 1. - uv.y]
+            })
+            .collect::<Vec<_>>(),
+    );
+    mesh.set_indices(Some(Indices::U32(triangles)));
+
+    let soft_body = commands
+        .spawn((
+            MaterialMesh2dBundle {
+                mesh: Mesh2dHandle(meshes.add(mesh)),
+                material: materials.add(ColorMaterial::from(settings.color)),
+                ..default()
+            },
+            // the vertices move every frame, bevy only works out bounds once
+            NoFrustumCulling,
+        ))
+        .id();
+    let nodes = positions
+        .iter()
+        .map(|position| {
+            commands
+                .spawn((
+                    TransformBundle::from_transform(Transform::from_translation(
+                        position.extend(0.),
+                    )),
+                    RigidBody::Dynamic,
+                    Collider::ball(spacing * NODE_RADIUS),
+                    LockedAxes::ROTATION_LOCKED,
+                    Velocity::zero(),
+                    ExternalImpulse::default(),
+                    ReadMassProperties::default(),
+                    SoftBodyNode { soft_body },
+                ))
+                .id()
+        })
+        .collect();
+    commands.entity(soft_body).insert(SoftBody {
+        nodes,
+        springs,
+        frequency: settings.frequency,
+        damping_ratio: settings.damping_ratio,
+    });
+    Some(soft_body)
+}
+
+// drag with the soft body tool to fill a box or ball
+fn draw_soft_body(
+    mut commands: Commands,
+    buttons: Res<Input<MouseButton>>,
+    tool_res: Res<Tools>,
+    snapping: Res<Snapping>,
+    settings: Res<SoftBodySettings>,
+    rapier_context: Res<RapierContext>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform, &OrthographicProjection), With<MainCamera>>,
+    mut draft: ResMut<SoftBodyDraft>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    if tool_res.current_tool != Tool::SoftBody {
+        draft.start = None;
+        return;
+    }
+    let (camera, camera_transform, projection) = camera_query.single();
+    let Some(world_position) = cursor_world_position(q_window.single(), camera, camera_transform)
+    else {
+        return;
+    };
+    let point = snapping.snap_point(
+        world_position,
+        projection.scale,
+        &rapier_context,
+        QueryFilter::default(),
+    );
+
+    if buttons.just_pressed(MouseButton::Left) {
+        draft.start = Some(point);
+    }
+    draft.end = point;
+    if !buttons.just_released(MouseButton::Left) {
+        return;
+    }
+    let Some(start) = draft.start.take() else {
+        return;
+    };
+    spawn_soft_body(
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+        &settings,
+        Rect::from_corners(start, point),
+    );
+}
+
+fn draw_soft_body_draft(
+    draft: Res<SoftBodyDraft>,
+    settings: Res<SoftBodySettings>,
+    mut gizmos: Gizmos,
+) {
+    let Some(start) = draft.start else {
+        return;
+    };
+    let rect = Rect::from_corners(start, draft.end);
+    match settings.shape {
+        SoftShape::Box => gizmos.rect_2d(rect.center(), 0., rect.size(), settings.color),
+        SoftShape::Ball => {
+            // gizmos can only do circles, so trace the ellipse by hand
+            let points = (0..=32).map(|i| {
+                rect.center()
+                    + Vec2::from_angle(std::f32::consts::TAU * i as f32 / 32.) * rect.half_size()
+            });
+            gizmos.linestrip_2d(points, settings.color);
+        }
+    }
+}
+
+// a soft body missing a node is broken, and nodes without their soft body are just loose balls,
+// so either going takes the rest with it
+fn clean_up_soft_bodies(
+    mut commands: Commands,
+    mut removed: RemovedComponents<SoftBody>,
+    soft_bodies: Query<(Entity, &SoftBody)>,
+    nodes: Query<(Entity, &SoftBodyNode)>,
+) {
+    for removed in removed.read() {
+        for (entity, node) in nodes.iter() {
+            if node.soft_body == removed {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+    for (entity, soft_body) in soft_bodies.iter() {
+        if soft_body.nodes.iter().any(|node| !nodes.contains(*node)) {
+            for node in soft_body.nodes.iter().filter(|node| nodes.contains(**node)) {
+                commands.entity(*node).despawn_recursive();
+            }
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+// damped springs between nodes, tuned by frequency so they wobble the same whatever the node mass
+fn simulate_soft_bodies(
+    time: Res<Time>,
+    soft_bodies: Query<&SoftBody>,
+    mut nodes: Query<(
+        &GlobalTransform,
+        &Velocity,
+        &ReadMassProperties,
+        &mut ExternalImpulse,
+    )>,
+) {
+    let dt = time.delta_seconds();
+    for soft_body in soft_bodies.iter() {
+        let omega = std::f32::consts::TAU * soft_body.frequency.min(MAX_FREQUENCY);
+        let damping_ratio = soft_body.damping_ratio.min(MAX_DAMPING_RATIO);
+        for spring in soft_body.springs.iter() {
+            let Ok([a, b]) =
+                nodes.get_many_mut([soft_body.nodes[spring.a], soft_body.nodes[spring.b]])
+            else {
+                continue;
+            };
+            let (transform_a, velocity_a, mass_a, mut impulse_a) = a;
+            let (transform_b, velocity_b, mass_b, mut impulse_b) = b;
+            // mass properties are only read back after the first step
+            if mass_a.mass <= 0. || mass_b.mass <= 0. {
+                continue;
+            }
+            let reduced_mass = mass_a.mass * mass_b.mass / (mass_a.mass + mass_b.mass);
+            let stiffness = reduced_mass * omega * omega;
+            let damping = 2. * reduced_mass * damping_ratio * omega;
+
+            let delta = transform_b.translation().truncate() - transform_a.translation().truncate();
+            let length = delta.length();
+            if length < f32::EPSILON {
+                continue;
+            }
+            let direction = delta / length;
+            let stretch_speed = (velocity_b.linvel - velocity_a.linvel).dot(direction);
+            let force =
+                direction * (stiffness * (length - spring.rest_length) + damping * stretch_speed);
+            impulse_a.impulse += force * dt;
+            impulse_b.impulse -= force * dt;
+        }
+    }
+}
+
+// vertices follow the nodes, the soft body entity itself never moves from the origin
+fn update_soft_body_meshes(
+    soft_bodies: Query<(&SoftBody, &Mesh2dHandle)>,
+    nodes: Query<&Transform, With<SoftBodyNode>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    for (soft_body, mesh) in soft_bodies.iter() {
+        let Some(mesh) = meshes.get_mut(&mesh.0) else {
+            continue;
+        };
+        let Ok(positions) = soft_body
+            .nodes
+            .iter()
+            .map(|node| {
+                nodes
+                    .get(*node)
+                    .map(|transform| transform.translation.truncate().extend(0.).to_array())
+            })
+            .collect::<Result<Vec<_>, _>>()
+        else {
+            continue;
+        };
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    }
+}
+
+fn soft_body_ui(
+    mut contexts: EguiContexts,
+    tool_res: Res<Tools>,
+    mut settings: ResMut<SoftBodySettings>,
+    mut soft_bodies: Query<&mut SoftBody>,
+) {
+    if tool_res.current_tool != Tool::SoftBody {
+        return;
+    }
+    egui::Window::new("Soft body").show(contexts.ctx_mut(), |ui| {
+        ui.label("Drag to fill a shape with jelly.");
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut settings.shape, SoftShape::Box, "Box");
+            ui.selectable_value(&mut settings.shape, SoftShape::Ball, "Ball");
+        });
+        ui.add(egui::Slider::new(&mut settings.spacing, 2.0..=10.).text("Node spacing"));
+        ui.add(
+            egui::Slider::new(&mut settings.frequency, 0.5..=MAX_FREQUENCY).text("Stiffness (Hz)"),
+        );
+        ui.add(
+            egui::Slider::new(&mut settings.damping_ratio, 0.0..=MAX_DAMPING_RATIO).text("Damping"),
+        );
+        ui.horizontal(|ui| {
+            ui.label("Color");
+            let mut color = settings.color.as_rgba_f32();
+            if ui.color_edit_button_rgba_unmultiplied(&mut color).changed() {
+                settings.color = Color::rgba(color[0], color[1], color[2], color[3]);
+            }
+        });
+        if !soft_bodies.is_empty() && ui.button("Apply stiffness to all").clicked() {
+            for mut soft_body in soft_bodies.iter_mut() {
+                soft_body.frequency = settings.frequency;
+                soft_body.damping_ratio = settings.damping_ratio;
+            }
+        }
+    });
+}
@@ -0,0 +1,328 @@
+use std::sync::Arc;
+
+use bevy::audio::{AddAudioSource, AudioPlugin, Decodable, Source, Volume};
+use bevy::prelude::*;
+use bevy::utils::{Duration, HashMap};
+use bevy_egui::{egui, EguiContexts};
+use bevy_rapier2d::prelude::*;
+use bevy_turborand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::body_material::BodyMaterial;
+use crate::inspector::body_of_collider;
+use crate::ragdoll::RagdollPart;
+
+// contacts softer than this are silent and dont make events
+const SOUND_EVENT_THRESHOLD: f32 = 500.;
+// an impact this big (in newton seconds) plays at full volume
+const LOUD_IMPULSE: f32 = 300.;
+// a contact that was already pushing gets another sound if the force jumps this much
+const RETRIGGER_RATIO: f32 = 2.;
+// so spawning a pile of cubes doesn't start a hundred sounds in one frame
+const MAX_NEW_SOUNDS_PER_FRAME: usize = 4;
+const SAMPLE_RATE: u32 = 44100;
+
+/// Saved with the settings
+#[derive(Resource, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SoundSettings {
+    /// 0 to 1, multiplies every collision sound
+    pub volume: f32,
+    /// most collision sounds playing at once
+    pub max_voices: usize,
+}
+
+impl Default for SoundSettings {
+    fn default() -> Self {
+        Self {
+            volume: 0.5,
+            max_voices: 16,
+        }
+    }
+}
+
+/// A short knock made up in code, one per material
+#[derive(Asset, TypePath, Clone)]
+pub struct ImpactSample {
+    samples: Arc<[f32]>,
+}
+
+pub struct ImpactDecoder {
+    samples: Arc<[f32]>,
+    index: usize,
+}
+
+impl Iterator for ImpactDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.samples.get(self.index).copied();
+        self.index += 1;
+        sample
+    }
+}
+
+impl Source for ImpactDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(Duration::from_secs_f32(
+            self.samples.len() as f32 / SAMPLE_RATE as f32,
+        ))
+    }
+}
+
+impl Decodable for ImpactSample {
+    type DecoderItem = f32;
+    type Decoder = ImpactDecoder;
+
+    fn decoder(&self) -> ImpactDecoder {
+        ImpactDecoder {
+            samples: self.samples.clone(),
+            index: 0,
+        }
+    }
+}
+
+impl ImpactSample {
+    /// Decaying partials plus a burst of noise, roughly how a struck object rings
+    fn synthesize(material: BodyMaterial) -> Self {
+        // (frequency in Hz, amplitude, decay per second)
+        let (partials, noise, noise_decay): (&[(f32, f32, f32)], f32, f32) = match material {
+            BodyMaterial::Plastic => (&[(520., 0.6, 30.), (1310., 0.3, 45.)], 0.3, 80.),
+            BodyMaterial::Wood => (&[(280., 0.7, 25.), (640., 0.4, 35.)], 0.4, 60.),
+            BodyMaterial::Metal => (
+                &[(820., 0.5, 6.), (2140., 0.35, 8.), (3910., 0.2, 12.)],
+                0.15,
+                120.,
+            ),
+            BodyMaterial::Rubber => (&[(120., 0.9, 40.)], 0.1, 100.),
+            BodyMaterial::Ice => (&[(1500., 0.4, 25.), (3600., 0.3, 35.)], 0.4, 90.),
+            BodyMaterial::Stone => (&[(210., 0.5, 35.), (470., 0.3, 45.)], 0.7, 45.),
+            BodyMaterial::Glass => (&[(2050., 0.5, 10.), (4830., 0.3, 14.)], 0.2, 110.),
+        };
+        let length = (SAMPLE_RATE as f32 * 0.5) as usize;
+        let mut seed: u32 = 0x9e37_79b9;
+        let mut samples: Vec<f32> = (0..length)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                let ringing: f32 = partials
+                    .iter()
+                    .map(|(frequency, amplitude, decay)| {
+                        amplitude
+                            * (-decay * t).exp()
+                            * (std::f32::consts::TAU * frequency * t).sin()
+                    })
+                    .sum();
+                // xorshift, it only has to sound like noise
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                let white = seed as f32 / u32::MAX as f32 * 2. - 1.;
+                ringing + white * noise * (-noise_decay * t).exp()
+            })
+            .collect();
+        // a couple of milliseconds of fade in so it doesn't click
+        let fade = (SAMPLE_RATE / 500) as usize;
+        for (i, sample) in samples.iter_mut().take(fade).enumerate() {
+            *sample *= i as f32 / fade as f32;
+        }
+        let peak = samples
+            .iter()
+            .fold(0f32, |peak, sample| peak.max(sample.abs()));
+        for sample in samples.iter_mut() {
+            *sample *= 0.9 / peak;
+        }
+        Self {
+            samples: samples.into(),
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+struct ImpactSamples(HashMap<BodyMaterial, Handle<ImpactSample>>);
+
+/// How hard each touching pair of colliders pushed last step
+#[derive(Resource, Default)]
+struct ContactForces(HashMap<(Entity, Entity), f32>);
+
+/// On the entities playing collision sounds, so we can count them
+#[derive(Component)]
+struct CollisionSound;
+
+pub struct SoundPlugin;
+
+impl Plugin for SoundPlugin {
+    fn build(&self, app: &mut App) {
+        // the settings plugin has usually put the saved ones in already
+        app.init_resource::<SoundSettings>()
+            // even headless, so the benchmark pays for the extra events like a real run does
+            .add_systems(
+                PostUpdate,
+                enable_sound_events.before(PhysicsSet::SyncBackend),
+            );
+        // headless runs have no audio, so stay quiet
+        if !app.is_plugin_added::<AudioPlugin>() {
+            return;
+        }
+        app.add_audio_source::<ImpactSample>()
+            .init_resource::<ImpactSamples>()
+            .init_resource::<ContactForces>()
+            .add_systems(Startup, make_impact_samples)
+            .add_systems(Update, (play_collision_sounds, sound_ui));
+    }
+}
+
+fn make_impact_samples(
+    mut samples: ResMut<ImpactSamples>,
+    mut assets: ResMut<Assets<ImpactSample>>,
+) {
+    for material in BodyMaterial::ALL {
+        samples
+            .0
+            .insert(material, assets.add(ImpactSample::synthesize(material)));
+    }
+}
+
+// every collider reports contact forces, at our threshold or whatever lower one it already had.
+// health and ragdolls check their own limits on the events, so a lower threshold doesn't hurt them.
+// runs after Update so it sees the thresholds they put in there
+fn enable_sound_events(
+    mut commands: Commands,
+    colliders: Query<
+        (
+            Entity,
+            Option<&ActiveEvents>,
+            Option<&ContactForceEventThreshold>,
+        ),
+        (
+            With<Collider>,
+            Or<(Added<Collider>, Changed<ContactForceEventThreshold>)>,
+        ),
+    >,
+) {
+    for (entity, events, threshold) in colliders.iter() {
+        let events = events.copied().unwrap_or(ActiveEvents::empty());
+        if !events.contains(ActiveEvents::CONTACT_FORCE_EVENTS) {
+            commands
+                .entity(entity)
+                .insert(events | ActiveEvents::CONTACT_FORCE_EVENTS);
+        }
+        if threshold.map_or(true, |threshold| threshold.0 > SOUND_EVENT_THRESHOLD) {
+            commands
+                .entity(entity)
+                .insert(ContactForceEventThreshold(SOUND_EVENT_THRESHOLD));
+        }
+    }
+}
+
+// resting contacts keep reporting force every step, so only new contacts and sudden jumps count
+fn play_collision_sounds(
+    mut commands: Commands,
+    mut contact_force_events: EventReader<ContactForceEvent>,
+    mut contact_forces: ResMut<ContactForces>,
+    rapier_context: Res<RapierContext>,
+    parts: Query<&RagdollPart>,
+    materials: Query<&BodyMaterial>,
+    playing: Query<(), With<CollisionSound>>,
+    samples: Res<ImpactSamples>,
+    settings: Res<SoundSettings>,
+    mut global_rng: ResMut<GlobalRng>,
+) {
+    let mut forces = HashMap::new();
+    let mut impacts: Vec<(f32, BodyMaterial)> = Vec::new();
+    for event in contact_force_events.read() {
+        let pair = if event.collider1 < event.collider2 {
+            (event.collider1, event.collider2)
+        } else {
+            (event.collider2, event.collider1)
+        };
+        let force = event.total_force_magnitude;
+        forces.insert(pair, force);
+        if contact_forces
+            .0
+            .get(&pair)
+            .is_some_and(|previous| force < previous * RETRIGGER_RATIO)
+        {
+            continue;
+        }
+
+        let impulse = force * rapier_context.integration_parameters.dt;
+        let loudness = (impulse / LOUD_IMPULSE).sqrt().min(1.);
+        // each side sounds like what it's made of, bodies without a material are plastic
+        let mut sides: Vec<BodyMaterial> = [pair.0, pair.1]
+            .iter()
+            .filter_map(|collider| {
+                materials
+                    .get(body_of_collider(&rapier_context, &parts, *collider))
+                    .ok()
+                    .copied()
+            })
+            .collect();
+        sides.dedup();
+        if sides.is_empty() {
+            sides.push(BodyMaterial::default());
+        }
+        for material in sides {
+            impacts.push((loudness, material));
+        }
+    }
+    // no events means no step happened, so keep what we had
+    if !forces.is_empty() {
+        contact_forces.0 = forces;
+    }
+
+    if settings.volume <= 0. {
+        return;
+    }
+    let free_voices = settings.max_voices.saturating_sub(playing.iter().count());
+    impacts.sort_by(|a, b| b.0.total_cmp(&a.0));
+    for (loudness, material) in impacts
+        .into_iter()
+        .take(free_voices.min(MAX_NEW_SOUNDS_PER_FRAME))
+    {
+        let Some(sample) = samples.0.get(&material) else {
+            continue;
+        };
+        // harder hits ring a little lower, and no two knocks are quite the same
+        let speed = (1.15 - 0.3 * loudness) * (0.95 + 0.1 * global_rng.f32());
+        commands.spawn((
+            AudioSourceBundle {
+                source: sample.clone(),
+                settings: PlaybackSettings::DESPAWN
+                    .with_volume(Volume::new_relative(loudness * settings.volume))
+                    .with_speed(speed),
+            },
+            CollisionSound,
+        ));
+    }
+}
+
+fn sound_ui(mut contexts: EguiContexts, mut settings: ResMut<SoundSettings>) {
+    let mut edited = settings.clone();
+
+    egui::Window::new("Sound")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Volume");
+                ui.add(egui::Slider::new(&mut edited.volume, 0.0..=1.0));
+            });
+            ui.horizontal(|ui| {
+                ui.label("Most sounds at once");
+                ui.add(egui::Slider::new(&mut edited.max_voices, 1..=64));
+            });
+        });
+
+    settings.set_if_neq(edited);
+}
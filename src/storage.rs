@@ -0,0 +1,57 @@
+//! Small text blobs that should survive a restart. They're files in the platform's
+//! config folder on native, and `localStorage` entries in the browser.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+
+/// Where our config files live, e.g. `~/.config/simulo` on Linux
+#[cfg(not(target_arch = "wasm32"))]
+pub fn config_dir() -> Option<PathBuf> {
+    let base = if cfg!(target_os = "windows") {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        std::env::var_os("HOME").map(|home| {
+            PathBuf::from(home)
+                .join("Library")
+                .join("Application Support")
+        })
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    };
+    base.map(|base| base.join("simulo"))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load(key: &str) -> Option<String> {
+    std::fs::read_to_string(config_dir()?.join(format!("{}.ron", key))).ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save(key: &str, contents: &str) -> Result<(), String> {
+    let dir = config_dir().ok_or("Couldn't find a config folder")?;
+    std::fs::create_dir_all(&dir)
+        .map_err(|err| format!("Couldn't make {}: {}", dir.display(), err))?;
+    let path = dir.join(format!("{}.ron", key));
+    std::fs::write(&path, contents)
+        .map_err(|err| format!("Couldn't write {}: {}", path.display(), err))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn load(key: &str) -> Option<String> {
+    local_storage()?.get_item(&format!("simulo_{}", key)).ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn save(key: &str, contents: &str) -> Result<(), String> {
+    local_storage()
+        .ok_or("localStorage isn't available")?
+        .set_item(&format!("simulo_{}", key), contents)
+        .map_err(|err| format!("Couldn't save {}: {:?}", key, err))
+}
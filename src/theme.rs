@@ -0,0 +1,196 @@
+use bevy::prelude::*;
+use bevy_egui::egui::{self, Color32, FontFamily, FontId, Rounding, Stroke, TextStyle};
+use bevy_egui::{EguiContexts, EguiSettings};
+use serde::{Deserialize, Serialize};
+
+const REGULAR_FONT: &str = "Urbanist-Regular";
+const HEADING_FONT: &str = "Urbanist-SemiBold";
+
+pub const UI_SCALES: [f32; 5] = [0.75, 1., 1.25, 1.5, 2.];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ThemeVariant {
+    #[default]
+    Dark,
+    Light,
+}
+
+#[derive(Resource, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    pub variant: ThemeVariant,
+    /// multiplies the size of everything egui draws
+    pub ui_scale: f32,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            variant: ThemeVariant::Dark,
+            ui_scale: 1.,
+        }
+    }
+}
+
+impl Theme {
+    /// World background to go with the UI
+    pub fn clear_color(&self) -> Color {
+        match self.variant {
+            ThemeVariant::Dark => Color::rgb(
+                0.20392156862745098,
+                0.12941176470588237,
+                0.23921568627450981,
+            ),
+            ThemeVariant::Light => Color::rgb(0.88, 0.84, 0.91),
+        }
+    }
+
+    fn visuals(&self) -> egui::Visuals {
+        // simulo purple
+        let accent = Color32::from_rgb(152, 99, 190);
+        let mut visuals = match self.variant {
+            ThemeVariant::Dark => {
+                let mut visuals = egui::Visuals::dark();
+                visuals.window_fill = Color32::from_rgb(38, 27, 45);
+                visuals.panel_fill = Color32::from_rgb(38, 27, 45);
+                visuals.extreme_bg_color = Color32::from_rgb(26, 18, 31);
+                visuals.faint_bg_color = Color32::from_rgb(46, 34, 54);
+                visuals.window_stroke = Stroke::new(1., Color32::from_rgb(72, 54, 84));
+                visuals.widgets.noninteractive.bg_fill = Color32::from_rgb(38, 27, 45);
+                visuals.widgets.inactive.weak_bg_fill = Color32::from_rgb(58, 43, 68);
+                visuals.widgets.inactive.bg_fill = Color32::from_rgb(58, 43, 68);
+                visuals.widgets.hovered.weak_bg_fill = Color32::from_rgb(78, 58, 92);
+                visuals.widgets.hovered.bg_fill = Color32::from_rgb(78, 58, 92);
+                visuals.widgets.active.weak_bg_fill = Color32::from_rgb(98, 72, 116);
+                visuals.widgets.active.bg_fill = Color32::from_rgb(98, 72, 116);
+                visuals.hyperlink_color = Color32::from_rgb(196, 160, 230);
+                visuals
+            }
+            ThemeVariant::Light => {
+                let mut visuals = egui::Visuals::light();
+                visuals.window_fill = Color32::from_rgb(250, 246, 252);
+                visuals.panel_fill = Color32::from_rgb(250, 246, 252);
+                visuals.extreme_bg_color = Color32::WHITE;
+                visuals.faint_bg_color = Color32::from_rgb(240, 232, 245);
+                visuals.window_stroke = Stroke::new(1., Color32::from_rgb(210, 196, 220));
+                visuals.widgets.inactive.weak_bg_fill = Color32::from_rgb(232, 222, 240);
+                visuals.widgets.inactive.bg_fill = Color32::from_rgb(232, 222, 240);
+                visuals.widgets.hovered.weak_bg_fill = Color32::from_rgb(220, 204, 232);
+                visuals.widgets.hovered.bg_fill = Color32::from_rgb(220, 204, 232);
+                visuals.widgets.active.weak_bg_fill = Color32::from_rgb(204, 182, 222);
+                visuals.widgets.active.bg_fill = Color32::from_rgb(204, 182, 222);
+                visuals.hyperlink_color = Color32::from_rgb(110, 60, 150);
+                visuals
+            }
+        };
+        visuals.selection.bg_fill = accent;
+        visuals.selection.stroke = Stroke::new(1., Color32::WHITE);
+        visuals.window_rounding = Rounding::same(8.);
+        visuals.menu_rounding = Rounding::same(6.);
+        for widget in [
+            &mut visuals.widgets.noninteractive,
+            &mut visuals.widgets.inactive,
+            &mut visuals.widgets.hovered,
+            &mut visuals.widgets.active,
+            &mut visuals.widgets.open,
+        ] {
+            widget.rounding = Rounding::same(4.);
+        }
+        visuals
+    }
+}
+
+pub struct ThemePlugin;
+
+impl Plugin for ThemePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Theme>()
+            .add_systems(Startup, setup_fonts)
+            .add_systems(Update, (appearance_ui, apply_theme).chain());
+    }
+}
+
+// the Urbanist fonts are bundled with the game, make them egui's default
+fn setup_fonts(mut contexts: EguiContexts) {
+    let mut fonts = egui::FontDefinitions::default();
+    fonts.font_data.insert(
+        REGULAR_FONT.to_owned(),
+        egui::FontData::from_static(include_bytes!("../assets/fonts/Urbanist-Regular.ttf")),
+    );
+    fonts.font_data.insert(
+        HEADING_FONT.to_owned(),
+        egui::FontData::from_static(include_bytes!("../assets/fonts/Urbanist-SemiBold.ttf")),
+    );
+    // the egui fonts stay behind ours as fallbacks for symbols Urbanist doesn't have
+    fonts
+        .families
+        .entry(FontFamily::Proportional)
+        .or_default()
+        .insert(0, REGULAR_FONT.to_owned());
+    let mut heading = vec![HEADING_FONT.to_owned()];
+    heading.extend(
+        fonts
+            .families
+            .get(&FontFamily::Proportional)
+            .cloned()
+            .unwrap_or_default(),
+    );
+    fonts
+        .families
+        .insert(FontFamily::Name(HEADING_FONT.into()), heading);
+    contexts.ctx_mut().set_fonts(fonts);
+}
+
+fn apply_theme(
+    mut contexts: EguiContexts,
+    theme: Res<Theme>,
+    mut egui_settings: ResMut<EguiSettings>,
+    mut clear_color: ResMut<ClearColor>,
+) {
+    if !theme.is_changed() {
+        return;
+    }
+    let ctx = contexts.ctx_mut();
+    let mut style = (*ctx.style()).clone();
+    style.visuals = theme.visuals();
+    style.text_styles.insert(
+        TextStyle::Heading,
+        FontId::new(22., FontFamily::Name(HEADING_FONT.into())),
+    );
+    style
+        .text_styles
+        .insert(TextStyle::Body, FontId::new(15., FontFamily::Proportional));
+    style.text_styles.insert(
+        TextStyle::Button,
+        FontId::new(15., FontFamily::Proportional),
+    );
+    style.spacing.item_spacing = egui::vec2(8., 5.);
+    style.spacing.button_padding = egui::vec2(8., 3.);
+    ctx.set_style(style);
+
+    egui_settings.scale_factor = theme.ui_scale as f64;
+    clear_color.0 = theme.clear_color();
+}
+
+fn appearance_ui(mut contexts: EguiContexts, mut theme: ResMut<Theme>) {
+    let mut edited = theme.clone();
+    egui::Window::new("Appearance")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Theme");
+                ui.selectable_value(&mut edited.variant, ThemeVariant::Dark, "Dark");
+                ui.selectable_value(&mut edited.variant, ThemeVariant::Light, "Light");
+            });
+            ui.horizontal(|ui| {
+                ui.label("UI scale");
+                for scale in UI_SCALES {
+                    ui.selectable_value(&mut edited.ui_scale, scale, format!("{}x", scale));
+                }
+            });
+        });
+    // only trip change detection when something actually changed
+    if edited != *theme {
+        *theme = edited;
+    }
+}
@@ -0,0 +1,466 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_egui::{egui, EguiContexts};
+use bevy_rapier2d::prelude::*;
+
+use crate::inspector::{body_of_collider, Selected};
+use crate::matter::{Matter, MatterShape};
+use crate::ragdoll::RagdollPart;
+use crate::scene::SavedShape;
+use crate::snapping::Snapping;
+use crate::{cursor_world_position, EguiUnfocusedSystemSet, MainCamera, Tool, Tools};
+
+// handle sizes are in pixels so they look the same at any zoom
+const GRIP_PIXELS: f32 = 6.;
+const ROTATE_GRIP_OFFSET_PIXELS: f32 = 24.;
+// nothing gets resized smaller than this
+const MIN_SIZE: f32 = 0.1;
+const GRIP_COLOR: Color = Color::rgb(1., 0.85, 0.3);
+
+/// The bits of the gizmo you can grab. `Move` is the body itself, it has no handle.
+#[derive(Clone, Copy, PartialEq)]
+enum Grip {
+    Move,
+    Rotate,
+    Pivot,
+    /// corner of the box, each component is -1 or 1
+    Corner(Vec2),
+}
+
+/// What we started with when a grip was grabbed. Everything is worked out from this so nothing drifts.
+struct GripDrag {
+    grip: Grip,
+    entity: Entity,
+    start_cursor: Vec2,
+    start_transform: Transform,
+    start_shape: Option<SavedShape>,
+    start_matter_size: Vec2,
+    start_min: Vec2,
+    start_max: Vec2,
+}
+
+#[derive(Resource, Default)]
+struct TransformToolState {
+    /// rotation pivot in the selected body's local space
+    pivot: Vec2,
+    /// body the pivot belongs to, it goes back to the origin when the selection changes
+    pivot_body: Option<Entity>,
+    drag: Option<GripDrag>,
+}
+
+pub struct TransformToolPlugin;
+
+impl Plugin for TransformToolPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TransformToolState>()
+            .add_systems(Update, drag_grips.in_set(EguiUnfocusedSystemSet))
+            .add_systems(Update, (draw_grips, transform_ui).after(drag_grips));
+    }
+}
+
+/// Local bounding box of a body, from its collider or failing that its drawn size
+fn local_bounds(collider: Option<&Collider>, matter: Option<&Matter>) -> (Vec2, Vec2) {
+    if let Some(collider) = collider {
+        let aabb = collider.raw.compute_local_aabb();
+        return (
+            Vec2::new(aabb.mins.x, aabb.mins.y),
+            Vec2::new(aabb.maxs.x, aabb.maxs.y),
+        );
+    }
+    let half = matter.map_or(Vec2::splat(4.), |matter| matter.size / 2.);
+    (-half, half)
+}
+
+fn grip_positions(
+    transform: &Transform,
+    min: Vec2,
+    max: Vec2,
+    pivot: Vec2,
+    scale: f32,
+) -> Vec<(Grip, Vec2)> {
+    let to_world = |local: Vec2| transform.transform_point(local.extend(0.)).truncate();
+    let center = (min + max) / 2.;
+    let up = (transform.rotation * Vec3::Y).truncate();
+    let mut grips = vec![
+        (
+            Grip::Rotate,
+            to_world(Vec2::new(center.x, max.y)) + up * ROTATE_GRIP_OFFSET_PIXELS * scale,
+        ),
+        (Grip::Pivot, to_world(pivot)),
+    ];
+    for corner in [
+        Vec2::new(-1., -1.),
+        Vec2::new(1., -1.),
+        Vec2::new(1., 1.),
+        Vec2::new(-1., 1.),
+    ] {
+        let local = center + (max - min) / 2. * corner;
+        grips.push((Grip::Corner(corner), to_world(local)));
+    }
+    grips
+}
+
+fn angle_of(v: Vec2) -> f32 {
+    v.y.atan2(v.x)
+}
+
+/// Swap in a stretched copy of the body's collider, and keep image shapes matching
+fn apply_resize(
+    commands: &mut Commands,
+    entity: Entity,
+    shape: &SavedShape,
+    matter: Option<&mut Matter>,
+    start_matter_size: Vec2,
+    factor: Vec2,
+) {
+    commands
+        .entity(entity)
+        .insert((shape.scaled(factor).to_collider(), Sleeping::default()));
+    if let Some(matter) = matter {
+        if let MatterShape::Image(_) = matter.shape {
+            matter.size = start_matter_size * factor.abs();
+        }
+    }
+}
+
+fn drag_grips(
+    mut commands: Commands,
+    buttons: Res<Input<MouseButton>>,
+    tool_res: Res<Tools>,
+    snapping: Res<Snapping>,
+    rapier_context: Res<RapierContext>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform, &OrthographicProjection), With<MainCamera>>,
+    parts: Query<&RagdollPart>,
+    selected_query: Query<Entity, With<Selected>>,
+    mut bodies: Query<
+        (
+            &mut Transform,
+            Option<&Collider>,
+            Option<&mut Matter>,
+            Option<&mut Velocity>,
+        ),
+        Without<MainCamera>,
+    >,
+    mut state: ResMut<TransformToolState>,
+) {
+    if tool_res.current_tool != Tool::Transform {
+        state.drag = None;
+        return;
+    }
+    let (camera, camera_transform, projection) = camera_query.single();
+    let Some(cursor) = cursor_world_position(q_window.single(), camera, camera_transform) else {
+        return;
+    };
+    let scale = projection.scale;
+    let selected = selected_query.iter().next();
+    if state.pivot_body != selected {
+        state.pivot_body = selected;
+        state.pivot = Vec2::ZERO;
+    }
+
+    if buttons.just_pressed(MouseButton::Left) {
+        // grab a grip on the selected body, otherwise select whatever was clicked
+        let grabbed = selected.and_then(|entity| {
+            let (transform, collider, matter, _) = bodies.get(entity).ok()?;
+            let (min, max) = local_bounds(collider, matter);
+            let local_cursor = transform
+                .compute_affine()
+                .inverse()
+                .transform_point3(cursor.extend(0.))
+                .truncate();
+            let inside = local_cursor.cmpge(min).all() && local_cursor.cmple(max).all();
+            let grip = grip_positions(transform, min, max, state.pivot, scale)
+                .into_iter()
+                .filter(|(_, position)| position.distance(cursor) < GRIP_PIXELS * 1.5 * scale)
+                .min_by_key(|(grip, _)| match grip {
+                    Grip::Pivot => 0,
+                    _ => 1,
+                })
+                .map(|(grip, _)| grip)
+                // anywhere else on the body drags it around
+                .or(inside.then_some(Grip::Move))?;
+            Some(GripDrag {
+                grip,
+                entity,
+                start_cursor: cursor,
+                start_transform: *transform,
+                start_shape: collider.and_then(SavedShape::from_collider),
+                start_matter_size: matter.map_or(Vec2::ZERO, |matter| matter.size),
+                start_min: min,
+                start_max: max,
+            })
+        });
+        match grabbed {
+            Some(drag) => state.drag = Some(drag),
+            None => {
+                for entity in selected_query.iter() {
+                    commands.entity(entity).remove::<Selected>();
+                }
+                let mut found = None;
+                rapier_context.intersections_with_point(
+                    cursor,
+                    QueryFilter::default(),
+                    |collider| {
+                        found = Some(collider);
+                        false
+                    },
+                );
+                if let Some(collider) = found {
+                    let body = body_of_collider(&rapier_context, &parts, collider);
+                    commands.entity(body).insert(Selected);
+                }
+            }
+        }
+        return;
+    }
+    if buttons.just_released(MouseButton::Left) {
+        state.drag = None;
+        return;
+    }
+    let Some(drag) = state.drag.as_ref() else {
+        return;
+    };
+    let Ok((mut transform, _, mut matter, velocity)) = bodies.get_mut(drag.entity) else {
+        state.drag = None;
+        return;
+    };
+    // it shouldn't fly off when you let go
+    if let Some(mut velocity) = velocity {
+        *velocity = Velocity::zero();
+    }
+    let start = drag.start_transform;
+    let filter = QueryFilter::default()
+        .exclude_collider(drag.entity)
+        .exclude_rigid_body(drag.entity);
+    let mut new_pivot = None;
+
+    match drag.grip {
+        Grip::Move => {
+            let moved = start.translation.truncate() + cursor - drag.start_cursor;
+            let snapped = snapping.snap_point(moved, scale, &rapier_context, filter);
+            transform.translation = snapped.extend(start.translation.z);
+        }
+        Grip::Rotate => {
+            let pivot_world = start.transform_point(state.pivot.extend(0.)).truncate();
+            let start_angle = start.rotation.to_euler(EulerRot::XYZ).2;
+            let turned = angle_of(cursor - pivot_world) - angle_of(drag.start_cursor - pivot_world);
+            let delta = snapping.snap_angle(start_angle + turned) - start_angle;
+            let rotation = Quat::from_rotation_z(delta);
+            let offset = start.translation.truncate() - pivot_world;
+            transform.translation = (pivot_world + (rotation * offset.extend(0.)).truncate())
+                .extend(start.translation.z);
+            transform.rotation = rotation * start.rotation;
+        }
+        Grip::Pivot => {
+            let local = start
+                .compute_affine()
+                .inverse()
+                .transform_point3(cursor.extend(0.))
+                .truncate();
+            new_pivot = Some(local);
+        }
+        Grip::Corner(corner) => {
+            if let Some(shape) = &drag.start_shape {
+                // opposite corner stays put
+                let center = (drag.start_min + drag.start_max) / 2.;
+                let half = (drag.start_max - drag.start_min) / 2.;
+                let anchor = center - half * corner;
+                let grabbed = center + half * corner;
+                let target = snapping.snap_point(cursor, scale, &rapier_context, filter);
+                let target = start
+                    .compute_affine()
+                    .inverse()
+                    .transform_point3(target.extend(0.))
+                    .truncate();
+                let span = grabbed - anchor;
+                let factor = ((target - anchor) / span).max(Vec2::splat(MIN_SIZE) / span.abs());
+                // scaling happens around the body origin, so shift the body to keep the anchor where it was
+                let shift = anchor - anchor * factor;
+                transform.translation = start.translation + start.rotation * shift.extend(0.);
+                apply_resize(
+                    &mut commands,
+                    drag.entity,
+                    shape,
+                    matter.as_deref_mut(),
+                    drag.start_matter_size,
+                    factor,
+                );
+            }
+        }
+    }
+    // moving it by hand should wake it up so it falls/collides straight away
+    commands.entity(drag.entity).insert(Sleeping::default());
+    if let Some(pivot) = new_pivot {
+        state.pivot = pivot;
+    }
+}
+
+fn draw_grips(
+    tool_res: Res<Tools>,
+    state: Res<TransformToolState>,
+    camera_query: Query<&OrthographicProjection, With<MainCamera>>,
+    selected_query: Query<(&Transform, Option<&Collider>, Option<&Matter>), With<Selected>>,
+    mut gizmos: Gizmos,
+) {
+    if tool_res.current_tool != Tool::Transform {
+        return;
+    }
+    let Some((transform, collider, matter)) = selected_query.iter().next() else {
+        return;
+    };
+    let scale = camera_query.single().scale;
+    let (min, max) = local_bounds(collider, matter);
+    let angle = transform.rotation.to_euler(EulerRot::XYZ).2;
+    let center = transform
+        .transform_point(((min + max) / 2.).extend(0.))
+        .truncate();
+    gizmos.rect_2d(center, angle, max - min, GRIP_COLOR);
+
+    let grip_size = Vec2::splat(GRIP_PIXELS * 2. * scale);
+    for (grip, position) in grip_positions(transform, min, max, state.pivot, scale) {
+        match grip {
+            Grip::Move => {}
+            Grip::Rotate => {
+                gizmos.circle_2d(position, GRIP_PIXELS * scale, GRIP_COLOR);
+                gizmos.line_2d(
+                    position,
+                    position
+                        - (transform.rotation * Vec3::Y).truncate()
+                            * ROTATE_GRIP_OFFSET_PIXELS
+                            * scale,
+                    GRIP_COLOR,
+                );
+            }
+            Grip::Pivot => {
+                let arm = GRIP_PIXELS * scale;
+                gizmos.line_2d(
+                    position - Vec2::X * arm,
+                    position + Vec2::X * arm,
+                    Color::CYAN,
+                );
+                gizmos.line_2d(
+                    position - Vec2::Y * arm,
+                    position + Vec2::Y * arm,
+                    Color::CYAN,
+                );
+            }
+            // can't resize without a collider
+            Grip::Corner(_) if collider.is_none() => {}
+            Grip::Corner(_) => gizmos.rect_2d(position, angle, grip_size, GRIP_COLOR),
+        }
+    }
+}
+
+fn transform_ui(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    tool_res: Res<Tools>,
+    mut state: ResMut<TransformToolState>,
+    mut selected_query: Query<
+        (
+            Entity,
+            &mut Transform,
+            Option<&Collider>,
+            Option<&mut Matter>,
+        ),
+        With<Selected>,
+    >,
+) {
+    if tool_res.current_tool != Tool::Transform {
+        return;
+    }
+    let Some((entity, mut transform, collider, mut matter)) = selected_query.iter_mut().next()
+    else {
+        return;
+    };
+
+    let mut position = transform.translation.truncate();
+    let mut degrees = transform.rotation.to_euler(EulerRot::XYZ).2.to_degrees();
+    let (min, max) = local_bounds(collider, matter.as_deref());
+    let size = max - min;
+    let mut new_size = size;
+    let mut pivot = state.pivot;
+    let mut position_edited = false;
+    let mut rotation_edited = false;
+    let mut size_edited = false;
+
+    egui::Window::new("Transform").show(contexts.ctx_mut(), |ui| {
+        egui::Grid::new("transform_grid").show(ui, |ui| {
+            ui.label("Position");
+            position_edited |= ui
+                .add(egui::DragValue::new(&mut position.x).speed(0.1).prefix("x: "))
+                .changed();
+            position_edited |= ui
+                .add(egui::DragValue::new(&mut position.y).speed(0.1).prefix("y: "))
+                .changed();
+            ui.end_row();
+
+            ui.label("Rotation");
+            rotation_edited = ui
+                .add(egui::DragValue::new(&mut degrees).speed(1.).suffix("°"))
+                .changed();
+            ui.end_row();
+
+            if collider.is_some() {
+                ui.label("Size");
+                size_edited |= ui
+                    .add(
+                        egui::DragValue::new(&mut new_size.x)
+                            .speed(0.1)
+                            .clamp_range(MIN_SIZE..=f32::MAX)
+                            .prefix("w: "),
+                    )
+                    .changed();
+                size_edited |= ui
+                    .add(
+                        egui::DragValue::new(&mut new_size.y)
+                            .speed(0.1)
+                            .clamp_range(MIN_SIZE..=f32::MAX)
+                            .prefix("h: "),
+                    )
+                    .changed();
+                ui.end_row();
+            }
+
+            ui.label("Pivot");
+            ui.add(egui::DragValue::new(&mut pivot.x).speed(0.1).prefix("x: "));
+            ui.add(egui::DragValue::new(&mut pivot.y).speed(0.1).prefix("y: "));
+            ui.end_row();
+        });
+        if ui.button("Reset pivot").clicked() {
+            pivot = Vec2::ZERO;
+        }
+        ui.label("Drag the corners to resize, the circle to rotate around the pivot, and the cross to move the pivot.");
+    });
+
+    // only touch the transform when a field was edited, writing it every frame would keep the body awake
+    let mut changed = false;
+    if position_edited {
+        transform.translation = position.extend(transform.translation.z);
+        changed = true;
+    }
+    if rotation_edited {
+        transform.rotation = Quat::from_rotation_z(degrees.to_radians());
+        changed = true;
+    }
+    if size_edited && new_size != size {
+        if let Some(shape) = collider.and_then(SavedShape::from_collider) {
+            let start_matter_size = matter.as_ref().map_or(Vec2::ZERO, |matter| matter.size);
+            apply_resize(
+                &mut commands,
+                entity,
+                &shape,
+                matter.as_deref_mut(),
+                start_matter_size,
+                new_size / size.max(Vec2::splat(f32::EPSILON)),
+            );
+            changed = true;
+        }
+    }
+    if pivot != state.pivot {
+        state.pivot = pivot;
+    }
+    if changed {
+        commands.entity(entity).insert(Sleeping::default());
+    }
+}
@@ -0,0 +1,384 @@
+use bevy::utils::HashMap;
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_egui::{egui, EguiContexts};
+use bevy_prototype_lyon::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::matter::collider_polygons;
+use crate::snapping::Snapping;
+use crate::{cursor_world_position, EguiUnfocusedSystemSet, MainCamera, Tool, Tools};
+
+const WATER_COLOR: Color = Color::rgba(0.2, 0.45, 0.9, 0.35);
+const SURFACE_COLOR: Color = Color::rgba(0.6, 0.8, 1., 0.8);
+// in front of bodies so whatever is under water gets tinted
+const WATER_Z: f32 = 0.5;
+// drags smaller than this are treated as misclicks
+const MIN_SIZE: f32 = 1.;
+
+/// Axis-aligned pool of water centered on the entity. The top edge is the surface.
+#[derive(Component, Clone)]
+pub struct WaterRegion {
+    pub size: Vec2,
+    /// kg per square meter, bodies lighter than this float. Colliders default to 1
+    pub density: f32,
+    pub linear_drag: f32,
+    pub angular_drag: f32,
+}
+
+impl Default for WaterRegion {
+    fn default() -> Self {
+        Self {
+            size: Vec2::new(100., 50.),
+            density: 1.,
+            linear_drag: 1.,
+            angular_drag: 1.,
+        }
+    }
+}
+
+impl WaterRegion {
+    fn bounds(&self, center: Vec2) -> Rect {
+        Rect::from_center_size(center, self.size)
+    }
+}
+
+/// The region being dragged out with the water tool
+#[derive(Resource, Default)]
+struct WaterDraft {
+    start: Option<Vec2>,
+    end: Vec2,
+}
+
+pub struct WaterPlugin;
+
+impl Plugin for WaterPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WaterDraft>()
+            .add_systems(Update, draw_water.in_set(EguiUnfocusedSystemSet))
+            .add_systems(
+                Update,
+                (
+                    water_ui,
+                    sync_water_shapes,
+                    apply_buoyancy,
+                    draw_water_draft.after(draw_water),
+                ),
+            );
+    }
+}
+
+pub fn spawn_water(commands: &mut Commands, center: Vec2, region: WaterRegion) -> Entity {
+    commands
+        .spawn((
+            ShapeBundle {
+                spatial: SpatialBundle::from_transform(Transform::from_translation(
+                    center.extend(WATER_Z),
+                )),
+                ..default()
+            },
+            Fill::color(WATER_COLOR),
+            Stroke::new(SURFACE_COLOR, 0.5),
+            region,
+        ))
+        .id()
+}
+
+// drag with the water tool to fill a rectangle
+fn draw_water(
+    mut commands: Commands,
+    buttons: Res<Input<MouseButton>>,
+    tool_res: Res<Tools>,
+    snapping: Res<Snapping>,
+    rapier_context: Res<RapierContext>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform, &OrthographicProjection), With<MainCamera>>,
+    mut draft: ResMut<WaterDraft>,
+) {
+    if tool_res.current_tool != Tool::Water {
+        draft.start = None;
+        return;
+    }
+    let (camera, camera_transform, projection) = camera_query.single();
+    let Some(world_position) = cursor_world_position(q_window.single(), camera, camera_transform)
+    else {
+        return;
+    };
+    let point = snapping.snap_point(
+        world_position,
+        projection.scale,
+        &rapier_context,
+        QueryFilter::default(),
+    );
+
+    if buttons.just_pressed(MouseButton::Left) {
+        draft.start = Some(point);
+    }
+    draft.end = point;
+    if !buttons.just_released(MouseButton::Left) {
+        return;
+    }
+    let Some(start) = draft.start.take() else {
+        return;
+    };
+    let rect = Rect::from_corners(start, point);
+    if rect.width() < MIN_SIZE || rect.height() < MIN_SIZE {
+        return;
+    }
+    spawn_water(
+        &mut commands,
+        rect.center(),
+        WaterRegion {
+            size: rect.size(),
+            ..default()
+        },
+    );
+}
+
+fn draw_water_draft(draft: Res<WaterDraft>, mut gizmos: Gizmos) {
+    let Some(start) = draft.start else {
+        return;
+    };
+    let rect = Rect::from_corners(start, draft.end);
+    gizmos.rect_2d(rect.center(), 0., rect.size(), SURFACE_COLOR);
+}
+
+fn sync_water_shapes(mut regions: Query<(&WaterRegion, &mut Path), Changed<WaterRegion>>) {
+    for (region, mut path) in regions.iter_mut() {
+        *path = GeometryBuilder::build_as(&shapes::Rectangle {
+            extents: region.size,
+            origin: RectangleOrigin::Center,
+        });
+    }
+}
+
+/// Per body, what's under water this frame
+#[derive(Default)]
+struct Submerged {
+    /// square pixels
+    area: f32,
+    /// area weighted, divide by `area` for the centroid
+    moment: Vec2,
+    density: f32,
+    linear_drag: f32,
+    angular_drag: f32,
+}
+
+// buoyancy pushes up at the middle of the submerged part of each collider,
+// drag slows bodies down by how much of them is wet
+fn apply_buoyancy(
+    mut commands: Commands,
+    time: Res<Time>,
+    rapier_context: Res<RapierContext>,
+    rapier_config: Res<RapierConfiguration>,
+    regions: Query<(&WaterRegion, &GlobalTransform)>,
+    colliders: Query<(&Collider, &GlobalTransform)>,
+    mut bodies: Query<(
+        &GlobalTransform,
+        Option<&ReadMassProperties>,
+        Option<&mut ExternalImpulse>,
+    )>,
+) {
+    let dt = time.delta_seconds();
+    let scale = rapier_context.physics_scale();
+    let mut submerged: HashMap<Entity, Submerged> = HashMap::new();
+    for (region, region_transform) in regions.iter() {
+        let center = region_transform.translation().truncate();
+        let bounds = region.bounds(center);
+        rapier_context.intersections_with_shape(
+            center,
+            0.,
+            &Collider::cuboid(region.size.x / 2., region.size.y / 2.),
+            QueryFilter::only_dynamic().exclude_sensors(),
+            |collider| {
+                let Ok((shape, collider_transform)) = colliders.get(collider) else {
+                    return true;
+                };
+                let body = rapier_context.collider_parent(collider).unwrap_or(collider);
+                let affine = collider_transform.affine();
+                for polygon in collider_polygons(shape) {
+                    let world: Vec<Vec2> = polygon
+                        .iter()
+                        .map(|p| affine.transform_point3(p.extend(0.)).truncate())
+                        .collect();
+                    let Some((area, centroid)) = area_and_centroid(&clip_to_rect(&world, bounds))
+                    else {
+                        continue;
+                    };
+                    let entry = submerged.entry(body).or_default();
+                    // overlapping regions count as the densest, stickiest one
+                    entry.area += area;
+                    entry.moment += centroid * area;
+                    entry.density = entry.density.max(region.density);
+                    entry.linear_drag = entry.linear_drag.max(region.linear_drag);
+                    entry.angular_drag = entry.angular_drag.max(region.angular_drag);
+                }
+                true
+            },
+        );
+    }
+
+    for (body, wet) in submerged {
+        let Ok((body_transform, mass_props, external)) = bodies.get_mut(body) else {
+            continue;
+        };
+        // ReadMassProperties is only filled in the step after it's added
+        let Some(mass_props) = mass_props.filter(|props| props.mass > 0.) else {
+            if mass_props.is_none() {
+                commands.entity(body).insert(ReadMassProperties::default());
+            }
+            continue;
+        };
+        let Some((linvel, angvel)) = rapier_context
+            .entity2body()
+            .get(&body)
+            .and_then(|handle| rapier_context.bodies.get(*handle))
+            .map(|rigid_body| {
+                let linvel = rigid_body.linvel();
+                (Vec2::new(linvel.x, linvel.y) * scale, rigid_body.angvel())
+            })
+        else {
+            continue;
+        };
+        let center_of_mass = body_transform
+            .transform_point(mass_props.local_center_of_mass.extend(0.))
+            .truncate();
+        let centroid = wet.moment / wet.area;
+        // mass of the water pushed out of the way
+        let displaced = wet.density * wet.area / (scale * scale);
+
+        let buoyancy = -rapier_config.gravity * displaced * dt;
+        // never take away more velocity than the body has
+        let drag = (wet.linear_drag * displaced / mass_props.mass * dt).min(1.);
+        let linear = buoyancy - linvel * mass_props.mass * drag;
+        let angular_drag = (wet.angular_drag * displaced / mass_props.mass * dt).min(1.);
+        let torque = (centroid - center_of_mass).perp_dot(buoyancy)
+            - angvel * mass_props.principal_inertia * angular_drag;
+
+        match external {
+            Some(mut external) => {
+                external.impulse += linear;
+                external.torque_impulse += torque;
+            }
+            None => {
+                commands.entity(body).insert(ExternalImpulse {
+                    impulse: linear,
+                    torque_impulse: torque,
+                });
+            }
+        }
+    }
+}
+
+// Sutherland-Hodgman against each side of the rect in turn
+fn clip_to_rect(polygon: &[Vec2], rect: Rect) -> Vec<Vec2> {
+    // (axis, limit, keep the side below the limit)
+    let planes = [
+        (0, rect.min.x, false),
+        (0, rect.max.x, true),
+        (1, rect.min.y, false),
+        (1, rect.max.y, true),
+    ];
+    let mut points = polygon.to_vec();
+    for (axis, limit, below) in planes {
+        if points.is_empty() {
+            break;
+        }
+        let inside = |p: Vec2| (p[axis] <= limit) == below || p[axis] == limit;
+        let mut clipped = Vec::with_capacity(points.len() + 1);
+        for i in 0..points.len() {
+            let current = points[i];
+            let next = points[(i + 1) % points.len()];
+            if inside(current) {
+                clipped.push(current);
+            }
+            if inside(current) != inside(next) {
+                let t = (limit - current[axis]) / (next[axis] - current[axis]);
+                clipped.push(current.lerp(next, t));
+            }
+        }
+        points = clipped;
+    }
+    points
+}
+
+fn area_and_centroid(polygon: &[Vec2]) -> Option<(f32, Vec2)> {
+    if polygon.len() < 3 {
+        return None;
+    }
+    let mut signed_area = 0.;
+    let mut moment = Vec2::ZERO;
+    for i in 0..polygon.len() {
+        let a = polygon[i];
+        let b = polygon[(i + 1) % polygon.len()];
+        let cross = a.perp_dot(b);
+        signed_area += cross;
+        moment += (a + b) * cross;
+    }
+    signed_area /= 2.;
+    if signed_area.abs() < f32::EPSILON {
+        return None;
+    }
+    Some((signed_area.abs(), moment / (6. * signed_area)))
+}
+
+// only shows up with the water tool so it doesn't crowd the screen
+fn water_ui(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    tool_res: Res<Tools>,
+    mut regions: Query<(Entity, &mut WaterRegion, &mut Transform)>,
+) {
+    if tool_res.current_tool != Tool::Water {
+        return;
+    }
+    egui::Window::new("Water").show(contexts.ctx_mut(), |ui| {
+        if regions.is_empty() {
+            ui.label("Drag to fill a rectangle with water.");
+            return;
+        }
+        for (index, (entity, mut region, mut transform)) in regions.iter_mut().enumerate() {
+            if index > 0 {
+                ui.separator();
+            }
+            ui.horizontal(|ui| {
+                ui.label(format!(
+                    "Pool at {:.0}, {:.0}",
+                    transform.translation.x, transform.translation.y
+                ));
+                if ui.small_button("Delete").clicked() {
+                    commands.entity(entity).despawn_recursive();
+                }
+            });
+            // only write through when something was edited so Changed doesn't fire every frame
+            let mut edited = region.clone();
+            let mut surface = transform.translation.y + edited.size.y / 2.;
+            let mut changed = false;
+            ui.horizontal(|ui| {
+                ui.label("Size");
+                changed |= ui
+                    .add(egui::DragValue::new(&mut edited.size.x).clamp_range(MIN_SIZE..=f32::MAX))
+                    .changed();
+                changed |= ui
+                    .add(egui::DragValue::new(&mut edited.size.y).clamp_range(MIN_SIZE..=f32::MAX))
+                    .changed();
+            });
+            ui.horizontal(|ui| {
+                ui.label("Surface height");
+                changed |= ui.add(egui::DragValue::new(&mut surface)).changed();
+            });
+            changed |= ui
+                .add(egui::Slider::new(&mut edited.density, 0.0..=5.).text("Density"))
+                .changed();
+            changed |= ui
+                .add(egui::Slider::new(&mut edited.linear_drag, 0.0..=10.).text("Drag"))
+                .changed();
+            changed |= ui
+                .add(egui::Slider::new(&mut edited.angular_drag, 0.0..=10.).text("Spin drag"))
+                .changed();
+            if changed {
+                transform.translation.y = surface - edited.size.y / 2.;
+                *region = edited;
+            }
+        }
+    });
+}